@@ -0,0 +1,52 @@
+use super::morphtrack::CalCoreMorphTrack;
+
+pub struct CalCoreAnimatedMorph {
+    m_duration: f32,
+    m_listCoreTrack: Vec<CalCoreMorphTrack>,
+    m_name: String,
+    // std::string m_filename;
+}
+
+impl CalCoreAnimatedMorph {
+    pub fn new(m_duration: f32, m_listCoreTrack: Vec<CalCoreMorphTrack>) -> Self {
+        CalCoreAnimatedMorph {
+            m_duration,
+            m_listCoreTrack,
+            m_name: String::new(),
+        }
+    }
+
+    pub fn getDuration(&self) -> f32 {
+        self.m_duration
+    }
+
+    pub fn getListCoreTrack(&self) -> &Vec<CalCoreMorphTrack> {
+        &self.m_listCoreTrack
+    }
+
+    //105 cpp
+    /*****************************************************************************/
+    /** Provides access to a core track.
+     *
+     * This function returns the core track for a given morph target.
+     *
+     * @param name The name of the morph target.
+     *
+     * @return One of the following values:
+     *         \li the core track
+     *         \li \b None if the morph target has no track
+     *****************************************************************************/
+    pub fn getCoreTrack(&self, name: &str) -> Option<&CalCoreMorphTrack> {
+        self.m_listCoreTrack
+            .iter()
+            .find(|track| track.getMorphName() == name)
+    }
+
+    pub fn getName(&self) -> &str {
+        &self.m_name
+    }
+
+    pub fn setName(&mut self, name: String) {
+        self.m_name = name;
+    }
+}
@@ -7,7 +7,7 @@ pub struct CalCoreAnimation {
     // std::vector<CallbackRecord> m_listCallbacks;
     m_duration: f32,
     m_listCoreTrack: Vec<Rc<RefCell<CalCoreTrack>>>,
-    // std::string m_name;
+    m_name: String,
     // std::string m_filename;
 }
 
@@ -16,6 +16,7 @@ impl CalCoreAnimation {
         CalCoreAnimation {
             m_duration,
             m_listCoreTrack,
+            m_name: String::new(),
         }
     }
 
@@ -30,4 +31,12 @@ impl CalCoreAnimation {
     pub fn getListCoreTrackMut(&mut self) -> &mut Vec<Rc<RefCell<CalCoreTrack>>> {
         &mut self.m_listCoreTrack
     }
+
+    pub fn getName(&self) -> &str {
+        &self.m_name
+    }
+
+    pub fn setName(&mut self, name: String) {
+        self.m_name = name;
+    }
 }
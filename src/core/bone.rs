@@ -45,7 +45,7 @@ impl CalCoreBone {
             m_strName,
             m_pCoreSkeleton,
             m_parentId,
-            m_listChildId: Vec::<i32>::new(),
+            m_listChildId,
             m_translation,
             m_rotation,
             m_translationAbsolute: CalVector::<f32>::new(0.0, 0.0, 0.0),
@@ -164,6 +164,22 @@ impl CalCoreBone {
         }
     }
 
+    /// Calculates the absolute state of the bone from the absolute state of
+    /// its parent, without visiting the children.
+    pub(crate) fn calculateStateFromParent(&mut self, parent: Option<&CalCoreBone>) {
+        match parent {
+            Some(parent) => {
+                self.m_translationAbsolute = parent.getRotationAbsolute().mul(self.m_translation)
+                    + parent.getTranslationAbsolute();
+                self.m_rotationAbsolute = self.m_rotation.mul(parent.getRotationAbsolute());
+            }
+            None => {
+                self.m_translationAbsolute = self.m_translation;
+                self.m_rotationAbsolute = self.m_rotation;
+            }
+        }
+    }
+
     // 160 cpp
     pub fn initBoundingBox(&mut self) {
         use cgmath::Rotation;
@@ -1,33 +1,37 @@
 use std::fs;
-use std::io::{BufReader, Read};
-use std::path::PathBuf;
+use std::io::{self, BufReader, Read};
 
-use byteorder::{NativeEndian, ReadBytesExt};
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 
-use crate::core::datasource;
+use super::datasource::{DataSource, Endianness, SourceError};
 
-use super::datasource::{DataSource, SourceError};
-
-pub struct BufReaderSource {
-    reader: BufReader<fs::File>,
+/// A data source reading from any buffered reader, by default a file.
+pub struct BufReaderSource<R: Read = fs::File> {
+    reader: BufReader<R>,
+    endianness: Endianness,
+    position: u64,
 }
 
-impl BufReaderSource {
-    pub fn new(reader: BufReader<fs::File>) -> Self {
-        BufReaderSource { reader }
+impl<R: Read> BufReaderSource<R> {
+    pub fn new(reader: BufReader<R>) -> Self {
+        Self::with_endianness(reader, Endianness::LittleEndian)
     }
 
-    pub fn report_unused_bytes(&mut self, filename: &PathBuf) {
-        let mut buf: Vec<u8> = Vec::new();
-        if let Ok(remaining) = self.reader.read_to_end(&mut buf) {
-            if remaining > 0 {
-                println!("Warning: {} bytes left after loading {filename:?}", remaining);
-            }
+    pub fn with_endianness(reader: BufReader<R>, endianness: Endianness) -> Self {
+        BufReaderSource {
+            reader,
+            endianness,
+            position: 0,
         }
     }
+
+    /// Reads the rest of the reader, returning how many bytes were left.
+    pub fn unused_byte_count(&mut self) -> io::Result<u64> {
+        io::copy(&mut self.reader, &mut io::sink())
+    }
 }
 
-impl DataSource for BufReaderSource {
+impl<R: Read> DataSource for BufReaderSource<R> {
     fn ok(&self) -> bool {
         true
     }
@@ -35,41 +39,72 @@ impl DataSource for BufReaderSource {
     fn setError(&mut self) {}
 
     fn readBytes(&mut self, pBuffer: &mut [u8], length: usize) -> Result<(), SourceError> {
-        let count = self.reader.read(pBuffer)?;
-        if count != length {
-            println!("Error expected {} got {} bytes read", length, count);
+        if let Err(e) = self.reader.read_exact(&mut pBuffer[..length]) {
+            return Err(match e.kind() {
+                io::ErrorKind::UnexpectedEof => SourceError::IoError(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("expected {length} bytes at offset {}", self.position),
+                )),
+                _ => SourceError::IoError(e),
+            });
         }
+        self.position += length as u64;
         Ok(())
     }
 
     fn readByte(&mut self) -> Result<u8, SourceError> {
-        Ok(self.reader.read_u8()?)
+        let value = self.reader.read_u8()?;
+        self.position += 1;
+        Ok(value)
     }
 
     fn readFloat(&mut self) -> Result<f32, SourceError> {
-        Ok(self.reader.read_f32::<NativeEndian>()?)
+        let value = match self.endianness {
+            Endianness::LittleEndian => self.reader.read_f32::<LittleEndian>()?,
+            Endianness::BigEndian => self.reader.read_f32::<BigEndian>()?,
+        };
+        self.position += 4;
+        Ok(value)
     }
 
     fn readShort(&mut self) -> Result<i16, SourceError> {
-        Ok(self.reader.read_i16::<NativeEndian>()?)
+        let value = match self.endianness {
+            Endianness::LittleEndian => self.reader.read_i16::<LittleEndian>()?,
+            Endianness::BigEndian => self.reader.read_i16::<BigEndian>()?,
+        };
+        self.position += 2;
+        Ok(value)
     }
 
     fn readInteger(&mut self) -> Result<i32, SourceError> {
-        Ok(self.reader.read_i32::<NativeEndian>()?)
+        let value = match self.endianness {
+            Endianness::LittleEndian => self.reader.read_i32::<LittleEndian>()?,
+            Endianness::BigEndian => self.reader.read_i32::<BigEndian>()?,
+        };
+        self.position += 4;
+        Ok(value)
     }
 
-    fn readString(&mut self) -> Result<String, SourceError> {
-        let length = self.reader.read_i32::<NativeEndian>()?;
-        if length <= 0 || length > datasource::maxStringLength {
+    fn readStringWithMaxLength(&mut self, maxLength: usize) -> Result<String, SourceError> {
+        let length = self.readInteger()?;
+        if length <= 0 || length as usize > maxLength {
             return Err(SourceError::FormatError(format!(
-                "string length {length} fails sanity check {}",
-                datasource::maxStringLength
+                "string length {length} fails sanity check {maxLength}"
             )));
         }
 
         let mut buf: Vec<u8> = vec![0; length as usize];
         self.readBytes(buf.as_mut_slice(), length as usize)?;
 
+        // strings are stored with a trailing null
+        while buf.last() == Some(&0) {
+            buf.pop();
+        }
+
         Ok(String::from_utf8(buf)?)
     }
+
+    fn position(&self) -> Option<u64> {
+        Some(self.position)
+    }
 }
@@ -0,0 +1,791 @@
+//! Importing and exporting of BVH motion capture.
+//!
+//! A BVH file holds a joint hierarchy followed by a stream of frames, each
+//! with the values of every channel of every joint. The hierarchy can become
+//! a core skeleton of its own, or the channels can be applied to the bones of
+//! an existing skeleton with the same names. BVH rotations are relative to a
+//! rest pose without any rotation, so an existing skeleton should have that
+//! same rest pose.
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use cgmath::{InnerSpace, Rad, Rotation3};
+
+use crate::{CalQuaternion, CalVector};
+
+use super::animation::CalCoreAnimation;
+use super::bone::CalCoreBone;
+use super::keyframe::CalCoreKeyframe;
+use super::loader::{AssetKind, LoaderError, LoaderOptions};
+use super::saver::{self, SaverError};
+use super::skeleton::CalCoreSkeleton;
+use super::track::CalCoreTrack;
+use super::vfs::{self, FileSystem, NATIVE_FILE_SYSTEM};
+
+/// The deepest nesting of joints loaded, as every level is a recursive call.
+const MAX_DEPTH: usize = 256;
+
+/// A channel of a BVH joint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BvhChannel {
+    Xposition,
+    Yposition,
+    Zposition,
+    Xrotation,
+    Yrotation,
+    Zrotation,
+}
+
+impl BvhChannel {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "xposition" => Some(BvhChannel::Xposition),
+            "yposition" => Some(BvhChannel::Yposition),
+            "zposition" => Some(BvhChannel::Zposition),
+            "xrotation" => Some(BvhChannel::Xrotation),
+            "yrotation" => Some(BvhChannel::Yrotation),
+            "zrotation" => Some(BvhChannel::Zrotation),
+            _ => None,
+        }
+    }
+
+    fn isPosition(&self) -> bool {
+        matches!(
+            self,
+            BvhChannel::Xposition | BvhChannel::Yposition | BvhChannel::Zposition
+        )
+    }
+}
+
+/// A joint of a BVH hierarchy.
+#[derive(Clone, Debug)]
+pub struct BvhJoint {
+    pub name: String,
+    /// The index of the parent joint, or -1 for a root joint.
+    pub parentId: i32,
+    pub offset: CalVector<f32>,
+    pub channels: Vec<BvhChannel>,
+}
+
+/// The hierarchy and frames of a BVH file.
+#[derive(Clone, Debug)]
+pub struct BvhDocument {
+    joints: Vec<BvhJoint>,
+    frameTime: f32,
+    /// The values of every channel, frame by frame.
+    frames: Vec<Vec<f32>>,
+}
+
+/// The words of a BVH file, with the lines they are on.
+struct Tokens<'a> {
+    tokens: Vec<(&'a str, usize)>,
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(text: &'a str) -> Self {
+        let tokens = text
+            .lines()
+            .enumerate()
+            .flat_map(|(line, text)| text.split_whitespace().map(move |token| (token, line + 1)))
+            .collect();
+        Tokens { tokens, pos: 0 }
+    }
+
+    fn line(&self) -> usize {
+        match self.tokens.get(self.pos).or(self.tokens.last()) {
+            Some((_, line)) => *line,
+            None => 1,
+        }
+    }
+
+    fn error(&self, message: String) -> LoaderError {
+        LoaderError::FormatError(message).in_element(format!("line {}", self.line()))
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|(token, _)| *token)
+    }
+
+    fn next(&mut self) -> Result<&'a str, LoaderError> {
+        let token = self
+            .peek()
+            .ok_or_else(|| self.error(String::from("Unexpected end of file")))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), LoaderError> {
+        let token = self.next()?;
+        if !token.eq_ignore_ascii_case(expected) {
+            self.pos -= 1;
+            return Err(self.error(format!("Expected {expected}, found {token}")));
+        }
+        Ok(())
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, LoaderError> {
+        let token = self.next()?;
+        token.parse::<T>().map_err(|_| {
+            self.pos -= 1;
+            self.error(format!("Invalid number {token}"))
+        })
+    }
+
+    /// Returns the rest of the current line, which is how joint names are
+    /// written as they may hold spaces.
+    fn restOfLine(&mut self) -> Result<String, LoaderError> {
+        let line = self.line();
+        let mut words = Vec::new();
+        while let Some((token, tokenLine)) = self.tokens.get(self.pos)
+            && *tokenLine == line
+            && *token != "{"
+        {
+            words.push(*token);
+            self.pos += 1;
+        }
+        if words.is_empty() {
+            return Err(self.error(String::from("Missing joint name")));
+        }
+        Ok(words.join(" "))
+    }
+}
+
+/*****************************************************************************/
+/** Loads a BVH file.
+ *
+ * This function loads the hierarchy and the frames of a BVH file.
+ *
+ * @param filename The file to load.
+ *
+ * @return The document, or the reason it could not be loaded.
+ *****************************************************************************/
+pub fn loadBvh(filename: &Path) -> Result<BvhDocument, LoaderError> {
+    loadBvhFromFileSystem(&NATIVE_FILE_SYSTEM, filename)
+}
+
+/*****************************************************************************/
+/** Loads a BVH file.
+ *
+ * This function loads the hierarchy and the frames of a BVH file of a file
+ * system, such as a directory or an archive.
+ *
+ * @param fileSystem The file system to find the file in.
+ * @param filename The file to load.
+ *
+ * @return The document, or the reason it could not be loaded.
+ *****************************************************************************/
+pub fn loadBvhFromFileSystem(
+    fileSystem: &dyn FileSystem,
+    filename: &Path,
+) -> Result<BvhDocument, LoaderError> {
+    let inFile = |e: LoaderError| e.in_file(filename, AssetKind::Animation);
+    let data = vfs::readFile(fileSystem, filename).map_err(|e| inFile(e.into()))?;
+    let Ok(text) = String::from_utf8(data) else {
+        return Err(inFile(LoaderError::FormatError(
+            "BVH file is not valid UTF-8".into(),
+        )));
+    };
+    loadBvhFromText(&text).map_err(inFile)
+}
+
+/*****************************************************************************/
+/** Loads BVH from text.
+ *
+ * This function loads the hierarchy and the frames of the contents of a BVH
+ * file.
+ *
+ * @param text The contents of the file.
+ *
+ * @return The document, or the reason it could not be loaded.
+ *****************************************************************************/
+pub fn loadBvhFromText(text: &str) -> Result<BvhDocument, LoaderError> {
+    let mut tokens = Tokens::new(text);
+    let mut joints = Vec::new();
+
+    tokens.expect("HIERARCHY")?;
+    while tokens
+        .peek()
+        .is_some_and(|token| token.eq_ignore_ascii_case("ROOT"))
+    {
+        tokens.next()?;
+        loadJoint(&mut tokens, &mut joints, -1, 0)?;
+    }
+    if joints.is_empty() {
+        return Err(tokens.error(String::from("BVH hierarchy has no ROOT")));
+    }
+
+    tokens.expect("MOTION")?;
+    tokens.expect("Frames:")?;
+    let frameCount: usize = tokens.number()?;
+    tokens.expect("Frame")?;
+    tokens.expect("Time:")?;
+    let frameTime: f32 = tokens.number()?;
+    if !frameTime.is_finite() || frameTime <= 0.0 {
+        return Err(tokens.error(format!("Invalid frame time {frameTime}")));
+    }
+
+    // check the frames are all there before allocating for them
+    let channelCount: usize = joints.iter().map(|joint| joint.channels.len()).sum();
+    let valueCount = tokens.tokens.len() - tokens.pos;
+    if frameCount
+        .checked_mul(channelCount)
+        .is_none_or(|needed| needed > valueCount)
+    {
+        return Err(tokens.error(format!(
+            "{frameCount} frames of {channelCount} channels expected, {valueCount} values found"
+        )));
+    }
+
+    let mut frames = Vec::with_capacity(frameCount);
+    for _ in 0..frameCount {
+        let mut frame = Vec::with_capacity(channelCount);
+        for _ in 0..channelCount {
+            frame.push(tokens.number::<f32>()?);
+        }
+        frames.push(frame);
+    }
+
+    Ok(BvhDocument {
+        joints,
+        frameTime,
+        frames,
+    })
+}
+
+/// Loads a joint and its children, after its ROOT or JOINT keyword.
+fn loadJoint(
+    tokens: &mut Tokens,
+    joints: &mut Vec<BvhJoint>,
+    parentId: i32,
+    depth: usize,
+) -> Result<(), LoaderError> {
+    if depth >= MAX_DEPTH {
+        return Err(tokens.error(format!("Joints nested over {MAX_DEPTH} deep")));
+    }
+    let name = tokens.restOfLine()?;
+    tokens.expect("{")?;
+    tokens.expect("OFFSET")?;
+    let offset = CalVector::new(tokens.number()?, tokens.number()?, tokens.number()?);
+
+    let mut channels = Vec::new();
+    if tokens
+        .peek()
+        .is_some_and(|token| token.eq_ignore_ascii_case("CHANNELS"))
+    {
+        tokens.next()?;
+        let channelCount: usize = tokens.number()?;
+        for _ in 0..channelCount {
+            let channel = tokens.next()?;
+            channels.push(BvhChannel::parse(channel).ok_or_else(|| {
+                tokens.pos -= 1;
+                tokens.error(format!("Invalid channel {channel}"))
+            })?);
+        }
+    }
+
+    let jointId = joints.len() as i32;
+    joints.push(BvhJoint {
+        name,
+        parentId,
+        offset,
+        channels,
+    });
+
+    loop {
+        let token = tokens.next()?;
+        if token == "}" {
+            return Ok(());
+        } else if token.eq_ignore_ascii_case("JOINT") {
+            loadJoint(tokens, joints, jointId, depth + 1)?;
+        } else if token.eq_ignore_ascii_case("End") {
+            // an end site only gives the length of the last bone
+            tokens.expect("Site")?;
+            tokens.expect("{")?;
+            tokens.expect("OFFSET")?;
+            for _ in 0..3 {
+                tokens.number::<f32>()?;
+            }
+            tokens.expect("}")?;
+        } else {
+            tokens.pos -= 1;
+            return Err(tokens.error(format!(
+                "Unexpected {token} in joint {}",
+                joints[jointId as usize].name
+            )));
+        }
+    }
+}
+
+/// Returns the rotation for rotation channel values applied in the order of
+/// the channels, in the usual convention where a quaternion rotates a vector
+/// as q v q*.
+fn eulerRotation(channels: &[BvhChannel], values: &[f32]) -> CalQuaternion<f32> {
+    let mut rotation = CalQuaternion::new(1.0, 0.0, 0.0, 0.0);
+    for (channel, value) in channels.iter().zip(values) {
+        let angle = Rad(value.to_radians());
+        let axisRotation = match channel {
+            BvhChannel::Xrotation => CalQuaternion::from_angle_x(angle),
+            BvhChannel::Yrotation => CalQuaternion::from_angle_y(angle),
+            BvhChannel::Zrotation => CalQuaternion::from_angle_z(angle),
+            _ => continue,
+        };
+        rotation = rotation * axisRotation;
+    }
+    rotation.normalize()
+}
+
+/// Returns the Z, X and Y angles in degrees of a rotation made of Z, X and Y
+/// axis rotations in that order, the order BVH files are usually written in.
+fn eulerAnglesZXY(rotation: CalQuaternion<f32>) -> [f32; 3] {
+    // the matrix is column major, so m.y.z is row 2, column 1
+    let m = cgmath::Matrix3::from(rotation.normalize());
+    let x = m.y.z.clamp(-1.0, 1.0).asin();
+    let (z, y) = match m.y.z.abs() < 0.99999 {
+        true => ((-m.y.x).atan2(m.y.y), (-m.x.z).atan2(m.z.z)),
+        // at the poles only the sum of the Z and Y angles is known
+        false => (m.x.y.atan2(m.x.x), 0.0),
+    };
+    [z.to_degrees(), x.to_degrees(), y.to_degrees()]
+}
+
+impl BvhDocument {
+    pub fn getJoints(&self) -> &Vec<BvhJoint> {
+        &self.joints
+    }
+
+    pub fn getFrameCount(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn getFrameTime(&self) -> f32 {
+        self.frameTime
+    }
+
+    /// Returns the translation and rotation of a joint in a frame, in the
+    /// usual convention. Joints without position channels keep `rest`.
+    fn jointState(
+        &self,
+        frame: &[f32],
+        firstChannel: usize,
+        joint: &BvhJoint,
+        rest: CalVector<f32>,
+    ) -> (CalVector<f32>, CalQuaternion<f32>) {
+        let values = &frame[firstChannel..firstChannel + joint.channels.len()];
+        let mut translation = rest;
+        for (channel, value) in joint.channels.iter().zip(values) {
+            match channel {
+                BvhChannel::Xposition => translation.x = *value,
+                BvhChannel::Yposition => translation.y = *value,
+                BvhChannel::Zposition => translation.z = *value,
+                _ => {}
+            }
+        }
+        (translation, eulerRotation(&joint.channels, values))
+    }
+
+    /*****************************************************************************/
+    /** Loads a core skeleton from the hierarchy.
+     *
+     * This function adds a bone to the core skeleton for every joint of the
+     * hierarchy, in the order they are written. The bones are in their rest
+     * pose, which has no rotations.
+     *
+     * @param skeleton The core skeleton to add the bones to.
+     * @param options The conversions to apply while loading.
+     *****************************************************************************/
+    pub fn loadCoreSkeleton(
+        &self,
+        skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+        options: &LoaderOptions,
+    ) -> Result<(), LoaderError> {
+        let boneOffset = skeleton.borrow().getVectorCoreBone().len();
+        let identity = CalQuaternion::new(1.0, 0.0, 0.0, 0.0);
+
+        // without rotations, the bone space is just the opposite of the
+        // position of the joint
+        let mut positions: Vec<CalVector<f32>> = Vec::with_capacity(self.joints.len());
+        for joint in self.joints.iter() {
+            let (parentId, position) = match joint.parentId {
+                -1 => (-1, joint.offset),
+                parentId => (
+                    parentId + boneOffset as i32,
+                    positions[parentId as usize] + joint.offset,
+                ),
+            };
+            positions.push(position);
+
+            let (rotation, translation) = match parentId {
+                -1 => options.rotateRootTransform(identity, joint.offset),
+                _ => (identity, joint.offset),
+            };
+            let bone = CalCoreBone::new(
+                joint.name.clone(),
+                skeleton.clone(),
+                parentId,
+                Vec::new(),
+                translation,
+                rotation,
+                -position,
+                options.rotateBoneSpace(identity),
+            );
+            let boneId = skeleton
+                .borrow_mut()
+                .addCoreBone(Rc::new(RefCell::new(bone)));
+            if parentId != -1 {
+                skeleton.borrow().getVectorCoreBone()[parentId as usize]
+                    .borrow_mut()
+                    .addChildId(boneId as i32);
+            }
+        }
+
+        Ok(())
+    }
+
+    /*****************************************************************************/
+    /** Loads a core animation from the frames.
+     *
+     * This function loads a core animation with a keyframe for every frame.
+     * Joints are matched to the bones of the core skeleton by name, and
+     * joints without a bone of the same name are skipped. Joints without
+     * position channels keep the translation of their bone.
+     *
+     * @param skeleton The core skeleton to animate.
+     * @param options The conversions to apply while loading.
+     *
+     * @return The core animation, or the reason it could not be loaded.
+     *****************************************************************************/
+    pub fn loadCoreAnimation(
+        &self,
+        skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+        options: &LoaderOptions,
+    ) -> Result<Rc<RefCell<CalCoreAnimation>>, LoaderError> {
+        if self.frames.is_empty() {
+            return Err(LoaderError::FormatError(String::from(
+                "BVH file has no frames",
+            )));
+        }
+
+        let mut tracks = Vec::new();
+        let mut firstChannel = 0;
+        for joint in self.joints.iter() {
+            let jointChannel = firstChannel;
+            firstChannel += joint.channels.len();
+
+            let Some(boneId) = skeleton.borrow().getCoreBoneId(&joint.name) else {
+                continue;
+            };
+            if joint.channels.is_empty() {
+                continue;
+            }
+            let coreBone = skeleton.borrow().getCoreBone(boneId).unwrap();
+            let (boneTranslation, isRoot) = {
+                let coreBone = coreBone.borrow();
+                (*coreBone.getTranslation(), coreBone.getParentId() == -1)
+            };
+            let hasPositions = joint.channels.iter().any(BvhChannel::isPosition);
+
+            let mut keyframes = Vec::with_capacity(self.frames.len());
+            for (frameId, frame) in self.frames.iter().enumerate() {
+                let (mut translation, rotation) =
+                    self.jointState(frame, jointChannel, joint, joint.offset);
+                let mut rotation = rotation.conjugate();
+                if isRoot {
+                    (rotation, translation) = options.rotateRootTransform(rotation, translation);
+                }
+                if !hasPositions {
+                    translation = boneTranslation;
+                }
+                keyframes.push(Rc::new(CalCoreKeyframe::new(
+                    frameId as f32 * self.frameTime,
+                    translation,
+                    rotation,
+                )));
+            }
+
+            let mut track = CalCoreTrack::new(boneId, hasPositions, true, hasPositions, keyframes);
+            if options.collapse_sequences {
+                track.collapseSequences(
+                    options.translation_tolerance,
+                    options.rotation_tolerance_degrees,
+                );
+            }
+            if options.compress_tracks {
+                track.compress(
+                    options.translation_tolerance,
+                    options.rotation_tolerance_degrees,
+                    skeleton,
+                );
+            }
+            tracks.push(Rc::new(RefCell::new(track)));
+        }
+
+        let duration = self.frameTime * (self.frames.len() - 1).max(1) as f32;
+        Ok(Rc::new(RefCell::new(CalCoreAnimation::new(
+            duration, tracks,
+        ))))
+    }
+}
+
+/*****************************************************************************/
+/** Saves a core animation as BVH.
+ *
+ * This function saves the hierarchy of a core skeleton and a core animation
+ * sampled at a fixed frame time to a BVH file, see `saveBvhToString`.
+ *
+ * @param filename The name of the file to save to.
+ * @param skeleton The core skeleton the animation is for.
+ * @param animation The core animation to save.
+ * @param frameTime The time between frames, in seconds.
+ *****************************************************************************/
+pub fn saveBvh(
+    filename: &PathBuf,
+    skeleton: &CalCoreSkeleton,
+    animation: &CalCoreAnimation,
+    frameTime: f32,
+) -> Result<(), SaverError> {
+    fs::write(filename, saveBvhToString(skeleton, animation, frameTime)?)?;
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core animation as BVH text.
+ *
+ * This function returns the contents of a BVH file holding a core animation.
+ * The offsets of the joints are the translations of the bones. Root joints
+ * have position and rotation channels, and other joints rotation channels in
+ * Z, X, Y order. Bones without a track keep their rest pose in every frame.
+ *
+ * @param skeleton The core skeleton the animation is for.
+ * @param animation The core animation to save.
+ * @param frameTime The time between frames, in seconds.
+ *
+ * @return The contents of the file.
+ *****************************************************************************/
+pub fn saveBvhToString(
+    skeleton: &CalCoreSkeleton,
+    animation: &CalCoreAnimation,
+    frameTime: f32,
+) -> Result<String, SaverError> {
+    if !frameTime.is_finite() || frameTime <= 0.0 {
+        return Err(SaverError::FormatError(format!(
+            "Invalid frame time {frameTime}"
+        )));
+    }
+
+    let vectorCoreBone = skeleton.getVectorCoreBone();
+    let listChildIds = saver::childIdsFromParentIds(skeleton)?;
+
+    let mut tracks = vec![None; vectorCoreBone.len()];
+    for coreTrack in animation.getListCoreTrack() {
+        let boneId = coreTrack.borrow().getCoreBoneId();
+        let Some(track) = tracks.get_mut(boneId) else {
+            return Err(SaverError::FormatError(format!(
+                "Invalid bone id {boneId} in animation"
+            )));
+        };
+        if coreTrack.borrow().getCoreKeyframeCount() > 0 {
+            *track = Some(coreTrack.clone());
+        }
+    }
+
+    // the joints in the order they are written, which is the order of the
+    // channels in the frames
+    let mut order = Vec::with_capacity(vectorCoreBone.len());
+    let mut bvh = String::from("HIERARCHY\n");
+    for (boneId, coreBone) in vectorCoreBone.iter().enumerate() {
+        if coreBone.borrow().getParentId() == -1 {
+            saveJoint(&mut bvh, skeleton, &listChildIds, boneId, 0, &mut order)?;
+        }
+    }
+
+    let frameCount = (animation.getDuration() / frameTime).round() as usize + 1;
+    writeln!(bvh, "MOTION")?;
+    writeln!(bvh, "Frames: {frameCount}")?;
+    writeln!(bvh, "Frame Time: {frameTime}")?;
+
+    for frameId in 0..frameCount {
+        let time = (frameId as f32 * frameTime).min(animation.getDuration());
+        let mut values = Vec::new();
+        for boneId in order.iter() {
+            let coreBone = vectorCoreBone[*boneId].borrow();
+            let (translation, rotation) = match &tracks[*boneId] {
+                Some(track) => track.borrow().getState(time),
+                None => (*coreBone.getTranslation(), *coreBone.getRotation()),
+            };
+            if coreBone.getParentId() == -1 {
+                values.extend([translation.x, translation.y, translation.z]);
+            }
+            values.extend(eulerAnglesZXY(rotation.conjugate()));
+        }
+        let line: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        writeln!(bvh, "{}", line.join(" "))?;
+    }
+
+    Ok(bvh)
+}
+
+fn saveJoint(
+    bvh: &mut String,
+    skeleton: &CalCoreSkeleton,
+    listChildIds: &[Vec<i32>],
+    boneId: usize,
+    depth: usize,
+    order: &mut Vec<usize>,
+) -> Result<(), SaverError> {
+    if depth > listChildIds.len() {
+        return Err(SaverError::FormatError(format!(
+            "Bone {boneId} is its own ancestor"
+        )));
+    }
+    order.push(boneId);
+
+    let coreBone = skeleton.getVectorCoreBone()[boneId].borrow();
+    let indent = "  ".repeat(depth);
+    let translation = coreBone.getTranslation();
+    let name = match coreBone.getName().trim() {
+        "" => format!("bone{boneId}"),
+        name => name.to_string(),
+    };
+    match coreBone.getParentId() {
+        -1 => writeln!(bvh, "{indent}ROOT {name}")?,
+        _ => writeln!(bvh, "{indent}JOINT {name}")?,
+    }
+    writeln!(bvh, "{indent}{{")?;
+    writeln!(
+        bvh,
+        "{indent}  OFFSET {} {} {}",
+        translation.x, translation.y, translation.z
+    )?;
+    match coreBone.getParentId() {
+        -1 => writeln!(
+            bvh,
+            "{indent}  CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation"
+        )?,
+        _ => writeln!(bvh, "{indent}  CHANNELS 3 Zrotation Xrotation Yrotation")?,
+    }
+
+    if listChildIds[boneId].is_empty() {
+        writeln!(bvh, "{indent}  End Site")?;
+        writeln!(bvh, "{indent}  {{")?;
+        writeln!(bvh, "{indent}    OFFSET 0 0 0")?;
+        writeln!(bvh, "{indent}  }}")?;
+    }
+    for childId in listChildIds[boneId].iter() {
+        saveJoint(
+            bvh,
+            skeleton,
+            listChildIds,
+            *childId as usize,
+            depth + 1,
+            order,
+        )?;
+    }
+    writeln!(bvh, "{indent}}}")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::loader::LoaderOptions;
+    use crate::core::testdata;
+    use crate::core::vfs::TarFileSystem;
+
+    #[test]
+    fn saved_animations_load_the_same() {
+        let skeleton = testdata::skeleton();
+        let animation = testdata::animation(&skeleton);
+        let frameTime = 0.25;
+        let text = saveBvhToString(&skeleton.borrow(), &animation.borrow(), frameTime).unwrap();
+        let document = loadBvhFromText(&text).unwrap();
+        assert_eq!(document.getFrameCount(), 5);
+        assert_eq!(document.getFrameTime(), frameTime);
+
+        // the hierarchy
+        let options = LoaderOptions::default();
+        let loadedSkeleton = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+        document
+            .loadCoreSkeleton(&loadedSkeleton, &options)
+            .unwrap();
+        let (bones, loadedBones) = (skeleton.borrow(), loadedSkeleton.borrow());
+        assert_eq!(
+            bones.getVectorCoreBone().len(),
+            loadedBones.getVectorCoreBone().len()
+        );
+        for (bone, loadedBone) in bones
+            .getVectorCoreBone()
+            .iter()
+            .zip(loadedBones.getVectorCoreBone())
+        {
+            let (bone, loadedBone) = (bone.borrow(), loadedBone.borrow());
+            assert_eq!(bone.getName(), loadedBone.getName());
+            assert_eq!(bone.getParentId(), loadedBone.getParentId());
+            assert!((bone.getTranslation() - loadedBone.getTranslation()).magnitude() < 1e-5);
+        }
+
+        // the frames, where only the root keeps its translations
+        let loadedAnimation = document.loadCoreAnimation(&skeleton, &options).unwrap();
+        let (animation, loadedAnimation) = (animation.borrow(), loadedAnimation.borrow());
+        assert_eq!(animation.getDuration(), loadedAnimation.getDuration());
+        for track in animation.getListCoreTrack() {
+            let track = track.borrow();
+            let boneId = track.getCoreBoneId();
+            let loadedTrack = loadedAnimation
+                .getListCoreTrack()
+                .iter()
+                .find(|loadedTrack| loadedTrack.borrow().getCoreBoneId() == boneId)
+                .unwrap()
+                .borrow();
+            for frameId in 0..document.getFrameCount() {
+                let time = frameId as f32 * frameTime;
+                let (translation, rotation) = track.getState(time);
+                let (loadedTranslation, loadedRotation) = loadedTrack.getState(time);
+                assert!(rotation.dot(loadedRotation).abs() > 1.0 - 1e-5, "{time}");
+                if bones.getVectorCoreBone()[boneId].borrow().getParentId() == -1 {
+                    assert!(
+                        (translation - loadedTranslation).magnitude() < 1e-5,
+                        "{time}"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Returns a hierarchy of a root with a chain of joints below it.
+    fn chain(jointCount: usize) -> String {
+        let mut text = String::from("HIERARCHY\nROOT root\n{\nOFFSET 0 0 0\n");
+        for jointId in 0..jointCount {
+            text += &format!("JOINT joint{jointId}\n{{\nOFFSET 0 1 0\n");
+        }
+        text += &"}\n".repeat(jointCount + 1);
+        text + "MOTION\nFrames: 0\nFrame Time: 0.1\n"
+    }
+
+    #[test]
+    fn deeply_nested_joints_are_rejected() {
+        let document = loadBvhFromText(&chain(MAX_DEPTH - 1)).unwrap();
+        assert_eq!(document.getJoints().len(), MAX_DEPTH);
+
+        let error = loadBvhFromText(&chain(MAX_DEPTH)).err().unwrap();
+        assert!(error.to_string().contains("nested"), "{error}");
+    }
+
+    #[test]
+    fn documents_load_from_archives() {
+        let skeleton = testdata::skeleton();
+        let animation = testdata::animation(&skeleton);
+        let text = saveBvhToString(&skeleton.borrow(), &animation.borrow(), 0.25).unwrap();
+        let archive = testdata::tarArchive(&[("motion/walk.bvh", text.as_bytes())]);
+        let fileSystem = TarFileSystem::from_bytes(archive).unwrap();
+
+        let document = loadBvhFromFileSystem(&fileSystem, Path::new("motion/walk.bvh")).unwrap();
+        assert_eq!(document.getFrameCount(), 5);
+        assert!(loadBvhFromFileSystem(&fileSystem, Path::new("walk.bvh")).is_err());
+    }
+}
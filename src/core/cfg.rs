@@ -0,0 +1,248 @@
+//! Loading of model description (.cfg) files.
+//!
+//! A model description lists the files a model is made of, one `key=value`
+//! entry per line:
+//!
+//! ```text
+//! # comments start with a hash
+//! scale=0.5
+//! path=data
+//! skeleton=paladin.csf
+//! animation walk=paladin_walk.caf
+//! mesh=paladin_body.cmf
+//! material=paladin_skin.crf
+//! ```
+//!
+//! Files are found relative to the directory of the description, or to the
+//! directory given by the last `path` entry before them, which is itself
+//! relative to the description. Animations, meshes, materials and animated
+//! morphs may be given a name after their key, which they can then be looked
+//! up by in the core model.
+//!
+//! Every file is read through the file system of the core model, see the
+//! `vfs` module, so a model can be loaded from a directory or an archive alike.
+//! The map filenames of the materials are relative to the final `path`, and
+//! can be opened the same way with `CalCoreModel::openMapFile`.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::loader::{AssetKind, LoaderError};
+use super::model::CalCoreModel;
+
+/// What a model description holds besides the assets it loads into the core
+/// model.
+#[derive(Clone, Debug)]
+pub struct ModelDescription {
+    /// The scale to render the model at, 1 unless given.
+    pub scale: f32,
+    /// The directory the last files were loaded from.
+    pub path: PathBuf,
+    /// The ids of the loaded animations, in the order they are listed.
+    pub animationIds: Vec<usize>,
+    /// The ids of the loaded animated morphs, in the order they are listed.
+    pub animatedMorphIds: Vec<usize>,
+    /// The ids of the loaded meshes, in the order they are listed.
+    pub meshIds: Vec<usize>,
+    /// The ids of the loaded materials, in the order they are listed.
+    pub materialIds: Vec<i32>,
+}
+
+impl ModelDescription {
+    fn new(path: PathBuf) -> Self {
+        ModelDescription {
+            scale: 1.0,
+            path,
+            animationIds: Vec::new(),
+            animatedMorphIds: Vec::new(),
+            meshIds: Vec::new(),
+            materialIds: Vec::new(),
+        }
+    }
+}
+
+/*****************************************************************************/
+/** Loads a model description file.
+ *
+ * This function loads every file listed in a model description into a core
+ * model. The description and the files are read through the file system of
+ * the core model.
+ *
+ * @param filename The model description file.
+ * @param pCoreModel The core model to load the files into.
+ *
+ * @return The settings of the description, or the reason it could not be
+ *         loaded.
+ *****************************************************************************/
+pub fn loadCfg(
+    filename: &Path,
+    pCoreModel: &mut CalCoreModel,
+) -> Result<ModelDescription, LoaderError> {
+    let mut text = String::new();
+    pCoreModel
+        .getFileSystem()
+        .open(filename)
+        .and_then(|mut reader| reader.read_to_string(&mut text))
+        .map_err(|e| LoaderError::from(e).in_file(filename, AssetKind::Model))?;
+    let basePath = filename.parent().unwrap_or(Path::new(""));
+    loadCfgFromText(&text, basePath, pCoreModel).map_err(|e| locateCfgError(e, filename))
+}
+
+/// Records the description file in the location of an error, or in where
+/// the file the error happened in was listed.
+fn locateCfgError(error: LoaderError, filename: &Path) -> LoaderError {
+    match error.location() {
+        Some(location) if location.file.is_some() => error.in_listing(|listing| {
+            listing.file = Some(filename.to_path_buf());
+            listing.asset = Some(AssetKind::Model);
+        }),
+        _ => error.in_file(filename, AssetKind::Model),
+    }
+}
+
+/*****************************************************************************/
+/** Loads a model description.
+ *
+ * This function loads every file listed in the contents of a model
+ * description into a core model.
+ *
+ * @param text The contents of the model description.
+ * @param basePath The directory relative file names are found in.
+ * @param pCoreModel The core model to load the files into.
+ *
+ * @return The settings of the description, or the reason it could not be
+ *         loaded.
+ *****************************************************************************/
+pub fn loadCfgFromText(
+    text: &str,
+    basePath: &Path,
+    pCoreModel: &mut CalCoreModel,
+) -> Result<ModelDescription, LoaderError> {
+    let mut description = ModelDescription::new(basePath.to_path_buf());
+
+    for (lineId, line) in text.lines().enumerate() {
+        let lineNumber = lineId + 1;
+        loadCfgLine(line, basePath, pCoreModel, &mut description).map_err(|e| {
+            let line = format!("line {lineNumber}");
+            match e.location() {
+                // errors in the listed files are located in them, and the
+                // line is where they were listed
+                Some(location) if location.file.is_some() => {
+                    e.in_listing(|listing| listing.elements.insert(0, line))
+                }
+                _ => e.in_element(line),
+            }
+        })?;
+    }
+
+    Ok(description)
+}
+
+fn loadCfgLine(
+    line: &str,
+    basePath: &Path,
+    pCoreModel: &mut CalCoreModel,
+    description: &mut ModelDescription,
+) -> Result<(), LoaderError> {
+    let line = line.trim();
+
+    // skip blank and comment lines
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(());
+    }
+
+    let Some((key, value)) = line.split_once('=') else {
+        return Err(LoaderError::FormatError(format!(
+            "Invalid syntax, expected key=value: {line}"
+        )));
+    };
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(LoaderError::FormatError(format!(
+            "Missing value for {}",
+            key.trim()
+        )));
+    }
+
+    // the key may be followed by the name of the asset
+    let (key, name) = match key.trim().split_once(char::is_whitespace) {
+        Some((key, name)) => (key, Some(name.trim().to_string())),
+        None => (key.trim(), None),
+    };
+
+    let filename = description.path.join(value);
+    match (key, name) {
+        ("scale", None) => {
+            description.scale = value
+                .parse::<f32>()
+                .map_err(|_| LoaderError::FormatError(format!("Invalid scale {value}")))?;
+        }
+        ("path", None) => {
+            description.path = basePath.join(value);
+        }
+        ("skeleton", None) => {
+            pCoreModel.loadCoreSkeleton(&filename)?;
+        }
+        ("animation", name) => {
+            let id = pCoreModel.loadCoreAnimation(&filename)?;
+            description.animationIds.push(id);
+            if let Some(name) = name {
+                pCoreModel.addAnimationName(&name, id)?;
+            }
+        }
+        ("animatedmorph", name) => {
+            let id = pCoreModel.loadCoreAnimatedMorph(&filename)?;
+            description.animatedMorphIds.push(id);
+            if let Some(name) = name {
+                pCoreModel.addAnimatedMorphName(&name, id)?;
+            }
+        }
+        ("mesh", name) => {
+            let id = pCoreModel.loadCoreMesh(&filename)?;
+            description.meshIds.push(id);
+            if let Some(name) = name {
+                pCoreModel.addMeshName(&name, id)?;
+            }
+        }
+        ("material", name) => {
+            let id = pCoreModel.loadCoreMaterial(&filename)?;
+            description.materialIds.push(id);
+            if let Some(name) = name {
+                pCoreModel.addMaterialName(&name, id)?;
+            }
+        }
+        ("scale" | "path" | "skeleton", Some(_)) => {
+            return Err(LoaderError::FormatError(format!(
+                "A {key} entry cannot be named"
+            )));
+        }
+        _ => {
+            return Err(LoaderError::FormatError(format!("Unknown key {key}")));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_in_listed_files_keep_the_line() {
+        let mut coreModel = CalCoreModel::default();
+        let text = "# a mesh that is not there\nmesh=missing.cmf\n";
+        let error = loadCfgFromText(text, Path::new("no such directory"), &mut coreModel)
+            .err()
+            .unwrap();
+
+        let location = error.location().unwrap();
+        assert_eq!(
+            location.file.as_deref(),
+            Some(Path::new("no such directory/missing.cmf"))
+        );
+        assert_eq!(location.asset, Some(AssetKind::Mesh));
+        let listing = location.listing.as_deref().unwrap();
+        assert_eq!(listing.elements, ["line 2"]);
+    }
+}
@@ -0,0 +1,64 @@
+use crate::CalVector;
+
+use super::datasource::maxStringLength;
+
+#[derive(Debug)]
+pub enum SinkError {
+    IoError(std::io::Error),
+    FormatError(String),
+}
+
+impl std::fmt::Display for SinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SinkError::IoError(e) => write!(f, "I/O error: {e}"),
+            SinkError::FormatError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SinkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SinkError::IoError(e) => Some(e),
+            SinkError::FormatError(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SinkError {
+    fn from(error: std::io::Error) -> Self {
+        SinkError::IoError(error)
+    }
+}
+
+pub trait DataSink {
+    fn writeBytes(&mut self, pBuffer: &[u8]) -> Result<(), SinkError>;
+    fn writeByte(&mut self, value: u8) -> Result<(), SinkError>;
+    fn writeFloat(&mut self, value: f32) -> Result<(), SinkError>;
+    fn writeShort(&mut self, value: i16) -> Result<(), SinkError>;
+    fn writeInteger(&mut self, value: i32) -> Result<(), SinkError>;
+
+    /// Strings are written with a trailing null, as the C++ library expects.
+    fn writeString(&mut self, value: &str) -> Result<(), SinkError> {
+        let length = value.len() as i32 + 1;
+        if length > maxStringLength {
+            return Err(SinkError::FormatError(format!(
+                "string length {length} fails sanity check {maxStringLength}"
+            )));
+        }
+
+        self.writeInteger(length)?;
+        self.writeBytes(value.as_bytes())?;
+        self.writeByte(0)
+    }
+}
+
+pub fn CalVectorToDataSink(
+    dataSink: &mut dyn DataSink,
+    vector: &CalVector<f32>,
+) -> Result<(), SinkError> {
+    dataSink.writeFloat(vector.x)?;
+    dataSink.writeFloat(vector.y)?;
+    dataSink.writeFloat(vector.z)
+}
@@ -1,14 +1,44 @@
+use std::fmt;
 use std::string::FromUtf8Error;
 
 use crate::CalVector;
 
 pub(crate) const maxStringLength: i32 = 128;
 
+/// The byte order of the numbers in a binary file. Cal3D files are
+/// little-endian on disk; big-endian is only found in some legacy console
+/// exports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endianness {
+    #[default]
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug)]
 pub enum SourceError {
     IoError(std::io::Error),
     FormatError(String),
 }
 
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::IoError(e) => write!(f, "I/O error: {e}"),
+            SourceError::FormatError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SourceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SourceError::IoError(e) => Some(e),
+            SourceError::FormatError(_) => None,
+        }
+    }
+}
+
 impl From<std::io::Error> for SourceError {
     fn from(error: std::io::Error) -> Self {
         SourceError::IoError(error)
@@ -29,7 +59,19 @@ pub trait DataSource {
     fn readFloat(&mut self) -> Result<f32, SourceError>;
     fn readShort(&mut self) -> Result<i16, SourceError>;
     fn readInteger(&mut self) -> Result<i32, SourceError>;
-    fn readString(&mut self) -> Result<String, SourceError>;
+
+    fn readString(&mut self) -> Result<String, SourceError> {
+        self.readStringWithMaxLength(maxStringLength as usize)
+    }
+
+    /// Reads a string, failing before anything is allocated for it if its
+    /// length, including the trailing null, is over maxLength bytes.
+    fn readStringWithMaxLength(&mut self, maxLength: usize) -> Result<String, SourceError>;
+
+    /// Returns the number of bytes read so far, if the source keeps count.
+    fn position(&self) -> Option<u64> {
+        None
+    }
 }
 
 pub fn CalVectorFromDataSrc(dataSrc: &mut dyn DataSource) -> Result<CalVector<f32>, SourceError> {
@@ -0,0 +1,2507 @@
+//! Importing and exporting of glTF 2.0 assets.
+//!
+//! A glTF document describes a whole scene, so one file provides the
+//! skeleton, meshes, animations and materials of a core model. The joints of
+//! a skin become the bones of a core skeleton, in the order the skin lists
+//! them, so that the JOINTS attributes of its meshes are bone ids. Both the
+//! JSON form, with its buffers in separate files or data URIs, and the binary
+//! `.glb` form are read without any outside help. Core models are written
+//! back out as `.glb` files, so that they open in current tools.
+//!
+//! Cal3D has no scale in its transforms, so node and animation scales are
+//! ignored, as are morph target weight animations.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Mul;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use cgmath::InnerSpace;
+
+use crate::vector::blend::Blend;
+use crate::{CalIndex, CalQuaternion, CalVector};
+
+use super::animation::CalCoreAnimation;
+use super::bone::CalCoreBone;
+use super::json::{self, JsonValue};
+use super::keyframe::CalCoreKeyframe;
+use super::loader::{self, AssetKind, ErrorLocation, LoaderError, LoaderOptions};
+use super::material::{CalCoreMaterial, Color, Map};
+use super::mesh::CalCoreMesh;
+use super::model::CalCoreModel;
+use super::saver::{self, SaverError};
+use super::skeleton::CalCoreSkeleton;
+use super::submesh::{CalCoreSubmesh, Face, Influence};
+use super::submorphtarget::{BlendVertex, CalCoreSubMorphTarget};
+use super::track::CalCoreTrack;
+use super::vfs::{self, FileSystem, NATIVE_FILE_SYSTEM};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_VERSION: u32 = 2;
+const GLB_CHUNK_JSON: u32 = 0x4e4f534a;
+const GLB_CHUNK_BIN: u32 = 0x004e4942;
+
+const COMPONENT_BYTE: u64 = 5120;
+const COMPONENT_UNSIGNED_BYTE: u64 = 5121;
+const COMPONENT_SHORT: u64 = 5122;
+const COMPONENT_UNSIGNED_SHORT: u64 = 5123;
+const COMPONENT_UNSIGNED_INT: u64 = 5125;
+const COMPONENT_FLOAT: u64 = 5126;
+
+const MODE_TRIANGLES: usize = 4;
+
+/// A glTF document with all of its buffers loaded.
+pub struct GltfDocument {
+    json: JsonValue,
+    buffers: Vec<Vec<u8>>,
+}
+
+/// The values of an accessor, converted to floating point.
+struct Accessor {
+    components: usize,
+    values: Vec<f64>,
+}
+
+impl Accessor {
+    fn count(&self) -> usize {
+        self.values.len() / self.components
+    }
+
+    fn element(&self, index: usize) -> &[f64] {
+        &self.values[index * self.components..(index + 1) * self.components]
+    }
+
+    fn vector(&self, index: usize) -> CalVector<f32> {
+        let e = self.element(index);
+        CalVector::new(e[0] as f32, e[1] as f32, e[2] as f32)
+    }
+
+    fn quaternion(&self, index: usize) -> CalQuaternion<f32> {
+        let e = self.element(index);
+        CalQuaternion::new(e[3] as f32, e[0] as f32, e[1] as f32, e[2] as f32)
+    }
+}
+
+/// A rigid transform in the usual convention, where a quaternion rotates a
+/// vector as q v q*. Cal3D stores the conjugate of such rotations.
+#[derive(Clone, Copy)]
+struct Transform {
+    rotation: CalQuaternion<f32>,
+    translation: CalVector<f32>,
+}
+
+impl Transform {
+    fn identity() -> Self {
+        Transform {
+            rotation: CalQuaternion::new(1.0, 0.0, 0.0, 0.0),
+            translation: CalVector::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Returns the transform applying `child` first, then this one.
+    fn then(&self, child: &Transform) -> Transform {
+        Transform {
+            rotation: (self.rotation * child.rotation).normalize(),
+            translation: self.rotation.mul(child.translation) + self.translation,
+        }
+    }
+
+    /// Returns the rotation and translation in the form Cal3D stores them.
+    fn toCal3d(&self) -> (CalQuaternion<f32>, CalVector<f32>) {
+        (self.rotation.conjugate(), self.translation)
+    }
+}
+
+fn formatError(message: impl Into<String>) -> LoaderError {
+    LoaderError::FormatError(message.into())
+}
+
+/*****************************************************************************/
+/** Loads a glTF document.
+ *
+ * This function loads a glTF document from a `.gltf` or `.glb` file, along
+ * with the buffers it refers to, which are found relative to the file.
+ *
+ * @param filename The file to load the document from.
+ *
+ * @return The document, or the reason it could not be loaded.
+ *****************************************************************************/
+pub fn loadGltf(filename: &Path) -> Result<GltfDocument, LoaderError> {
+    loadGltfFromFileSystem(&NATIVE_FILE_SYSTEM, filename)
+}
+
+/*****************************************************************************/
+/** Loads a glTF document.
+ *
+ * This function loads a glTF document from a `.gltf` or `.glb` file of a file
+ * system, such as a directory or an archive, along with the buffers it refers
+ * to, which are found relative to the file in the same file system.
+ *
+ * @param fileSystem The file system to find the file in.
+ * @param filename The file to load the document from.
+ *
+ * @return The document, or the reason it could not be loaded.
+ *****************************************************************************/
+pub fn loadGltfFromFileSystem(
+    fileSystem: &dyn FileSystem,
+    filename: &Path,
+) -> Result<GltfDocument, LoaderError> {
+    let inFile = |e: LoaderError| e.in_file(filename, AssetKind::Model);
+    let bytes = vfs::readFile(fileSystem, filename).map_err(|e| inFile(e.into()))?;
+    let baseDirectory = filename.parent().unwrap_or(Path::new(""));
+    loadGltfDocument(&bytes, Some((fileSystem, baseDirectory))).map_err(inFile)
+}
+
+/*****************************************************************************/
+/** Loads a glTF document from memory.
+ *
+ * This function loads a glTF document from the contents of a `.gltf` or
+ * `.glb` file.
+ *
+ * @param bytes The contents of the file.
+ * @param baseDirectory The directory external buffers are found in, or
+ *                      \b None if the document may only use embedded ones.
+ *
+ * @return The document, or the reason it could not be loaded.
+ *****************************************************************************/
+pub fn loadGltfFromBytes(
+    bytes: &[u8],
+    baseDirectory: Option<&Path>,
+) -> Result<GltfDocument, LoaderError> {
+    let fileSystem: &dyn FileSystem = &NATIVE_FILE_SYSTEM;
+    let files = baseDirectory.map(|directory| (fileSystem, directory));
+    loadGltfDocument(bytes, files)
+}
+
+/// Loads a glTF document from memory, with the file system and directory
+/// its external buffers are found in, if it may have any.
+fn loadGltfDocument(
+    bytes: &[u8],
+    files: Option<(&dyn FileSystem, &Path)>,
+) -> Result<GltfDocument, LoaderError> {
+    let (text, binaryChunk) = match bytes.starts_with(GLB_MAGIC) {
+        true => {
+            let (text, binaryChunk) = splitGlb(bytes)?;
+            (text, Some(binaryChunk))
+        }
+        false => (bytes, None),
+    };
+
+    let text =
+        std::str::from_utf8(text).map_err(|_| formatError("glTF JSON is not valid UTF-8"))?;
+    // a byte order mark is allowed before the JSON
+    let json = json::parse(text.trim_start_matches('\u{feff}')).map_err(formatError)?;
+
+    let version = json
+        .get("asset")
+        .and_then(|asset| asset.get("version"))
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| formatError("glTF asset version is missing"))?;
+    if !version.starts_with("2.") {
+        return Err(LoaderError::VersionError);
+    }
+
+    let mut document = GltfDocument {
+        json,
+        buffers: Vec::new(),
+    };
+
+    let mut buffers = Vec::new();
+    for (bufferId, buffer) in document.array("buffers").iter().enumerate() {
+        let data = loadBuffer(buffer, bufferId, binaryChunk, files)
+            .map_err(|e| e.in_element(format!("buffer {bufferId}")))?;
+        buffers.push(data);
+    }
+    document.buffers = buffers;
+
+    Ok(document)
+}
+
+/// Splits a binary glTF file into its JSON chunk and its binary chunk, which
+/// is empty when the file has none.
+fn splitGlb(bytes: &[u8]) -> Result<(&[u8], &[u8]), LoaderError> {
+    let word = |offset: usize| -> Result<u32, LoaderError> {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| formatError("Truncated glb file").at_offset(offset as u64))
+    };
+
+    let version = word(4)?;
+    if version != GLB_VERSION {
+        return Err(LoaderError::VersionError);
+    }
+    let length = (word(8)? as usize).min(bytes.len());
+
+    let mut json = None;
+    let mut binary: &[u8] = &[];
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunkLength = word(offset)? as usize;
+        let chunkType = word(offset + 4)?;
+        let start = offset + 8;
+        let chunk = start
+            .checked_add(chunkLength)
+            .and_then(|end| bytes.get(start..end))
+            .ok_or_else(|| formatError("Truncated glb chunk").at_offset(offset as u64))?;
+        match chunkType {
+            GLB_CHUNK_JSON if json.is_none() => json = Some(chunk),
+            GLB_CHUNK_BIN if binary.is_empty() => binary = chunk,
+            // other chunks may be ignored
+            _ => {}
+        }
+        offset = start + chunkLength;
+    }
+
+    match json {
+        Some(json) => Ok((json, binary)),
+        None => Err(formatError("glb file has no JSON chunk")),
+    }
+}
+
+/// Returns the path of the file of an external buffer, which must be relative
+/// to the directory of the document and stay inside it.
+fn bufferPath(baseDirectory: &Path, uri: &str) -> Result<PathBuf, LoaderError> {
+    let mut depth = 0;
+    for component in Path::new(uri).components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => {
+                return Err(formatError(format!(
+                    "Buffer {uri} is outside the directory of the document"
+                )));
+            }
+        }
+    }
+    Ok(baseDirectory.join(uri))
+}
+
+fn loadBuffer(
+    buffer: &JsonValue,
+    bufferId: usize,
+    binaryChunk: Option<&[u8]>,
+    files: Option<(&dyn FileSystem, &Path)>,
+) -> Result<Vec<u8>, LoaderError> {
+    let byteLength = requiredIndex(buffer, "byteLength")?;
+
+    let mut data = match buffer.get("uri").and_then(JsonValue::as_str) {
+        Some(uri) if uri.starts_with("data:") => {
+            let Some((_, encoded)) = uri.split_once(";base64,") else {
+                return Err(formatError("Buffer data URI is not base64"));
+            };
+            decodeBase64(encoded)?
+        }
+        Some(uri) => {
+            let Some((fileSystem, baseDirectory)) = files else {
+                return Err(formatError(format!("No directory to find buffer {uri} in")));
+            };
+            vfs::readFile(fileSystem, &bufferPath(baseDirectory, &decodeUri(uri))?)?
+        }
+        None => match (bufferId, binaryChunk) {
+            (0, Some(chunk)) => chunk.to_vec(),
+            _ => return Err(formatError("Buffer has no data")),
+        },
+    };
+
+    if data.len() < byteLength {
+        return Err(formatError(format!(
+            "Buffer holds {} bytes, expected {byteLength}",
+            data.len()
+        )));
+    }
+    // the binary chunk may be padded
+    data.truncate(byteLength);
+
+    Ok(data)
+}
+
+/// Decodes the percent escapes of a relative URI.
+fn decodeUri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes[i] {
+            b'%' => bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn decodeBase64(encoded: &str) -> Result<Vec<u8>, LoaderError> {
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+    let mut bits: u32 = 0;
+    let mut bitCount = 0;
+    for c in encoded.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return Err(formatError("Invalid base64 data")),
+        };
+        bits = (bits << 6) | value as u32;
+        bitCount += 6;
+        if bitCount >= 8 {
+            bitCount -= 8;
+            decoded.push((bits >> bitCount) as u8);
+        }
+    }
+    Ok(decoded)
+}
+
+/// Reads a property that has to be a valid index or count, if it is present.
+fn index(value: &JsonValue, name: &str) -> Result<Option<usize>, LoaderError> {
+    match value.get(name) {
+        None => Ok(None),
+        Some(v) => v
+            .as_usize()
+            .map(Some)
+            .ok_or_else(|| formatError(format!("Invalid {name} {v:?}"))),
+    }
+}
+
+fn requiredIndex(value: &JsonValue, name: &str) -> Result<usize, LoaderError> {
+    index(value, name)?.ok_or_else(|| formatError(format!("Missing {name}")))
+}
+
+/// Reads a property that has to be an array of `count` numbers, if it is
+/// present.
+fn numbers(value: &JsonValue, name: &str, count: usize) -> Result<Option<Vec<f64>>, LoaderError> {
+    let Some(v) = value.get(name) else {
+        return Ok(None);
+    };
+    let numbers: Option<Vec<f64>> = v
+        .as_array()
+        .map(|values| values.iter().map(JsonValue::as_f64).collect())
+        .unwrap_or(None);
+    match numbers {
+        Some(numbers) if numbers.len() == count => Ok(Some(numbers)),
+        _ => Err(formatError(format!(
+            "Invalid {name}, expected {count} numbers"
+        ))),
+    }
+}
+
+/// Returns the local transform of a node, from its matrix or its
+/// translation and rotation.
+fn nodeTransform(node: &JsonValue) -> Result<Transform, LoaderError> {
+    if let Some(m) = numbers(node, "matrix", 16)? {
+        return Ok(matrixTransform(&m));
+    }
+
+    let mut transform = Transform::identity();
+    if let Some(t) = numbers(node, "translation", 3)? {
+        transform.translation = CalVector::new(t[0] as f32, t[1] as f32, t[2] as f32);
+    }
+    if let Some(r) = numbers(node, "rotation", 4)? {
+        transform.rotation =
+            CalQuaternion::new(r[3] as f32, r[0] as f32, r[1] as f32, r[2] as f32).normalize();
+    }
+    Ok(transform)
+}
+
+/// Returns the rotation and translation of a column major 4x4 matrix.
+fn matrixTransform(m: &[f64]) -> Transform {
+    // normalising the columns removes any scale
+    let column = |i: usize| {
+        CalVector::new(m[i * 4] as f32, m[i * 4 + 1] as f32, m[i * 4 + 2] as f32).normalize()
+    };
+    let rotation = cgmath::Matrix3::from_cols(column(0), column(1), column(2));
+    Transform {
+        rotation: CalQuaternion::from(rotation).normalize(),
+        translation: CalVector::new(m[12] as f32, m[13] as f32, m[14] as f32),
+    }
+}
+
+fn componentSize(componentType: u64) -> Result<usize, LoaderError> {
+    match componentType {
+        COMPONENT_BYTE | COMPONENT_UNSIGNED_BYTE => Ok(1),
+        COMPONENT_SHORT | COMPONENT_UNSIGNED_SHORT => Ok(2),
+        COMPONENT_UNSIGNED_INT | COMPONENT_FLOAT => Ok(4),
+        _ => Err(formatError(format!(
+            "Invalid accessor component type {componentType}"
+        ))),
+    }
+}
+
+fn readComponent(bytes: &[u8], componentType: u64, normalized: bool) -> f64 {
+    match componentType {
+        COMPONENT_BYTE => {
+            let v = bytes[0] as i8 as f64;
+            if normalized { (v / 127.0).max(-1.0) } else { v }
+        }
+        COMPONENT_UNSIGNED_BYTE => {
+            let v = bytes[0] as f64;
+            if normalized { v / 255.0 } else { v }
+        }
+        COMPONENT_SHORT => {
+            let v = i16::from_le_bytes([bytes[0], bytes[1]]) as f64;
+            if normalized {
+                (v / 32767.0).max(-1.0)
+            } else {
+                v
+            }
+        }
+        COMPONENT_UNSIGNED_SHORT => {
+            let v = u16::from_le_bytes([bytes[0], bytes[1]]) as f64;
+            if normalized { v / 65535.0 } else { v }
+        }
+        COMPONENT_UNSIGNED_INT => {
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+        }
+        _ => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+    }
+}
+
+impl GltfDocument {
+    /// Returns a top level array of the document, which is empty if the
+    /// document has none.
+    fn array(&self, name: &str) -> &[JsonValue] {
+        self.json
+            .get(name)
+            .and_then(JsonValue::as_array)
+            .map_or(&[], |values| values.as_slice())
+    }
+
+    fn item(&self, collection: &str, id: usize) -> Result<&JsonValue, LoaderError> {
+        self.array(collection)
+            .get(id)
+            .ok_or_else(|| formatError(format!("Invalid {collection} index {id}")))
+    }
+
+    /// Returns the number of skins in the document.
+    pub fn getSkinCount(&self) -> usize {
+        self.array("skins").len()
+    }
+
+    /// Returns the number of animations in the document.
+    pub fn getAnimationCount(&self) -> usize {
+        self.array("animations").len()
+    }
+
+    /// Returns the name of an animation, if it has one.
+    pub fn getAnimationName(&self, animationId: usize) -> Option<&str> {
+        self.array("animations")
+            .get(animationId)?
+            .get("name")?
+            .as_str()
+    }
+
+    /// Reads `count` elements of `components` components each from a buffer
+    /// view, honouring its stride.
+    fn readBufferView(
+        &self,
+        bufferViewId: usize,
+        byteOffset: usize,
+        componentType: u64,
+        normalized: bool,
+        components: usize,
+        count: usize,
+    ) -> Result<Vec<f64>, LoaderError> {
+        let bufferView = self.item("bufferViews", bufferViewId)?;
+        let buffer = self
+            .buffers
+            .get(requiredIndex(bufferView, "buffer")?)
+            .ok_or_else(|| formatError(format!("Invalid buffer in buffer view {bufferViewId}")))?;
+        let viewOffset = index(bufferView, "byteOffset")?.unwrap_or(0);
+        let viewLength = requiredIndex(bufferView, "byteLength")?;
+
+        let view = viewOffset
+            .checked_add(viewLength)
+            .and_then(|end| buffer.get(viewOffset..end))
+            .ok_or_else(|| formatError(format!("Buffer view {bufferViewId} outside its buffer")))?;
+
+        let size = componentSize(componentType)?;
+        let elementSize = size * components;
+        let stride = index(bufferView, "byteStride")?.unwrap_or(elementSize);
+        if stride < elementSize {
+            return Err(formatError(format!(
+                "Buffer view {bufferViewId} stride {stride} is less than the element size"
+            )));
+        }
+
+        // check that every element is in the view before allocating for them
+        if count > 0 {
+            let end = (count - 1)
+                .checked_mul(stride)
+                .and_then(|last| last.checked_add(byteOffset))
+                .and_then(|last| last.checked_add(elementSize));
+            if end.is_none_or(|end| end > view.len()) {
+                return Err(formatError(format!(
+                    "{count} elements do not fit in buffer view {bufferViewId}"
+                )));
+            }
+        }
+
+        let mut values = Vec::with_capacity(count * components);
+        for element in 0..count {
+            let start = byteOffset + element * stride;
+            for component in 0..components {
+                let at = start + component * size;
+                values.push(readComponent(
+                    &view[at..at + size],
+                    componentType,
+                    normalized,
+                ));
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Reads an accessor, applying any sparse substitution. Its number of
+    /// elements is checked against the limit before anything is allocated.
+    fn readAccessor(
+        &self,
+        accessorId: usize,
+        what: &str,
+        limit: usize,
+    ) -> Result<Accessor, LoaderError> {
+        (|| -> Result<Accessor, LoaderError> {
+            let accessor = self.item("accessors", accessorId)?;
+            let count = requiredIndex(accessor, "count")?;
+            loader::checkLimit(what, count, limit)?;
+            let componentType = requiredIndex(accessor, "componentType")? as u64;
+            let normalized = accessor.get("normalized") == Some(&JsonValue::Bool(true));
+            let components = match accessor.get("type").and_then(JsonValue::as_str) {
+                Some("SCALAR") => 1,
+                Some("VEC2") => 2,
+                Some("VEC3") => 3,
+                Some("VEC4") | Some("MAT2") => 4,
+                Some("MAT3") => 9,
+                Some("MAT4") => 16,
+                t => return Err(formatError(format!("Invalid accessor type {t:?}"))),
+            };
+
+            let mut values = match index(accessor, "bufferView")? {
+                Some(bufferViewId) => self.readBufferView(
+                    bufferViewId,
+                    index(accessor, "byteOffset")?.unwrap_or(0),
+                    componentType,
+                    normalized,
+                    components,
+                    count,
+                )?,
+                None => {
+                    componentSize(componentType)?;
+                    let length = count
+                        .checked_mul(components)
+                        .ok_or_else(|| formatError(format!("Invalid accessor count {count}")))?;
+                    vec![0.0; length]
+                }
+            };
+
+            if let Some(sparse) = accessor.get("sparse") {
+                let sparseCount = requiredIndex(sparse, "count")?;
+                if sparseCount > count {
+                    return Err(formatError(format!(
+                        "Sparse count {sparseCount} over the accessor count {count}"
+                    )));
+                }
+                let indices = sparse
+                    .get("indices")
+                    .ok_or_else(|| formatError("Sparse accessor has no indices"))?;
+                let sparseValues = sparse
+                    .get("values")
+                    .ok_or_else(|| formatError("Sparse accessor has no values"))?;
+                let indices = self.readBufferView(
+                    requiredIndex(indices, "bufferView")?,
+                    index(indices, "byteOffset")?.unwrap_or(0),
+                    requiredIndex(indices, "componentType")? as u64,
+                    false,
+                    1,
+                    sparseCount,
+                )?;
+                let sparseValues = self.readBufferView(
+                    requiredIndex(sparseValues, "bufferView")?,
+                    index(sparseValues, "byteOffset")?.unwrap_or(0),
+                    componentType,
+                    normalized,
+                    components,
+                    sparseCount,
+                )?;
+                for (i, elementIndex) in indices.iter().enumerate() {
+                    let elementIndex = *elementIndex as usize;
+                    if elementIndex >= count {
+                        return Err(formatError(format!(
+                            "Sparse index {elementIndex} outside accessor of {count} elements"
+                        )));
+                    }
+                    values[elementIndex * components..(elementIndex + 1) * components]
+                        .copy_from_slice(&sparseValues[i * components..(i + 1) * components]);
+                }
+            }
+
+            Ok(Accessor { components, values })
+        })()
+        .map_err(|e| e.in_element(format!("accessor {accessorId}")))
+    }
+
+    /// Reads the accessor of a mesh attribute, checking its element size.
+    fn readAttribute(
+        &self,
+        attributes: &JsonValue,
+        name: &str,
+        components: &[usize],
+        count: Option<usize>,
+        options: &LoaderOptions,
+    ) -> Result<Option<Accessor>, LoaderError> {
+        let Some(accessorId) = index(attributes, name)? else {
+            return Ok(None);
+        };
+        let accessor = self
+            .readAccessor(accessorId, "Vertex count", options.limits.max_vertices)
+            .map_err(|e| e.in_element(format!("attribute {name}")))?;
+        if !components.contains(&accessor.components) {
+            return Err(formatError(format!(
+                "Attribute {name} has {} components",
+                accessor.components
+            )));
+        }
+        if let Some(count) = count
+            && accessor.count() != count
+        {
+            return Err(formatError(format!(
+                "Attribute {name} has {} elements, expected {count}",
+                accessor.count()
+            )));
+        }
+        Ok(Some(accessor))
+    }
+
+    fn skinJoints(&self, skinId: usize) -> Result<Vec<usize>, LoaderError> {
+        let skin = self.item("skins", skinId)?;
+        let joints = skin
+            .get("joints")
+            .and_then(JsonValue::as_array)
+            .ok_or_else(|| formatError(format!("Skin {skinId} has no joints")))?;
+        joints
+            .iter()
+            .map(|joint| {
+                joint
+                    .as_usize()
+                    .filter(|nodeId| *nodeId < self.array("nodes").len())
+                    .ok_or_else(|| formatError(format!("Invalid joint {joint:?} in skin {skinId}")))
+            })
+            .collect()
+    }
+
+    /// Returns the parent node of every node.
+    fn nodeParents(&self) -> Result<Vec<Option<usize>>, LoaderError> {
+        let nodes = self.array("nodes");
+        let mut parents = vec![None; nodes.len()];
+        for (nodeId, node) in nodes.iter().enumerate() {
+            let Some(children) = node.get("children").and_then(JsonValue::as_array) else {
+                continue;
+            };
+            for child in children {
+                match child.as_usize() {
+                    Some(childId) if childId < nodes.len() && parents[childId].is_none() => {
+                        parents[childId] = Some(nodeId);
+                    }
+                    _ => {
+                        return Err(formatError(format!(
+                            "Invalid child {child:?} of node {nodeId}"
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(parents)
+    }
+
+    /// Finds the bone a joint hangs from, and the transform of the nodes
+    /// between that bone and the joint, which is applied on top of the
+    /// transform of the joint itself.
+    fn jointParent(
+        &self,
+        nodeId: usize,
+        parents: &[Option<usize>],
+        boneIds: &BTreeMap<usize, usize>,
+    ) -> Result<(i32, Transform), LoaderError> {
+        let mut transform = Transform::identity();
+        let mut current = parents[nodeId];
+        let mut steps = 0;
+        while let Some(parentId) = current {
+            if let Some(boneId) = boneIds.get(&parentId) {
+                return Ok((*boneId as i32, transform));
+            }
+            transform = nodeTransform(self.item("nodes", parentId)?)?.then(&transform);
+            current = parents[parentId];
+            steps += 1;
+            if steps > parents.len() {
+                return Err(formatError(format!("Node {nodeId} is its own ancestor")));
+            }
+        }
+        Ok((-1, transform))
+    }
+
+    /*****************************************************************************/
+    /** Loads a core skeleton from a skin.
+     *
+     * This function adds a bone to the core skeleton for every joint of the
+     * skin, so that bone ids match the joint indices used by the meshes and
+     * animations. The core skeleton therefore has to be empty.
+     *
+     * @param skinId The skin to load the skeleton of.
+     * @param skeleton The core skeleton to add the bones to.
+     * @param options The conversions to apply while loading.
+     *****************************************************************************/
+    pub fn loadCoreSkeleton(
+        &self,
+        skinId: usize,
+        skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+        options: &LoaderOptions,
+    ) -> Result<(), LoaderError> {
+        (|| -> Result<(), LoaderError> {
+            if !skeleton.borrow().getVectorCoreBone().is_empty() {
+                return Err(formatError("Skeleton already has bones"));
+            }
+            let skin = self.item("skins", skinId)?;
+            let joints = self.skinJoints(skinId)?;
+            loader::checkLimit("Bone count", joints.len(), options.limits.max_bones)?;
+            let parents = self.nodeParents()?;
+
+            let mut boneIds = BTreeMap::new();
+            for (boneId, nodeId) in joints.iter().enumerate() {
+                if boneIds.insert(*nodeId, boneId).is_some() {
+                    return Err(formatError(format!("Node {nodeId} is a joint twice")));
+                }
+            }
+
+            let inverseBindMatrices = match index(skin, "inverseBindMatrices")? {
+                Some(accessorId) => {
+                    let limit = options.limits.max_bones;
+                    let accessor = self.readAccessor(accessorId, "Bone count", limit)?;
+                    if accessor.components != 16 || accessor.count() < joints.len() {
+                        return Err(formatError("Inverse bind matrices do not match the joints"));
+                    }
+                    Some(accessor)
+                }
+                None => None,
+            };
+
+            for (boneId, nodeId) in joints.iter().enumerate() {
+                let node = self.item("nodes", *nodeId)?;
+                let name = node
+                    .get("name")
+                    .and_then(JsonValue::as_str)
+                    .map_or_else(|| format!("node{nodeId}"), str::to_string);
+
+                let (parentId, prefix) = self.jointParent(*nodeId, &parents, &boneIds)?;
+                let (mut rotation, mut translation) = prefix.then(&nodeTransform(node)?).toCal3d();
+                if parentId == -1 {
+                    (rotation, translation) = options.rotateRootTransform(rotation, translation);
+                }
+
+                // the inverse bind matrix takes the mesh into bone space
+                let boneSpace = match &inverseBindMatrices {
+                    Some(matrices) => matrixTransform(matrices.element(boneId)),
+                    None => Transform::identity(),
+                };
+                let (rotationBoneSpace, translationBoneSpace) = boneSpace.toCal3d();
+                let rotationBoneSpace = options.rotateBoneSpace(rotationBoneSpace);
+
+                let bone = CalCoreBone::new(
+                    name,
+                    skeleton.clone(),
+                    parentId,
+                    Vec::new(),
+                    translation,
+                    rotation,
+                    translationBoneSpace,
+                    rotationBoneSpace,
+                );
+                skeleton
+                    .borrow_mut()
+                    .addCoreBone(Rc::new(RefCell::new(bone)));
+            }
+
+            // link the children, now that every bone exists
+            let skeleton = skeleton.borrow();
+            for boneId in 0..skeleton.getVectorCoreBone().len() {
+                let parentId = skeleton.getVectorCoreBone()[boneId].borrow().getParentId();
+                if parentId >= 0 {
+                    skeleton.getVectorCoreBone()[parentId as usize]
+                        .borrow_mut()
+                        .addChildId(boneId as i32);
+                }
+            }
+
+            Ok(())
+        })()
+        .map_err(|e| e.in_element(format!("skin {skinId}")))
+    }
+
+    /*****************************************************************************/
+    /** Loads the core meshes of a skin.
+     *
+     * This function loads a core mesh for every node that uses the mesh with
+     * the skin, with a core submesh for each of its triangle primitives. The
+     * core material thread of a submesh is the index of its glTF material.
+     *
+     * @param skinId The skin the meshes have to use.
+     * @param options The conversions to apply while loading.
+     *****************************************************************************/
+    pub fn loadCoreMeshes(
+        &self,
+        skinId: usize,
+        options: &LoaderOptions,
+    ) -> Result<Vec<Rc<RefCell<CalCoreMesh>>>, LoaderError> {
+        let jointCount = self.skinJoints(skinId)?.len();
+
+        let mut meshes = Vec::new();
+        for node in self.array("nodes") {
+            if index(node, "skin")? != Some(skinId) {
+                continue;
+            }
+            let Some(meshId) = index(node, "mesh")? else {
+                continue;
+            };
+            let mut mesh = self.loadCoreMesh(meshId, jointCount, options)?;
+            if let Some(name) = node.get("name").and_then(JsonValue::as_str) {
+                mesh.setName(name.to_string());
+            }
+            meshes.push(Rc::new(RefCell::new(mesh)));
+        }
+
+        Ok(meshes)
+    }
+
+    fn loadCoreMesh(
+        &self,
+        meshId: usize,
+        jointCount: usize,
+        options: &LoaderOptions,
+    ) -> Result<CalCoreMesh, LoaderError> {
+        // errors are located in the mesh here rather than by the caller, as
+        // the problems reported to the options do not go through it
+        let inMesh = |e: LoaderError| e.in_element(format!("mesh {meshId}"));
+        let mesh = self.item("meshes", meshId).map_err(inMesh)?;
+        let primitives = mesh
+            .get("primitives")
+            .and_then(JsonValue::as_array)
+            .ok_or_else(|| inMesh(formatError("Mesh has no primitives")))?;
+        let targetNames = mesh
+            .get("extras")
+            .and_then(|extras| extras.get("targetNames"))
+            .and_then(JsonValue::as_array)
+            .map_or(&[][..], |names| names.as_slice());
+
+        loader::checkLimit(
+            "Submesh count",
+            primitives.len(),
+            options.limits.max_submeshes,
+        )
+        .map_err(inMesh)?;
+
+        let mut submeshes = Vec::new();
+        for (primitiveId, primitive) in primitives.iter().enumerate() {
+            let mode = index(primitive, "mode").map_err(inMesh)?;
+            if mode.unwrap_or(MODE_TRIANGLES) != MODE_TRIANGLES {
+                // other primitives are skipped when not loading strictly
+                options.reportProblemAt(
+                    "Primitive is not made of triangles".to_string(),
+                    ErrorLocation {
+                        elements: vec![
+                            format!("mesh {meshId}"),
+                            format!("primitive {primitiveId}"),
+                        ],
+                        ..Default::default()
+                    },
+                )?;
+                continue;
+            }
+            let submesh = self
+                .loadCoreSubmesh(primitive, jointCount, targetNames, options)
+                .map_err(|e| inMesh(e.in_element(format!("primitive {primitiveId}"))))?;
+            submeshes.push(submesh);
+        }
+
+        Ok(CalCoreMesh::new(submeshes))
+    }
+
+    fn loadCoreSubmesh(
+        &self,
+        primitive: &JsonValue,
+        jointCount: usize,
+        targetNames: &[JsonValue],
+        options: &LoaderOptions,
+    ) -> Result<Rc<RefCell<CalCoreSubmesh>>, LoaderError> {
+        let attributes = primitive
+            .get("attributes")
+            .ok_or_else(|| formatError("Primitive has no attributes"))?;
+
+        let positions = self
+            .readAttribute(attributes, "POSITION", &[3], None, options)?
+            .ok_or_else(|| formatError("Primitive has no POSITION attribute"))?;
+        let vertexCount = positions.count();
+        let count = Some(vertexCount);
+
+        // bound the values of the submesh before reading any more of them
+        let targets = primitive
+            .get("targets")
+            .and_then(JsonValue::as_array)
+            .map_or(&[][..], |targets| targets.as_slice());
+        loader::checkLimit(
+            "Morph target count",
+            targets.len(),
+            options.limits.max_morph_targets,
+        )?;
+        let textureCoordinateCount = (0..)
+            .take_while(|i| attributes.get(&format!("TEXCOORD_{i}")).is_some())
+            .count();
+        loader::checkSubmeshValues(
+            vertexCount,
+            textureCoordinateCount + targets.len(),
+            &options.limits,
+        )?;
+
+        let normals = self.readAttribute(attributes, "NORMAL", &[3], count, options)?;
+        let colors = self.readAttribute(attributes, "COLOR_0", &[3, 4], count, options)?;
+
+        let mut textureCoordinates = Vec::new();
+        while let Some(accessor) = self.readAttribute(
+            attributes,
+            &format!("TEXCOORD_{}", textureCoordinates.len()),
+            &[2],
+            count,
+            options,
+        )? {
+            loader::checkLimit(
+                "Texture coordinate count",
+                textureCoordinates.len() + 1,
+                options.limits.max_texture_coordinate_sets,
+            )?;
+            textureCoordinates.push(accessor);
+        }
+
+        let mut influenceSets = Vec::new();
+        while let Some(joints) = self.readAttribute(
+            attributes,
+            &format!("JOINTS_{}", influenceSets.len()),
+            &[4],
+            count,
+            options,
+        )? {
+            // every set holds four influences of each vertex
+            loader::checkLimit(
+                "Influence count",
+                (influenceSets.len() + 1) * 4,
+                options.limits.max_bones,
+            )?;
+            let weights = self
+                .readAttribute(
+                    attributes,
+                    &format!("WEIGHTS_{}", influenceSets.len()),
+                    &[4],
+                    count,
+                    options,
+                )?
+                .ok_or_else(|| {
+                    formatError(format!("JOINTS_{} has no WEIGHTS", influenceSets.len()))
+                })?;
+            influenceSets.push((joints, weights));
+        }
+
+        let indices: Vec<usize> = match index(primitive, "indices")? {
+            Some(accessorId) => {
+                let limit = options.limits.max_faces.saturating_mul(3);
+                let accessor = self.readAccessor(accessorId, "Index count", limit)?;
+                if accessor.components != 1 {
+                    return Err(formatError("Primitive indices are not scalars"));
+                }
+                accessor.values.iter().map(|i| *i as usize).collect()
+            }
+            None => (0..vertexCount).collect(),
+        };
+        if let Some(i) = indices.iter().find(|i| **i >= vertexCount) {
+            return Err(formatError(format!(
+                "Index {i} outside {vertexCount} vertices"
+            )));
+        }
+        if vertexCount > CalIndex::MAX as usize {
+            return Err(formatError(format!("Too many vertices {vertexCount}")));
+        }
+        let faceCount = indices.len() / 3;
+        loader::checkLimit("Face count", faceCount, options.limits.max_faces)?;
+
+        let materialThreadId = match index(primitive, "material")? {
+            Some(materialId) => materialId as i32,
+            None => -1,
+        };
+
+        let coreSubmesh = Rc::new(RefCell::new(CalCoreSubmesh::new(
+            materialThreadId,
+            0,
+            vertexCount,
+            textureCoordinates.len(),
+            faceCount,
+            0,
+        )));
+        let mut submesh = coreSubmesh.borrow_mut();
+
+        for textureCoordinateId in 0..textureCoordinates.len() {
+            submesh.enableTangents(textureCoordinateId, false);
+        }
+
+        let mut hasNonWhiteVertexColors = false;
+        for (vertexId, vertex) in submesh.getVectorVertexMut().iter_mut().enumerate() {
+            vertex.position = options.rotateVector(positions.vector(vertexId));
+            if let Some(normals) = &normals {
+                vertex.normal = options.rotateVector(normals.vector(vertexId));
+            }
+            vertex.vertexColor = match &colors {
+                Some(colors) => colors.vector(vertexId),
+                None => CalVector::new(1.0, 1.0, 1.0),
+            };
+            if vertex.vertexColor != CalVector::new(1.0, 1.0, 1.0) {
+                hasNonWhiteVertexColors = true;
+            }
+            vertex.collapseId = -1;
+            vertex.faceCollapseCount = 0;
+
+            for (joints, weights) in influenceSets.iter() {
+                let joints = joints.element(vertexId);
+                let weights = weights.element(vertexId);
+                for (joint, weight) in joints.iter().zip(weights) {
+                    if *weight <= 0.0 {
+                        continue;
+                    }
+                    if *joint as usize >= jointCount {
+                        return Err(formatError(format!(
+                            "Invalid joint {joint} in vertex {vertexId}"
+                        )));
+                    }
+                    vertex.vectorInfluence.push(Influence {
+                        boneId: *joint as i32,
+                        weight: *weight as f32,
+                    });
+                }
+            }
+        }
+        submesh.setHasNonWhiteVertexColors(hasNonWhiteVertexColors);
+
+        let textureCoordinate = |accessor: &Accessor, vertexId: usize| {
+            let e = accessor.element(vertexId);
+            options.textureCoordinate(e[0] as f32, e[1] as f32)
+        };
+        submesh.setAllTextureCoordinates(
+            textureCoordinates
+                .iter()
+                .map(|accessor| {
+                    (0..vertexCount)
+                        .map(|vertexId| textureCoordinate(accessor, vertexId))
+                        .collect()
+                })
+                .collect(),
+        );
+
+        for faceId in 0..faceCount {
+            let mut vertexId = [
+                indices[faceId * 3] as CalIndex,
+                indices[faceId * 3 + 1] as CalIndex,
+                indices[faceId * 3 + 2] as CalIndex,
+            ];
+            if options.flip_winding {
+                vertexId.swap(1, 2);
+            }
+            submesh.setFace(faceId, Face::new(vertexId));
+        }
+
+        // morph targets hold displacements, where Cal3D wants the morphed vertex
+        for (targetId, target) in targets.iter().enumerate() {
+            let positionDeltas = self.readAttribute(target, "POSITION", &[3], count, options)?;
+            let normalDeltas = self.readAttribute(target, "NORMAL", &[3], count, options)?;
+            let name = targetNames
+                .get(targetId)
+                .and_then(JsonValue::as_str)
+                .map_or_else(|| format!("target{targetId}"), str::to_string);
+
+            let mut morphTarget =
+                CalCoreSubMorphTarget::new(coreSubmesh.clone(), vertexCount, name);
+            for vertexId in 0..vertexCount {
+                let mut blendVertex = BlendVertex::new(textureCoordinates.len());
+                let mut position = positions.vector(vertexId);
+                if let Some(deltas) = &positionDeltas {
+                    position += deltas.vector(vertexId);
+                }
+                let mut normal = match &normals {
+                    Some(normals) => normals.vector(vertexId),
+                    None => CalVector::new(0.0, 0.0, 0.0),
+                };
+                if let Some(deltas) = &normalDeltas {
+                    normal += deltas.vector(vertexId);
+                }
+                blendVertex.position = options.rotateVector(position);
+                blendVertex.normal = options.rotateVector(normal);
+                for accessor in textureCoordinates.iter() {
+                    blendVertex
+                        .textureCoords
+                        .push(textureCoordinate(accessor, vertexId));
+                }
+                morphTarget.setBlendVertex(vertexId, &blendVertex);
+            }
+            submesh.addCoreSubMorphTarget(morphTarget);
+        }
+
+        drop(submesh);
+        Ok(coreSubmesh)
+    }
+
+    /*****************************************************************************/
+    /** Loads the core animations of a skin.
+     *
+     * This function loads a core animation for every animation of the
+     * document, with a track for each joint of the skin that is animated.
+     * Keyframes are placed at every time either the translation or the
+     * rotation of a joint has a key.
+     *
+     * @param skinId The skin the skeleton was loaded from.
+     * @param skeleton The core skeleton loaded from the skin.
+     * @param options The conversions to apply while loading.
+     *****************************************************************************/
+    pub fn loadCoreAnimations(
+        &self,
+        skinId: usize,
+        skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+        options: &LoaderOptions,
+    ) -> Result<Vec<Rc<RefCell<CalCoreAnimation>>>, LoaderError> {
+        let joints = self.skinJoints(skinId)?;
+        let parents = self.nodeParents()?;
+        let boneIds: BTreeMap<usize, usize> = joints
+            .iter()
+            .enumerate()
+            .map(|(boneId, nodeId)| (*nodeId, boneId))
+            .collect();
+
+        let mut animations = Vec::new();
+        for animationId in 0..self.getAnimationCount() {
+            let mut animation = self
+                .loadCoreAnimation(animationId, &boneIds, &parents, skeleton, options)
+                .map_err(|e| e.in_element(format!("animation {animationId}")))?;
+            if let Some(name) = self.getAnimationName(animationId) {
+                animation.setName(name.to_string());
+            }
+            animations.push(Rc::new(RefCell::new(animation)));
+        }
+
+        Ok(animations)
+    }
+
+    /// Reads the times and values of an animation sampler. Cubic spline
+    /// samplers keep only their values, and are then treated as linear.
+    fn readSampler(
+        &self,
+        animation: &JsonValue,
+        samplerId: usize,
+        components: usize,
+        options: &LoaderOptions,
+    ) -> Result<(Vec<f32>, Accessor, bool), LoaderError> {
+        let sampler = animation
+            .get("samplers")
+            .and_then(JsonValue::as_array)
+            .and_then(|samplers| samplers.get(samplerId))
+            .ok_or_else(|| formatError(format!("Invalid sampler {samplerId}")))?;
+        let limit = options.limits.max_keyframes;
+        let input = self.readAccessor(requiredIndex(sampler, "input")?, "Keyframe count", limit)?;
+        // cubic spline samplers hold two tangents along with every value
+        let mut output = self.readAccessor(
+            requiredIndex(sampler, "output")?,
+            "Keyframe value count",
+            limit.saturating_mul(3),
+        )?;
+        let interpolation = sampler
+            .get("interpolation")
+            .and_then(JsonValue::as_str)
+            .unwrap_or("LINEAR");
+
+        if output.components != components {
+            return Err(formatError(format!(
+                "Sampler {samplerId} output has {} components, expected {components}",
+                output.components
+            )));
+        }
+        if interpolation == "CUBICSPLINE" {
+            // each key is an in tangent, a value and an out tangent
+            output.values = output
+                .values
+                .chunks(components * 3)
+                .flat_map(|key| key[components..components * 2].to_vec())
+                .collect();
+        }
+
+        let times: Vec<f32> = input.values.iter().map(|t| *t as f32).collect();
+        if input.components != 1 || output.count() != times.len() || times.is_empty() {
+            return Err(formatError(format!(
+                "Sampler {samplerId} input and output do not match"
+            )));
+        }
+        if times.windows(2).any(|pair| pair[1] < pair[0]) {
+            return Err(formatError(format!(
+                "Sampler {samplerId} times are not increasing"
+            )));
+        }
+
+        Ok((times, output, interpolation == "STEP"))
+    }
+
+    fn loadCoreAnimation(
+        &self,
+        animationId: usize,
+        boneIds: &BTreeMap<usize, usize>,
+        parents: &[Option<usize>],
+        skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+        options: &LoaderOptions,
+    ) -> Result<CalCoreAnimation, LoaderError> {
+        let animation = self.item("animations", animationId)?;
+        let channels = animation
+            .get("channels")
+            .and_then(JsonValue::as_array)
+            .ok_or_else(|| formatError("Animation has no channels"))?;
+
+        // the translation and rotation channels of every animated joint
+        let mut samplers = BTreeMap::<usize, [Option<usize>; 2]>::new();
+        for channel in channels {
+            let target = channel
+                .get("target")
+                .ok_or_else(|| formatError("Channel has no target"))?;
+            let Some(nodeId) = index(target, "node")? else {
+                continue;
+            };
+            if !boneIds.contains_key(&nodeId) {
+                continue;
+            }
+            let samplerId = requiredIndex(channel, "sampler")?;
+            let entry = samplers.entry(nodeId).or_default();
+            match target.get("path").and_then(JsonValue::as_str) {
+                Some("translation") => entry[0] = Some(samplerId),
+                Some("rotation") => entry[1] = Some(samplerId),
+                _ => {}
+            }
+        }
+
+        loader::checkLimit("Track count", samplers.len(), options.limits.max_tracks)?;
+
+        let mut duration: f32 = 0.0;
+        let mut tracks = Vec::new();
+        for (nodeId, [translationSampler, rotationSampler]) in samplers {
+            if translationSampler.is_none() && rotationSampler.is_none() {
+                continue;
+            }
+            let boneId = boneIds[&nodeId];
+            let node = self.item("nodes", nodeId)?;
+            let rest = nodeTransform(node)?;
+            let (parentId, prefix) = self.jointParent(nodeId, parents, boneIds)?;
+
+            let translations = translationSampler
+                .map(|samplerId| self.readSampler(animation, samplerId, 3, options))
+                .transpose()?;
+            let rotations = rotationSampler
+                .map(|samplerId| self.readSampler(animation, samplerId, 4, options))
+                .transpose()?;
+
+            let mut times: Vec<f32> = translations
+                .iter()
+                .chain(rotations.iter())
+                .flat_map(|(times, _, _)| times.iter().copied())
+                .collect();
+            times.sort_by(f32::total_cmp);
+            times.dedup();
+
+            let mut keyframes = Vec::with_capacity(times.len());
+            for time in times.iter() {
+                let mut local = rest;
+                if let Some((times, values, step)) = &translations {
+                    local.translation = sample(times, *step, *time, |i| values.vector(i));
+                }
+                if let Some((times, values, step)) = &rotations {
+                    local.rotation =
+                        sample(times, *step, *time, |i| values.quaternion(i).normalize());
+                }
+                let (mut rotation, mut translation) = prefix.then(&local).toCal3d();
+                if parentId == -1 {
+                    (rotation, translation) = options.rotateRootTransform(rotation, translation);
+                }
+                keyframes.push(Rc::new(CalCoreKeyframe::new(*time, translation, rotation)));
+            }
+            duration = duration.max(*times.last().unwrap());
+
+            let mut track = CalCoreTrack::new(boneId, true, true, true, keyframes);
+            if options.collapse_sequences {
+                track.collapseSequences(
+                    options.translation_tolerance,
+                    options.rotation_tolerance_degrees,
+                );
+            }
+            if options.compress_tracks {
+                track.compress(
+                    options.translation_tolerance,
+                    options.rotation_tolerance_degrees,
+                    skeleton,
+                );
+            }
+            tracks.push(Rc::new(RefCell::new(track)));
+        }
+
+        Ok(CalCoreAnimation::new(duration, tracks))
+    }
+
+    /*****************************************************************************/
+    /** Loads the core materials.
+     *
+     * This function loads a core material for every material of the document.
+     * glTF describes metal and roughness rather than Phong colours, so the
+     * specular colour and shininess are approximated from them. The textures
+     * become maps, named after the image file they come from.
+     *****************************************************************************/
+    pub fn loadCoreMaterials(&self) -> Result<Vec<CalCoreMaterial>, LoaderError> {
+        let mut materials = Vec::new();
+        for (materialId, material) in self.array("materials").iter().enumerate() {
+            let coreMaterial = self
+                .loadCoreMaterial(material)
+                .map_err(|e| e.in_element(format!("material {materialId}")))?;
+            materials.push(coreMaterial);
+        }
+        Ok(materials)
+    }
+
+    fn loadCoreMaterial(&self, material: &JsonValue) -> Result<CalCoreMaterial, LoaderError> {
+        let pbr = material.get("pbrMetallicRoughness");
+        let baseColor = match pbr {
+            Some(pbr) => numbers(pbr, "baseColorFactor", 4)?,
+            None => None,
+        }
+        .unwrap_or(vec![1.0; 4]);
+        let factor = |name: &str| pbr.and_then(|pbr| pbr.get(name)?.as_f64()).unwrap_or(1.0);
+        let metallic = factor("metallicFactor");
+        let roughness = factor("roughnessFactor");
+
+        let byte = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let color = |scale: f64| {
+            Color::new(
+                byte(baseColor[0] * scale),
+                byte(baseColor[1] * scale),
+                byte(baseColor[2] * scale),
+                byte(baseColor[3]),
+            )
+        };
+        let specular = byte(0.04 + 0.96 * metallic);
+
+        let mut maps = Vec::new();
+        let textures = [
+            (
+                pbr.and_then(|pbr| pbr.get("baseColorTexture")),
+                "Diffuse Color",
+            ),
+            (material.get("normalTexture"), "Bump"),
+            (
+                pbr.and_then(|pbr| pbr.get("metallicRoughnessTexture")),
+                "Specular Level",
+            ),
+            (material.get("occlusionTexture"), "Ambient Color"),
+            (material.get("emissiveTexture"), "Self-Illumination"),
+        ];
+        for (textureInfo, mapType) in textures {
+            let Some(textureInfo) = textureInfo else {
+                continue;
+            };
+            let filename = self.textureFilename(requiredIndex(textureInfo, "index")?)?;
+            maps.push(Map::new(filename, String::from(mapType), 0));
+        }
+
+        let mut coreMaterial = CalCoreMaterial::new(
+            color(0.2),
+            color(1.0),
+            Color::new(specular, specular, specular, 255),
+            ((1.0 - roughness.clamp(0.0, 1.0)) * 128.0) as f32,
+            maps,
+        );
+        if let Some(name) = material.get("name").and_then(JsonValue::as_str) {
+            coreMaterial.setName(name.to_string());
+        }
+        Ok(coreMaterial)
+    }
+
+    /// Returns the file of the image of a texture, or the name of the image
+    /// when it is embedded in the document.
+    fn textureFilename(&self, textureId: usize) -> Result<String, LoaderError> {
+        let texture = self.item("textures", textureId)?;
+        let Some(imageId) = index(texture, "source")? else {
+            return Ok(format!("texture{textureId}"));
+        };
+        let image = self.item("images", imageId)?;
+        match image.get("uri").and_then(JsonValue::as_str) {
+            Some(uri) if !uri.starts_with("data:") => Ok(decodeUri(uri)),
+            _ => Ok(image
+                .get("name")
+                .and_then(JsonValue::as_str)
+                .map_or_else(|| format!("image{imageId}"), str::to_string)),
+        }
+    }
+}
+
+/// Samples an animation sampler at a time, holding the first and last keys
+/// outside of its times.
+fn sample<T: Blend<Item = T> + Copy>(
+    times: &[f32],
+    step: bool,
+    time: f32,
+    value: impl Fn(usize) -> T,
+) -> T {
+    let next = times.partition_point(|t| *t <= time);
+    if next == 0 {
+        return value(0);
+    }
+    if next == times.len() || step {
+        return value(next - 1);
+    }
+    let (t0, t1) = (times[next - 1], times[next]);
+    let mut result = value(next - 1);
+    result.blend((time - t0) / (t1 - t0), &value(next));
+    result
+}
+
+/*****************************************************************************/
+/** Loads a core model from a glTF file.
+ *
+ * This function loads the skeleton of the first skin of a glTF file, the
+ * meshes using that skin, every animation and every material into a core
+ * model. Each material gets a core material thread with the same ID, which is
+ * what the submeshes refer to. The file system and the loader options of the
+ * core model are used.
+ *
+ * @param filename The `.gltf` or `.glb` file to load.
+ * @param coreModel The core model to load into, which must have an empty
+ *                  skeleton.
+ *****************************************************************************/
+pub fn loadCoreModel(filename: &Path, coreModel: &mut CalCoreModel) -> Result<(), LoaderError> {
+    let document = loadGltfFromFileSystem(coreModel.getFileSystem(), filename)?;
+    let options = coreModel.getLoaderOptions().clone();
+    let skeleton = coreModel.getCoreSkeleton().clone();
+    let inFile = |asset: AssetKind| move |e: LoaderError| e.in_file(filename, asset);
+
+    if document.getSkinCount() == 0 {
+        return Err(formatError("glTF file has no skin").in_file(filename, AssetKind::Model));
+    }
+
+    document
+        .loadCoreSkeleton(0, &skeleton, &options)
+        .map_err(inFile(AssetKind::Skeleton))?;
+
+    for material in document
+        .loadCoreMaterials()
+        .map_err(inFile(AssetKind::Material))?
+    {
+        let name = material.getName().to_string();
+        let materialId = coreModel.addCoreMaterial(material);
+        if !name.is_empty() {
+            coreModel.addMaterialName(&name, materialId)?;
+        }
+    }
+
+    coreModel.createDefaultMaterialSet(0);
+
+    for mesh in document
+        .loadCoreMeshes(0, &options)
+        .map_err(inFile(AssetKind::Mesh))?
+    {
+        let name = mesh.borrow().getName().to_string();
+        let meshId = coreModel.addCoreMesh(mesh);
+        if !name.is_empty() {
+            coreModel.addMeshName(&name, meshId)?;
+        }
+    }
+
+    for animation in document
+        .loadCoreAnimations(0, &skeleton, &options)
+        .map_err(inFile(AssetKind::Animation))?
+    {
+        let name = animation.borrow().getName().to_string();
+        let animationId = coreModel.addCoreAnimation(animation);
+        if !name.is_empty() {
+            coreModel.addAnimationName(&name, animationId)?;
+        }
+    }
+
+    Ok(())
+}
+
+const TARGET_ARRAY_BUFFER: usize = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: usize = 34963;
+
+fn accessorType(components: usize) -> &'static str {
+    match components {
+        1 => "SCALAR",
+        2 => "VEC2",
+        3 => "VEC3",
+        16 => "MAT4",
+        _ => "VEC4",
+    }
+}
+
+/// Returns a transform as a column major 4x4 matrix.
+fn transformMatrix(transform: &Transform) -> [f32; 16] {
+    let r = cgmath::Matrix3::from(transform.rotation);
+    let t = transform.translation;
+    [
+        r.x.x, r.x.y, r.x.z, 0.0, r.y.x, r.y.y, r.y.z, 0.0, r.z.x, r.z.y, r.z.z, 0.0, t.x, t.y,
+        t.z, 1.0,
+    ]
+}
+
+fn vectorJson(v: &CalVector<f32>) -> JsonValue {
+    JsonValue::from(vec![v.x, v.y, v.z])
+}
+
+fn quaternionJson(q: &CalQuaternion<f32>) -> JsonValue {
+    JsonValue::from(vec![q.v.x, q.v.y, q.v.z, q.s])
+}
+
+/// Collects the binary data and the accessors of a glTF document being
+/// written. All the data goes in the one buffer of the `.glb` file.
+#[derive(Default)]
+struct GlbBuilder {
+    binary: Vec<u8>,
+    bufferViews: Vec<JsonValue>,
+    accessors: Vec<JsonValue>,
+}
+
+impl GlbBuilder {
+    fn addAccessor(
+        &mut self,
+        bytes: &[u8],
+        componentType: u64,
+        components: usize,
+        count: usize,
+        target: Option<usize>,
+        bounds: Vec<(&str, JsonValue)>,
+    ) -> usize {
+        // every view starts on a 4 byte boundary, as floats need
+        while !self.binary.len().is_multiple_of(4) {
+            self.binary.push(0);
+        }
+        let mut bufferView = vec![
+            ("buffer", JsonValue::from(0)),
+            ("byteOffset", self.binary.len().into()),
+            ("byteLength", bytes.len().into()),
+        ];
+        if let Some(target) = target {
+            bufferView.push(("target", target.into()));
+        }
+        self.binary.extend_from_slice(bytes);
+        self.bufferViews.push(JsonValue::object(bufferView));
+
+        let mut accessor = vec![
+            ("bufferView", JsonValue::from(self.bufferViews.len() - 1)),
+            ("componentType", (componentType as usize).into()),
+            ("count", count.into()),
+            ("type", accessorType(components).into()),
+        ];
+        accessor.extend(bounds);
+        self.accessors.push(JsonValue::object(accessor));
+        self.accessors.len() - 1
+    }
+
+    /// Adds an accessor of floats, with the bounds glTF requires for
+    /// positions and animation times if `bounds` is set.
+    fn addFloats(
+        &mut self,
+        values: &[f32],
+        components: usize,
+        bounds: bool,
+        target: Option<usize>,
+    ) -> usize {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let count = values.len() / components;
+        let mut extra = Vec::new();
+        if bounds && count > 0 {
+            let mut min = values[..components].to_vec();
+            let mut max = min.clone();
+            for element in values.chunks(components) {
+                for (component, value) in element.iter().enumerate() {
+                    min[component] = min[component].min(*value);
+                    max[component] = max[component].max(*value);
+                }
+            }
+            extra.push(("min", JsonValue::from(min)));
+            extra.push(("max", JsonValue::from(max)));
+        }
+        self.addAccessor(&bytes, COMPONENT_FLOAT, components, count, target, extra)
+    }
+
+    /// Adds an accessor of three component vectors that are all zero, which
+    /// needs no data at all.
+    fn addZeroVectors(&mut self, count: usize) -> usize {
+        let zero = || JsonValue::from(vec![0.0f32; 3]);
+        self.accessors.push(JsonValue::object([
+            ("componentType", JsonValue::from(COMPONENT_FLOAT as usize)),
+            ("count", count.into()),
+            ("type", "VEC3".into()),
+            ("min", zero()),
+            ("max", zero()),
+        ]));
+        self.accessors.len() - 1
+    }
+
+    /// Lays out a `.glb` file holding the JSON and the binary data.
+    fn finish(self, mut json: BTreeMap<String, JsonValue>) -> Vec<u8> {
+        if !self.binary.is_empty() {
+            json.insert(
+                String::from("buffers"),
+                JsonValue::from(vec![JsonValue::object([(
+                    "byteLength",
+                    self.binary.len().into(),
+                )])]),
+            );
+            json.insert(
+                String::from("bufferViews"),
+                JsonValue::Array(self.bufferViews),
+            );
+        }
+        if !self.accessors.is_empty() {
+            json.insert(String::from("accessors"), JsonValue::Array(self.accessors));
+        }
+
+        let mut text = String::new();
+        JsonValue::Object(json).write(&mut text);
+        // chunks are padded to 4 bytes, JSON with spaces and data with zeros
+        while !text.len().is_multiple_of(4) {
+            text.push(' ');
+        }
+        let mut binary = self.binary;
+        while !binary.len().is_multiple_of(4) {
+            binary.push(0);
+        }
+
+        let mut length = 12 + 8 + text.len();
+        if !binary.is_empty() {
+            length += 8 + binary.len();
+        }
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(GLB_MAGIC);
+        glb.extend_from_slice(&GLB_VERSION.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        glb.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&GLB_CHUNK_JSON.to_le_bytes());
+        glb.extend_from_slice(text.as_bytes());
+        if !binary.is_empty() {
+            glb.extend_from_slice(&(binary.len() as u32).to_le_bytes());
+            glb.extend_from_slice(&GLB_CHUNK_BIN.to_le_bytes());
+            glb.extend_from_slice(&binary);
+        }
+        glb
+    }
+}
+
+/*****************************************************************************/
+/** Saves a core model as a binary glTF file.
+ *
+ * This function saves the skeleton, meshes, materials and animations of a
+ * core model to a `.glb` file, see `saveCoreModelToGlb`.
+ *
+ * @param filename The name of the file to save to.
+ * @param coreModel The core model to save.
+ *****************************************************************************/
+pub fn saveCoreModel(filename: &PathBuf, coreModel: &CalCoreModel) -> Result<(), SaverError> {
+    fs::write(filename, saveCoreModelToGlb(coreModel)?)?;
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core model as binary glTF.
+ *
+ * This function returns the contents of a `.glb` file holding a core model.
+ * Every bone becomes a node and a joint of the one skin, in bone id order.
+ * Every core mesh becomes a skinned mesh with a primitive for each submesh,
+ * using the material of core material set 0 of its material thread. The
+ * morph targets of a mesh are matched up by name across its submeshes, as
+ * glTF needs every primitive of a mesh to have the same targets. Every core
+ * animation becomes an animation named by its ID.
+ *
+ * @param coreModel The core model to save.
+ *
+ * @return The contents of the file.
+ *****************************************************************************/
+pub fn saveCoreModelToGlb(coreModel: &CalCoreModel) -> Result<Vec<u8>, SaverError> {
+    let mut builder = GlbBuilder::default();
+    let mut json = BTreeMap::new();
+    let mut nodes = Vec::new();
+    let mut sceneNodes = Vec::new();
+
+    let skeleton = coreModel.getCoreSkeleton().borrow();
+    let vectorCoreBone = skeleton.getVectorCoreBone();
+    let listChildIds = saver::childIdsFromParentIds(&skeleton)?;
+    let mut inverseBindMatrices = Vec::with_capacity(vectorCoreBone.len() * 16);
+    for (boneId, coreBone) in vectorCoreBone.iter().enumerate() {
+        let coreBone = coreBone.borrow();
+        let mut node = vec![
+            ("name", JsonValue::from(coreBone.getName())),
+            ("translation", vectorJson(coreBone.getTranslation())),
+            (
+                "rotation",
+                quaternionJson(&coreBone.getRotation().conjugate()),
+            ),
+        ];
+        if !listChildIds[boneId].is_empty() {
+            let children = listChildIds[boneId].iter().map(|id| *id as usize);
+            node.push(("children", JsonValue::from(children.collect::<Vec<_>>())));
+        }
+        if coreBone.getParentId() == -1 {
+            sceneNodes.push(boneId);
+        }
+        nodes.push(JsonValue::object(node));
+
+        let boneSpace = Transform {
+            rotation: coreBone.getRotationBoneSpace().conjugate(),
+            translation: *coreBone.getTranslationBoneSpace(),
+        };
+        inverseBindMatrices.extend_from_slice(&transformMatrix(&boneSpace));
+    }
+    let boneCount = vectorCoreBone.len();
+    if boneCount > 0 {
+        let accessor = builder.addFloats(&inverseBindMatrices, 16, false, None);
+        json.insert(
+            String::from("skins"),
+            JsonValue::from(vec![JsonValue::object([
+                (
+                    "joints",
+                    JsonValue::from((0..boneCount).collect::<Vec<_>>()),
+                ),
+                ("inverseBindMatrices", accessor.into()),
+            ])]),
+        );
+    }
+
+    let (materials, textures) = saveMaterials(coreModel.getCoreMaterials());
+
+    let mut meshes = Vec::new();
+    for (meshId, coreMesh) in coreModel.getCoreMeshes().iter().enumerate() {
+        let Some(mesh) = saveMesh(&mut builder, coreModel, &coreMesh.borrow(), boneCount)
+            .map_err(|e| SaverError::FormatError(format!("{e} in mesh {meshId}")))?
+        else {
+            continue;
+        };
+        let name = match coreMesh.borrow().getName() {
+            "" => format!("mesh{meshId}"),
+            name => name.to_string(),
+        };
+        let mut node = vec![
+            ("name", JsonValue::from(name)),
+            ("mesh", meshes.len().into()),
+        ];
+        if boneCount > 0 {
+            node.push(("skin", JsonValue::from(0)));
+        }
+        meshes.push(mesh);
+        sceneNodes.push(nodes.len());
+        nodes.push(JsonValue::object(node));
+    }
+
+    let mut animations = Vec::new();
+    for animationId in 0..coreModel.getCoreAnimationCount() {
+        let Some(coreAnimation) = coreModel.getCoreAnimation(animationId) else {
+            continue;
+        };
+        if let Some(animation) = saveAnimation(
+            &mut builder,
+            animationId,
+            &coreAnimation.borrow(),
+            boneCount,
+        )? {
+            animations.push(animation);
+        }
+    }
+
+    json.insert(
+        String::from("asset"),
+        JsonValue::object([("version", JsonValue::from("2.0"))]),
+    );
+    json.insert(String::from("scene"), JsonValue::from(0));
+    json.insert(
+        String::from("scenes"),
+        JsonValue::from(vec![JsonValue::object([(
+            "nodes",
+            JsonValue::from(sceneNodes),
+        )])]),
+    );
+    let collections = [
+        ("nodes", nodes),
+        ("meshes", meshes),
+        ("materials", materials),
+        ("animations", animations),
+    ];
+    for (name, collection) in collections {
+        if !collection.is_empty() {
+            json.insert(String::from(name), JsonValue::Array(collection));
+        }
+    }
+    if !textures.is_empty() {
+        let images = textures
+            .iter()
+            .map(|filename| JsonValue::object([("uri", JsonValue::from(encodeUri(filename)))]))
+            .collect();
+        let textures = (0..textures.len())
+            .map(|imageId| JsonValue::object([("source", JsonValue::from(imageId))]))
+            .collect();
+        json.insert(String::from("images"), JsonValue::Array(images));
+        json.insert(String::from("textures"), JsonValue::Array(textures));
+    }
+
+    Ok(builder.finish(json))
+}
+
+/// Escapes the characters of a filename that a URI cannot hold.
+fn encodeUri(filename: &str) -> String {
+    let mut uri = String::with_capacity(filename.len());
+    for byte in filename.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            b'\\' => uri.push('/'),
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+/// Converts the core materials, returning the glTF materials and the files
+/// of the textures they use. This reverses the approximations made when
+/// loading.
+fn saveMaterials(coreMaterials: &[CalCoreMaterial]) -> (Vec<JsonValue>, Vec<String>) {
+    let mut materials = Vec::new();
+    let mut textures = Vec::new();
+    for coreMaterial in coreMaterials {
+        let diffuse = coreMaterial.getDiffuseColor();
+        let unit = |byte: u8| byte as f64 / 255.0;
+        let specular = unit(coreMaterial.getSpecularColor().red());
+        let mut pbr = vec![
+            (
+                "baseColorFactor",
+                JsonValue::from(vec![
+                    unit(diffuse.red()),
+                    unit(diffuse.green()),
+                    unit(diffuse.blue()),
+                    unit(diffuse.alpha()),
+                ]),
+            ),
+            (
+                "metallicFactor",
+                ((specular - 0.04) / 0.96).clamp(0.0, 1.0).into(),
+            ),
+            (
+                "roughnessFactor",
+                (1.0 - coreMaterial.getShininess() as f64 / 128.0)
+                    .clamp(0.0, 1.0)
+                    .into(),
+            ),
+        ];
+        let mut material = Vec::new();
+
+        for (mapId, map) in coreMaterial.getMaps().iter().enumerate() {
+            let textureInfo = JsonValue::object([("index", JsonValue::from(textures.len()))]);
+            // maps without a type are taken to be the diffuse map
+            let slot = match map.mapType.as_str() {
+                "" if mapId == 0 => "baseColorTexture",
+                "Diffuse Color" => "baseColorTexture",
+                "Specular Level" => "metallicRoughnessTexture",
+                "Bump" => "normalTexture",
+                "Ambient Color" => "occlusionTexture",
+                "Self-Illumination" => "emissiveTexture",
+                _ => continue,
+            };
+            let members = match slot {
+                "baseColorTexture" | "metallicRoughnessTexture" => &mut pbr,
+                _ => &mut material,
+            };
+            if members.iter().any(|(name, _)| *name == slot) {
+                continue;
+            }
+            members.push((slot, textureInfo));
+            if slot == "emissiveTexture" {
+                material.push(("emissiveFactor", JsonValue::from(vec![1.0f32; 3])));
+            }
+            textures.push(map.strFilename.clone());
+        }
+
+        material.push(("pbrMetallicRoughness", JsonValue::object(pbr)));
+        if !coreMaterial.getName().is_empty() {
+            material.push(("name", JsonValue::from(coreMaterial.getName())));
+        }
+        materials.push(JsonValue::object(material));
+    }
+    (materials, textures)
+}
+
+/// Converts a core mesh, returning None if it has nothing to draw.
+fn saveMesh(
+    builder: &mut GlbBuilder,
+    coreModel: &CalCoreModel,
+    coreMesh: &CalCoreMesh,
+    boneCount: usize,
+) -> Result<Option<JsonValue>, String> {
+    // the targets every primitive needs, by name
+    let mut targetNames = Vec::<String>::new();
+    for coreSubmesh in coreMesh.getCoreSubmeshes() {
+        for morphTarget in coreSubmesh.borrow().getVectorCoreSubMorphTarget() {
+            if !targetNames.iter().any(|name| name == morphTarget.getName()) {
+                targetNames.push(morphTarget.getName().to_string());
+            }
+        }
+    }
+
+    let mut primitives = Vec::new();
+    for (submeshId, coreSubmesh) in coreMesh.getCoreSubmeshes().iter().enumerate() {
+        let coreSubmesh = coreSubmesh.borrow();
+        if let Some(primitive) =
+            savePrimitive(builder, coreModel, &coreSubmesh, boneCount, &targetNames)
+                .map_err(|e| format!("{e} in submesh {submeshId}"))?
+        {
+            primitives.push(primitive);
+        }
+    }
+    if primitives.is_empty() {
+        return Ok(None);
+    }
+
+    let mut mesh = vec![("primitives", JsonValue::Array(primitives))];
+    if !targetNames.is_empty() {
+        mesh.push((
+            "extras",
+            JsonValue::object([("targetNames", JsonValue::from(targetNames))]),
+        ));
+    }
+    Ok(Some(JsonValue::object(mesh)))
+}
+
+fn savePrimitive(
+    builder: &mut GlbBuilder,
+    coreModel: &CalCoreModel,
+    coreSubmesh: &CalCoreSubmesh,
+    boneCount: usize,
+    targetNames: &[String],
+) -> Result<Option<JsonValue>, String> {
+    let vectorVertex = coreSubmesh.getVectorVertex();
+    let vertexCount = vectorVertex.len();
+    if vertexCount == 0 || coreSubmesh.getVectorFace().is_empty() {
+        return Ok(None);
+    }
+
+    let flatten = |vectors: &mut dyn Iterator<Item = CalVector<f32>>| -> Vec<f32> {
+        vectors.flat_map(|v| [v.x, v.y, v.z]).collect()
+    };
+    let positions = flatten(&mut vectorVertex.iter().map(|vertex| vertex.position));
+    let normals = flatten(&mut vectorVertex.iter().map(|vertex| vertex.normal));
+
+    let mut attributes = vec![
+        (
+            String::from("POSITION"),
+            builder.addFloats(&positions, 3, true, Some(TARGET_ARRAY_BUFFER)),
+        ),
+        (
+            String::from("NORMAL"),
+            builder.addFloats(&normals, 3, false, Some(TARGET_ARRAY_BUFFER)),
+        ),
+    ];
+
+    let white = CalVector::new(1.0, 1.0, 1.0);
+    if vectorVertex
+        .iter()
+        .any(|vertex| vertex.vertexColor != white)
+    {
+        let colors = flatten(&mut vectorVertex.iter().map(|vertex| vertex.vertexColor));
+        attributes.push((
+            String::from("COLOR_0"),
+            builder.addFloats(&colors, 3, false, Some(TARGET_ARRAY_BUFFER)),
+        ));
+    }
+
+    for (textureCoordinateId, textureCoordinates) in coreSubmesh
+        .getVectorVectorTextureCoordinate()
+        .iter()
+        .enumerate()
+    {
+        if textureCoordinates.len() != vertexCount {
+            return Err(format!(
+                "{} texture coordinates for {vertexCount} vertices",
+                textureCoordinates.len()
+            ));
+        }
+        let values: Vec<f32> = textureCoordinates.iter().flat_map(|t| [t.u, t.v]).collect();
+        attributes.push((
+            format!("TEXCOORD_{textureCoordinateId}"),
+            builder.addFloats(&values, 2, false, Some(TARGET_ARRAY_BUFFER)),
+        ));
+    }
+
+    if boneCount > 0 {
+        // glTF takes influences four at a time
+        let maxInfluences = vectorVertex
+            .iter()
+            .map(|vertex| vertex.vectorInfluence.len())
+            .max()
+            .unwrap_or(0);
+        for set in 0..maxInfluences.div_ceil(4).max(1) {
+            let mut joints = Vec::with_capacity(vertexCount * 8);
+            let mut weights = Vec::with_capacity(vertexCount * 4);
+            for (vertexId, vertex) in vectorVertex.iter().enumerate() {
+                for slot in set * 4..set * 4 + 4 {
+                    let (boneId, weight) = match vertex.vectorInfluence.get(slot) {
+                        Some(influence) => (influence.boneId, influence.weight),
+                        None => (0, 0.0),
+                    };
+                    if boneId < 0 || boneId as usize >= boneCount || boneId > u16::MAX as i32 {
+                        return Err(format!("Invalid bone id {boneId} in vertex {vertexId}"));
+                    }
+                    joints.extend_from_slice(&(boneId as u16).to_le_bytes());
+                    weights.push(weight);
+                }
+            }
+            let jointsAccessor = builder.addAccessor(
+                &joints,
+                COMPONENT_UNSIGNED_SHORT,
+                4,
+                vertexCount,
+                Some(TARGET_ARRAY_BUFFER),
+                Vec::new(),
+            );
+            attributes.push((format!("JOINTS_{set}"), jointsAccessor));
+            attributes.push((
+                format!("WEIGHTS_{set}"),
+                builder.addFloats(&weights, 4, false, Some(TARGET_ARRAY_BUFFER)),
+            ));
+        }
+    }
+
+    let mut indices = Vec::with_capacity(coreSubmesh.getVectorFace().len() * 12);
+    for face in coreSubmesh.getVectorFace() {
+        for vertexId in face.vertexId {
+            if vertexId < 0 || vertexId as usize >= vertexCount {
+                return Err(format!("Invalid vertex index {vertexId} in face"));
+            }
+            indices.extend_from_slice(&(vertexId as u32).to_le_bytes());
+        }
+    }
+    let indicesAccessor = builder.addAccessor(
+        &indices,
+        COMPONENT_UNSIGNED_INT,
+        1,
+        coreSubmesh.getVectorFace().len() * 3,
+        Some(TARGET_ELEMENT_ARRAY_BUFFER),
+        Vec::new(),
+    );
+
+    // targets hold displacements from the vertex
+    let mut targets = Vec::new();
+    for name in targetNames {
+        let morphTarget = coreSubmesh
+            .getVectorCoreSubMorphTarget()
+            .iter()
+            .find(|morphTarget| morphTarget.getName() == name);
+        let Some(morphTarget) = morphTarget else {
+            let zeros = builder.addZeroVectors(vertexCount);
+            targets.push(JsonValue::object([("POSITION", JsonValue::from(zeros))]));
+            continue;
+        };
+        let mut positionDeltas = Vec::with_capacity(vertexCount * 3);
+        let mut normalDeltas = Vec::with_capacity(vertexCount * 3);
+        for (vertex, blendVertex) in vectorVertex.iter().zip(morphTarget.getVectorBlendVertex()) {
+            let (position, normal) = match saver::isBlendVertexSet(blendVertex) {
+                true => (
+                    blendVertex.position - vertex.position,
+                    blendVertex.normal - vertex.normal,
+                ),
+                false => (CalVector::new(0.0, 0.0, 0.0), CalVector::new(0.0, 0.0, 0.0)),
+            };
+            positionDeltas.extend_from_slice(&[position.x, position.y, position.z]);
+            normalDeltas.extend_from_slice(&[normal.x, normal.y, normal.z]);
+        }
+        if positionDeltas.len() != vertexCount * 3 {
+            return Err(format!("Morph target {name} does not cover every vertex"));
+        }
+        targets.push(JsonValue::object([
+            (
+                "POSITION",
+                JsonValue::from(builder.addFloats(&positionDeltas, 3, true, None)),
+            ),
+            (
+                "NORMAL",
+                JsonValue::from(builder.addFloats(&normalDeltas, 3, false, None)),
+            ),
+        ]));
+    }
+
+    let mut primitive = vec![
+        (
+            "attributes",
+            JsonValue::Object(
+                attributes
+                    .into_iter()
+                    .map(|(name, accessor)| (name, JsonValue::from(accessor)))
+                    .collect(),
+            ),
+        ),
+        ("indices", indicesAccessor.into()),
+        ("mode", MODE_TRIANGLES.into()),
+    ];
+    let materialId = coreModel.getCoreMaterialId(coreSubmesh.getCoreMaterialThreadId(), 0);
+    if let Some(materialId) = materialId
+        && materialId >= 0
+        && (materialId as usize) < coreModel.getCoreMaterials().len()
+    {
+        primitive.push(("material", (materialId as usize).into()));
+    }
+    if !targets.is_empty() {
+        primitive.push(("targets", JsonValue::Array(targets)));
+    }
+    Ok(Some(JsonValue::object(primitive)))
+}
+
+/// Converts a core animation, returning None if it has no keyframes.
+fn saveAnimation(
+    builder: &mut GlbBuilder,
+    animationId: usize,
+    coreAnimation: &CalCoreAnimation,
+    boneCount: usize,
+) -> Result<Option<JsonValue>, SaverError> {
+    let mut channels = Vec::new();
+    let mut samplers = Vec::new();
+    for coreTrack in coreAnimation.getListCoreTrack() {
+        let coreTrack = coreTrack.borrow();
+        let boneId = coreTrack.getCoreBoneId();
+        if boneId >= boneCount {
+            return Err(SaverError::FormatError(format!(
+                "Invalid bone id {boneId} in animation {animationId}"
+            )));
+        }
+        let count = coreTrack.getCoreKeyframeCount();
+        if count == 0 {
+            continue;
+        }
+
+        let mut times = Vec::with_capacity(count);
+        let mut translations = Vec::with_capacity(count * 3);
+        let mut rotations = Vec::with_capacity(count * 4);
+        for keyframeId in 0..count {
+            let keyframe = coreTrack.getCoreKeyframe(keyframeId).unwrap();
+            let translation = keyframe.getTranslation();
+            let rotation = keyframe.getRotation().conjugate();
+            times.push(keyframe.getTime());
+            translations.extend_from_slice(&[translation.x, translation.y, translation.z]);
+            rotations.extend_from_slice(&[rotation.v.x, rotation.v.y, rotation.v.z, rotation.s]);
+        }
+
+        let input = builder.addFloats(&times, 1, true, None);
+        let outputs = [
+            (
+                "translation",
+                builder.addFloats(&translations, 3, false, None),
+            ),
+            ("rotation", builder.addFloats(&rotations, 4, false, None)),
+        ];
+        for (path, output) in outputs {
+            channels.push(JsonValue::object([
+                ("sampler", JsonValue::from(samplers.len())),
+                (
+                    "target",
+                    JsonValue::object([("node", JsonValue::from(boneId)), ("path", path.into())]),
+                ),
+            ]));
+            samplers.push(JsonValue::object([
+                ("input", JsonValue::from(input)),
+                ("output", output.into()),
+                ("interpolation", "LINEAR".into()),
+            ]));
+        }
+    }
+
+    if channels.is_empty() {
+        return Ok(None);
+    }
+    let name = match coreAnimation.getName() {
+        "" => format!("animation{animationId}"),
+        name => name.to_string(),
+    };
+    Ok(Some(JsonValue::object([
+        ("name", JsonValue::from(name)),
+        ("channels", JsonValue::Array(channels)),
+        ("samplers", JsonValue::Array(samplers)),
+    ])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::loader::{self, LoaderOptions};
+    use crate::core::submesh::Vertex;
+    use crate::core::testdata;
+    use crate::core::vfs::TarFileSystem;
+
+    fn sameRotation(a: &CalQuaternion<f32>, b: &CalQuaternion<f32>) -> bool {
+        a.dot(*b).abs() > 1.0 - 1e-5
+    }
+
+    #[test]
+    fn exported_models_import_the_same() {
+        let options = LoaderOptions::default();
+        let mut coreModel = CalCoreModel::default();
+        let skeleton = coreModel.getCoreSkeleton().clone();
+        loader::loadCoreSkeletonFromReader(testdata::SKELETON_XML.as_bytes(), &skeleton, &options)
+            .unwrap();
+        let mesh = testdata::mesh();
+        coreModel.addCoreMesh(mesh.clone());
+        let animation = testdata::animation(&skeleton);
+        coreModel.addCoreAnimation(animation.clone());
+
+        let document = loadGltfFromBytes(&saveCoreModelToGlb(&coreModel).unwrap(), None).unwrap();
+        let loadedSkeleton = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+        document
+            .loadCoreSkeleton(0, &loadedSkeleton, &options)
+            .unwrap();
+        let loadedMeshes = document.loadCoreMeshes(0, &options).unwrap();
+        let loadedAnimations = document
+            .loadCoreAnimations(0, &loadedSkeleton, &options)
+            .unwrap();
+
+        // the skeleton
+        let (skeleton, loadedSkeleton) = (skeleton.borrow(), loadedSkeleton.borrow());
+        let bones = skeleton.getVectorCoreBone();
+        let loadedBones = loadedSkeleton.getVectorCoreBone();
+        assert_eq!(bones.len(), loadedBones.len());
+        for (bone, loadedBone) in bones.iter().zip(loadedBones) {
+            let (bone, loadedBone) = (bone.borrow(), loadedBone.borrow());
+            assert_eq!(bone.getName(), loadedBone.getName());
+            assert_eq!(bone.getParentId(), loadedBone.getParentId());
+            assert!((bone.getTranslation() - loadedBone.getTranslation()).magnitude() < 1e-5);
+            assert!(sameRotation(bone.getRotation(), loadedBone.getRotation()));
+            assert!(
+                (bone.getTranslationBoneSpace() - loadedBone.getTranslationBoneSpace()).magnitude()
+                    < 1e-5
+            );
+            assert!(sameRotation(
+                bone.getRotationBoneSpace(),
+                loadedBone.getRotationBoneSpace()
+            ));
+        }
+
+        // the submeshes
+        assert_eq!(loadedMeshes.len(), 1);
+        let (mesh, loadedMesh) = (mesh.borrow(), loadedMeshes[0].borrow());
+        assert_eq!(
+            mesh.getCoreSubmeshes().len(),
+            loadedMesh.getCoreSubmeshes().len()
+        );
+        for (submesh, loadedSubmesh) in mesh
+            .getCoreSubmeshes()
+            .iter()
+            .zip(loadedMesh.getCoreSubmeshes())
+        {
+            let (submesh, loadedSubmesh) = (submesh.borrow(), loadedSubmesh.borrow());
+            let vertices = submesh.getVectorVertex();
+            let loadedVertices = loadedSubmesh.getVectorVertex();
+            assert_eq!(vertices.len(), loadedVertices.len());
+            for (vertex, loadedVertex) in vertices.iter().zip(loadedVertices) {
+                assert_eq!(vertex.position, loadedVertex.position);
+                assert_eq!(vertex.normal, loadedVertex.normal);
+                let influences = |vertex: &Vertex| {
+                    vertex
+                        .vectorInfluence
+                        .iter()
+                        .map(|influence| (influence.boneId, influence.weight))
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(influences(vertex), influences(loadedVertex));
+            }
+            let faces = |submesh: &CalCoreSubmesh| {
+                submesh
+                    .getVectorFace()
+                    .iter()
+                    .map(|face| face.vertexId)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(faces(&submesh), faces(&loadedSubmesh));
+            let morphNames = |submesh: &CalCoreSubmesh| {
+                submesh
+                    .getVectorCoreSubMorphTarget()
+                    .iter()
+                    .map(|morphTarget| morphTarget.getName().to_string())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(morphNames(&submesh), morphNames(&loadedSubmesh));
+        }
+
+        // the keyframes
+        assert_eq!(loadedAnimations.len(), 1);
+        let (animation, loadedAnimation) = (animation.borrow(), loadedAnimations[0].borrow());
+        assert!((animation.getDuration() - loadedAnimation.getDuration()).abs() < 1e-6);
+        assert_eq!(
+            animation.getListCoreTrack().len(),
+            loadedAnimation.getListCoreTrack().len()
+        );
+        for track in animation.getListCoreTrack() {
+            let track = track.borrow();
+            let loadedTrack = loadedAnimation
+                .getListCoreTrack()
+                .iter()
+                .find(|loadedTrack| loadedTrack.borrow().getCoreBoneId() == track.getCoreBoneId())
+                .unwrap()
+                .borrow();
+            assert_eq!(
+                track.getCoreKeyframeCount(),
+                loadedTrack.getCoreKeyframeCount()
+            );
+            for keyframeId in 0..track.getCoreKeyframeCount() {
+                let keyframe = track.getCoreKeyframe(keyframeId).unwrap();
+                let loadedKeyframe = loadedTrack.getCoreKeyframe(keyframeId).unwrap();
+                assert!((keyframe.getTime() - loadedKeyframe.getTime()).abs() < 1e-6);
+                assert!(
+                    (keyframe.getTranslation() - loadedKeyframe.getTranslation()).magnitude()
+                        < 1e-5
+                );
+                assert!(sameRotation(
+                    keyframe.getRotation(),
+                    loadedKeyframe.getRotation()
+                ));
+            }
+        }
+    }
+
+    // a node skinned by a single joint, with a mesh of four billion vertices
+    const HUGE_ACCESSOR: &str = r#"{
+        "asset": {"version": "2.0"},
+        "nodes": [{"mesh": 0, "skin": 0}, {}],
+        "skins": [{"joints": [1]}],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+        "accessors": [{"count": 4000000000, "componentType": 5126, "type": "VEC3"}]
+    }"#;
+
+    #[test]
+    fn accessor_counts_are_limited_before_allocating() {
+        let document = loadGltfFromBytes(HUGE_ACCESSOR.as_bytes(), None).unwrap();
+        let error = document
+            .loadCoreMeshes(0, &LoaderOptions::default())
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("over the limit"), "{error}");
+    }
+
+    #[test]
+    fn skeletons_are_only_loaded_into_empty_ones() {
+        let options = LoaderOptions::default();
+        let document = loadGltfFromBytes(HUGE_ACCESSOR.as_bytes(), None).unwrap();
+        let skeleton = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+        document.loadCoreSkeleton(0, &skeleton, &options).unwrap();
+        assert_eq!(skeleton.borrow().getVectorCoreBone().len(), 1);
+
+        // the bone ids of the meshes and animations would not match a second one
+        assert!(document.loadCoreSkeleton(0, &skeleton, &options).is_err());
+        assert_eq!(skeleton.borrow().getVectorCoreBone().len(), 1);
+    }
+
+    /// Saves the test assets as a `.gltf` document, returning it along with
+    /// the buffer it refers to by the given URI.
+    fn separateBuffer(uri: &str) -> (Vec<u8>, Vec<u8>) {
+        let mut coreModel = CalCoreModel::default();
+        let skeleton = coreModel.getCoreSkeleton().clone();
+        loader::loadCoreSkeletonFromReader(
+            testdata::SKELETON_XML.as_bytes(),
+            &skeleton,
+            &LoaderOptions::default(),
+        )
+        .unwrap();
+        coreModel.addCoreMesh(testdata::mesh());
+        coreModel.addCoreAnimation(testdata::animation(&skeleton));
+
+        let glb = saveCoreModelToGlb(&coreModel).unwrap();
+        let (text, binaryChunk) = splitGlb(&glb).unwrap();
+        let mut document = json::parse(std::str::from_utf8(text).unwrap()).unwrap();
+        let JsonValue::Object(members) = &mut document else {
+            panic!("glTF JSON is not an object");
+        };
+        let Some(JsonValue::Array(buffers)) = members.get_mut("buffers") else {
+            panic!("glTF JSON has no buffers");
+        };
+        let JsonValue::Object(buffer) = &mut buffers[0] else {
+            panic!("glTF buffer is not an object");
+        };
+        buffer.insert("uri".to_string(), JsonValue::from(uri));
+
+        let mut text = String::new();
+        document.write(&mut text);
+        (text.into_bytes(), binaryChunk.to_vec())
+    }
+
+    #[test]
+    fn models_load_from_archives() {
+        let (document, buffer) = separateBuffer("buffers/model.bin");
+        let archive = testdata::tarArchive(&[
+            ("models/model.gltf", &document),
+            ("models/buffers/model.bin", &buffer),
+        ]);
+
+        let mut coreModel = CalCoreModel::default();
+        coreModel.setFileSystem(Rc::new(TarFileSystem::from_bytes(archive).unwrap()));
+        loadCoreModel(&PathBuf::from("models/model.gltf"), &mut coreModel).unwrap();
+        assert_eq!(
+            coreModel
+                .getCoreSkeleton()
+                .borrow()
+                .getVectorCoreBone()
+                .len(),
+            testdata::skeleton().borrow().getVectorCoreBone().len()
+        );
+        assert_eq!(coreModel.getCoreMeshCount(), 1);
+        assert_eq!(coreModel.getCoreAnimationCount(), 1);
+    }
+
+    #[test]
+    fn buffers_outside_the_directory_are_rejected() {
+        for (uri, inside) in [
+            ("model.bin", true),
+            ("buffers/../model.bin", true),
+            ("../model.bin", false),
+            ("buffers/../../model.bin", false),
+            ("/model.bin", false),
+        ] {
+            let (document, buffer) = separateBuffer(uri);
+            // the buffer is where every URI leads, inside the archive or not
+            let archive = testdata::tarArchive(&[
+                ("models/model.gltf", &document),
+                ("models/model.bin", &buffer),
+                ("model.bin", &buffer),
+            ]);
+            let fileSystem = TarFileSystem::from_bytes(archive).unwrap();
+
+            let result = loadGltfFromFileSystem(&fileSystem, Path::new("models/model.gltf"));
+            match inside {
+                true => assert!(result.is_ok(), "buffer {uri} does not load"),
+                false => {
+                    let error = result.err().unwrap().kind().to_string();
+                    assert!(error.contains("outside the directory"), "{uri}: {error}");
+                }
+            }
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use std::cell::RefCell;
-use std::fs;
-use std::io::BufReader;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
 use std::ops::Mul;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use cgmath::InnerSpace;
 
@@ -11,69 +13,745 @@ use crate::core::submesh::Influence;
 use crate::{CalQuaternion, CalVector};
 
 use super::CoreError;
+use super::animatedmorph::CalCoreAnimatedMorph;
 use super::animation::CalCoreAnimation;
 use super::bone::{CalCoreBone, CalLightType};
 use super::bufreadersource::BufReaderSource;
-use super::datasource::{CalVectorFromDataSrc, DataSource, SourceError};
+use super::datasource::{self, CalVectorFromDataSrc, DataSource, SourceError};
 use super::keyframe::CalCoreKeyframe;
 use super::material::CalCoreMaterial;
 use super::mesh::CalCoreMesh;
+use super::morphkeyframe::CalCoreMorphKeyframe;
+use super::morphtrack::CalCoreMorphTrack;
 use super::skeleton::CalCoreSkeleton;
-use super::submesh::CalCoreSubmesh;
+use super::submesh::{CalCoreSubmesh, TextureCoordinate, Vertex};
 use super::submorphtarget::CalCoreSubMorphTarget;
 use super::track::CalCoreTrack;
+use super::vfs::{FileSystem, NATIVE_FILE_SYSTEM};
 use super::xmlformat;
 
-const SKELETON_FILE_MAGIC: &[u8; 4] = b"CSF\0";
-const ANIMATION_FILE_MAGIC: &[u8; 4] = b"CAF\0";
-const ANIMATEDMORPH_FILE_MAGIC: &[u8; 4] = b"CPF\0";
-const MESH_FILE_MAGIC: &[u8; 4] = b"CMF\0";
-const MATERIAL_FILE_MAGIC: &[u8; 4] = b"CRF\0";
+pub(super) const SKELETON_FILE_MAGIC: &[u8; 4] = b"CSF\0";
+pub(super) const ANIMATION_FILE_MAGIC: &[u8; 4] = b"CAF\0";
+pub(super) const ANIMATEDMORPH_FILE_MAGIC: &[u8; 4] = b"CPF\0";
+pub(super) const MESH_FILE_MAGIC: &[u8; 4] = b"CMF\0";
+pub(super) const MATERIAL_FILE_MAGIC: &[u8; 4] = b"CRF\0";
 
-const SKELETON_XMLFILE_MAGIC: &[u8; 4] = b"XSF\0";
-const ANIMATION_XMLFILE_MAGIC: &[u8; 4] = b"XAF\0";
-const MESH_XMLFILE_MAGIC: &[u8; 4] = b"XMF\0";
-const MATERIAL_XMLFILE_MAGIC: &[u8; 4] = b"XRF\0";
+pub(super) const SKELETON_XMLFILE_MAGIC: &[u8; 4] = b"XSF\0";
+pub(super) const ANIMATION_XMLFILE_MAGIC: &[u8; 4] = b"XAF\0";
+pub(super) const ANIMATEDMORPH_XMLFILE_MAGIC: &[u8; 4] = b"XPF\0";
+pub(super) const MESH_XMLFILE_MAGIC: &[u8; 4] = b"XMF\0";
+pub(super) const MATERIAL_XMLFILE_MAGIC: &[u8; 4] = b"XRF\0";
 
 const CAL3D_VERSION: i32 = 1301;
-const LIBRARY_VERSION: i32 = CAL3D_VERSION;
+pub(super) const LIBRARY_VERSION: i32 = CAL3D_VERSION;
 
 // file versions
-const CURRENT_FILE_VERSION: i32 = LIBRARY_VERSION;
-const EARLIEST_COMPATIBLE_FILE_VERSION: i32 = 699;
+pub(super) const CURRENT_FILE_VERSION: i32 = FIRST_FILE_VERSION_WITH_VERTEX_SLAVES_ATTRIBUTES;
+pub(super) const EARLIEST_COMPATIBLE_FILE_VERSION: i32 = 699;
 
 const FIRST_FILE_VERSION_WITH_ANIMATION_COMPRESSION6: i32 = 1300;
 const FIRST_FILE_VERSION_WITH_ANIMATION_COMPRESSION5: i32 = 1300;
 const FIRST_FILE_VERSION_WITH_ANIMATION_COMPRESSION4: i32 = 1300;
 const FIRST_FILE_VERSION_WITH_ANIMATION_COMPRESSION: i32 = 1300;
-const FIRST_FILE_VERSION_WITH_VERTEX_COLORS: i32 = 91300; //removed from spec (one would require both mesh type vertex color and texture color
-const FIRST_FILE_VERSION_WITH_VERTEX_SLAVES_ATTRIBUTES: i32 = 1400; //to implement (more generic than vertex color) all atribute that doesnt have semantic (not in[vertex,normal,tangent,physics) in cal3d but interpole linearly (color and others stuff)
+pub(super) const FIRST_FILE_VERSION_WITH_VERTEX_COLORS: i32 = 91300; //removed from spec (one would require both mesh type vertex color and texture color
+pub(super) const FIRST_FILE_VERSION_WITH_VERTEX_SLAVES_ATTRIBUTES: i32 = 1400; //more generic than vertex color: all attributes that have no semantic in cal3d (not in [vertex,normal,tangent,physics]) but interpolate linearly (color and other stuff)
 
-const FIRST_FILE_VERSION_WITH_NODE_LIGHTS: i32 = 91300; //removed
-const FIRST_FILE_VERSION_WITH_MATERIAL_TYPES: i32 = 1300;
-const FIRST_FILE_VERSION_WITH_MORPH_TARGETS_IN_MORPH_FILES: i32 = 1300;
+pub(super) const FIRST_FILE_VERSION_WITH_NODE_LIGHTS: i32 = 91300; //removed
+pub(super) const FIRST_FILE_VERSION_WITH_MATERIAL_TYPES: i32 = 1300;
+pub(super) const FIRST_FILE_VERSION_WITH_MORPH_TARGETS_IN_MORPH_FILES: i32 = 1300;
 const FIRST_FILE_VERSION_WITH_RELATIVE_BONE_TRANSLATION: i32 = 1300;
 const FIRST_FILE_VERSION_WITH_UPDATED_MORPHMIXER: i32 = 1301;
 
-const LOADER_ROTATE_X_AXIS: i32 = 1;
-const LOADER_INVERT_V_COORD: i32 = 2;
-const LOADER_FLIP_WINDING: i32 = 4;
+pub(super) const LOADER_ROTATE_X_AXIS: i32 = 1;
+pub(super) const LOADER_INVERT_V_COORD: i32 = 2;
+pub(super) const LOADER_FLIP_WINDING: i32 = 4;
 
 pub fn versionHasCompressionFlag(version: i32) -> bool {
     return version >= 1300;
 }
 
-pub static loadingMode: i32 = 0;
-static translationTolerance: f64 = 0.25;
-static rotationToleranceDegrees: f64 = 0.1;
-static loadingCompressionOn: bool = false;
-static collapseSequencesOn: bool = false;
+/// Options changing how assets are converted while they are loaded, so an
+/// engine with different conventions does not have to post-process them.
+///
+/// The same options should be used for every asset of a model, as a mesh
+/// rotated on load relies on its skeleton having been rotated too.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoaderOptions {
+    /// Rotate skeletons, animations and meshes 90 degrees about the X axis,
+    /// turning a Z-up asset into a Y-up one.
+    pub rotate_x_axis: bool,
+    /// Replace the V texture coordinate with 1 - V, for engines with the
+    /// texture origin at the top left.
+    pub invert_v_coord: bool,
+    /// Reverse the winding order of all faces.
+    pub flip_winding: bool,
+    /// Collapse runs of keyframes holding the same pose within the tolerances
+    /// below, see `CalCoreTrack::collapseSequences`. Each track records how
+    /// many keyframes this removed in `getCollapsedKeyframeCount`.
+    pub collapse_sequences: bool,
+    /// Drop the keyframes of animation tracks that the remaining keyframes
+    /// reproduce within the tolerances below, see `CalCoreTrack::compress`.
+    pub compress_tracks: bool,
+    /// The distance a translation may be off by when tracks are collapsed or
+    /// compressed.
+    pub translation_tolerance: f64,
+    /// The angle in degrees a rotation may be off by when tracks are
+    /// collapsed or compressed.
+    pub rotation_tolerance_degrees: f64,
+    /// Fail on suspicious data in binary files, such as bytes left after the
+    /// end of the file, values that are not finite numbers, rotations that
+    /// are not normalised, influences or tracks of bones that do not exist
+    /// and negative counts. Otherwise these are recorded in `warnings` and
+    /// loading carries on as well as it can.
+    pub strict: bool,
+    /// The problems found by loading that was not strict, oldest first. Use
+    /// `warnings.take()` to collect them.
+    pub warnings: RefCell<Vec<LoaderWarning>>,
+    /// The largest sizes accepted from a file.
+    pub limits: LoadLimits,
+    /// Called as binary and XML files are read, before each bone, track or
+    /// submesh and once all of them have been read.
+    pub progress: Option<ProgressCallback>,
+    /// Aborts loading from binary and XML files with `LoaderError::Cancelled`
+    /// once cancelled, at the next bone, track or submesh, or within the
+    /// next `CANCELLATION_STRIDE` vertices or keyframes.
+    pub cancellation: CancellationToken,
+}
+
+impl Default for LoaderOptions {
+    fn default() -> Self {
+        LoaderOptions {
+            rotate_x_axis: false,
+            invert_v_coord: false,
+            flip_winding: false,
+            collapse_sequences: false,
+            compress_tracks: false,
+            translation_tolerance: 0.25,
+            rotation_tolerance_degrees: 0.1,
+            strict: false,
+            warnings: RefCell::new(Vec::new()),
+            limits: LoadLimits::default(),
+            progress: None,
+            cancellation: CancellationToken::default(),
+        }
+    }
+}
+
+/// The largest sizes a loader accepts from a file. Sizes are checked before
+/// anything is allocated for them, so that corrupt or hostile files fail to
+/// load instead of exhausting memory. Files over a limit always fail, even
+/// when loading is not strict.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadLimits {
+    /// The most vertices in a submesh.
+    pub max_vertices: usize,
+    /// The most faces in a submesh.
+    pub max_faces: usize,
+    /// The most springs in a submesh.
+    pub max_springs: usize,
+    /// The most morph targets in a submesh.
+    pub max_morph_targets: usize,
+    /// The most submeshes in a mesh.
+    pub max_submeshes: usize,
+    /// The most bones in a skeleton, which also bounds the children of a bone
+    /// and the influences of a vertex.
+    pub max_bones: usize,
+    /// The most tracks in an animation or animated morph.
+    pub max_tracks: usize,
+    /// The most keyframes in a track.
+    pub max_keyframes: usize,
+    /// The most texture coordinate sets of a submesh, which also bounds the
+    /// maps of a material.
+    pub max_texture_coordinate_sets: usize,
+    /// The most vertex slave attributes of a submesh.
+    pub max_slave_attributes: usize,
+    /// The most components of a vertex slave attribute.
+    pub max_slave_attribute_components: usize,
+    /// The most values in a submesh, counting each vertex once for itself
+    /// and once more for each texture coordinate set, slave attribute
+    /// component and morph target. This bounds what a submesh allocates when
+    /// each of its counts is within its own limit.
+    pub max_submesh_values: usize,
+    /// The longest string, in bytes including its trailing null.
+    pub max_string_length: usize,
+}
+
+impl Default for LoadLimits {
+    fn default() -> Self {
+        LoadLimits {
+            max_vertices: 1 << 20,
+            max_faces: 1 << 21,
+            max_springs: 1 << 20,
+            max_morph_targets: 1 << 12,
+            max_submeshes: 1 << 12,
+            max_bones: 1 << 12,
+            max_tracks: 1 << 12,
+            max_keyframes: 1 << 20,
+            max_texture_coordinate_sets: 32,
+            max_slave_attributes: 64,
+            max_slave_attribute_components: 64,
+            max_submesh_values: 1 << 22,
+            max_string_length: datasource::maxStringLength as usize,
+        }
+    }
+}
+
+/// The part of a file a loader is reading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadStage {
+    Bones,
+    Tracks,
+    Submeshes,
+}
+
+/// How far a loader has got through a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub stage: LoadStage,
+    /// The index of the bone, track or submesh about to be read, which is
+    /// `count` once all of them have been read.
+    pub index: usize,
+    /// The number of bones, tracks or submeshes in the file.
+    pub count: usize,
+    /// The number of bytes read so far, if the data source keeps count. In
+    /// XML files it is the offset of the element about to be read.
+    pub bytes_read: Option<u64>,
+}
+
+/// A function told of the progress of loading, such as to update a loading
+/// screen.
+#[derive(Clone)]
+pub struct ProgressCallback(Rc<dyn Fn(&LoadProgress)>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(&LoadProgress) + 'static) -> Self {
+        ProgressCallback(Rc::new(callback))
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ProgressCallback")
+    }
+}
+
+impl PartialEq for ProgressCallback {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A flag to cancel loading with, which can be cancelled from another thread
+/// than the one loading. Clones share the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the loading using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// tokens compare by state, so that options are equal whichever token they use
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.is_cancelled() == other.is_cancelled()
+    }
+}
+
+/// How many vertices or keyframes are read between checks of the
+/// cancellation token.
+pub const CANCELLATION_STRIDE: usize = 1024;
+
+/// Checks a size read from a file against its limit.
+pub(super) fn checkLimit(what: &str, count: usize, limit: usize) -> Result<(), LoaderError> {
+    if count > limit {
+        return Err(LoaderError::FormatError(format!(
+            "{what} {count} is over the limit of {limit}"
+        )));
+    }
+    Ok(())
+}
+
+/// Checks the values of a submesh against the limit, counting each vertex
+/// once and once more for each of the other values it has.
+pub(super) fn checkSubmeshValues(
+    vertexCount: usize,
+    valuesPerVertex: usize,
+    limits: &LoadLimits,
+) -> Result<(), LoaderError> {
+    let values = valuesPerVertex
+        .checked_add(1)
+        .and_then(|values| values.checked_mul(vertexCount))
+        .unwrap_or(usize::MAX);
+    checkLimit("Submesh value count", values, limits.max_submesh_values)
+}
+
+impl LoaderOptions {
+    /// Builds options from a combination of the `LOADER_*` flags used by the
+    /// original library.
+    pub fn from_flags(flags: i32) -> Self {
+        LoaderOptions {
+            rotate_x_axis: (flags & LOADER_ROTATE_X_AXIS) != 0,
+            invert_v_coord: (flags & LOADER_INVERT_V_COORD) != 0,
+            flip_winding: (flags & LOADER_FLIP_WINDING) != 0,
+            ..Default::default()
+        }
+    }
+
+    /// Rotates the transform of a root bone, or of a keyframe of a root bone.
+    pub(super) fn rotateRootTransform(
+        &self,
+        rotation: CalQuaternion<f32>,
+        translation: CalVector<f32>,
+    ) -> (CalQuaternion<f32>, CalVector<f32>) {
+        if !self.rotate_x_axis {
+            return (rotation, translation);
+        }
+        // rot *= x_axis_90;
+        // trans *= x_axis_90;
+        (
+            rotation.mul(xAxisRotation()),
+            self.rotateVector(translation),
+        )
+    }
+
+    /// Returns whether the keyframes of the track of a bone need rotating,
+    /// which is only the case for the root bone.
+    pub(super) fn rotatesKeyframes(
+        &self,
+        coreBone: &Option<Rc<RefCell<CalCoreBone>>>,
+        coreBoneId: usize,
+    ) -> Result<bool, LoaderError> {
+        if !self.rotate_x_axis {
+            return Ok(false);
+        }
+        let bone = coreBone.as_ref().ok_or(LoaderError::FormatError(format!(
+            "Invalid bone ID {coreBoneId} in animation"
+        )))?;
+        Ok(bone.borrow().getParentId() == -1)
+    }
+
+    /// Rotates a keyframe of the root bone. A translation the keyframe took
+    /// from the skeleton is left alone, as the skeleton is already rotated.
+    pub(super) fn rotateKeyframe(
+        &self,
+        keyframe: Rc<CalCoreKeyframe>,
+        translationFromSkeleton: bool,
+    ) -> Rc<CalCoreKeyframe> {
+        let (rotation, mut translation) =
+            self.rotateRootTransform(*keyframe.getRotation(), *keyframe.getTranslation());
+        if translationFromSkeleton {
+            translation = *keyframe.getTranslation();
+        }
+        Rc::new(CalCoreKeyframe::new(
+            keyframe.getTime(),
+            translation,
+            rotation,
+        ))
+    }
+
+    /// Rotates the bone space rotation of a bone, so that it undoes the
+    /// rotation applied to the vertices of the meshes of the skeleton.
+    pub(super) fn rotateBoneSpace(
+        &self,
+        rotationBoneSpace: CalQuaternion<f32>,
+    ) -> CalQuaternion<f32> {
+        if !self.rotate_x_axis {
+            return rotationBoneSpace;
+        }
+        xAxisRotation().conjugate().mul(rotationBoneSpace)
+    }
+
+    /// Rotates a vertex position or normal of a mesh, or a root translation.
+    pub(super) fn rotateVector(&self, vector: CalVector<f32>) -> CalVector<f32> {
+        if !self.rotate_x_axis {
+            return vector;
+        }
+        // vector *= x_axis_90 rotates by the conjugate, taking +Z to +Y
+        xAxisRotation().conjugate().mul(vector)
+    }
+
+    /// Converts a texture coordinate read from a file.
+    pub(super) fn textureCoordinate(&self, u: f32, v: f32) -> TextureCoordinate {
+        match self.invert_v_coord {
+            true => TextureCoordinate::from_values(u, 1.0 - v),
+            false => TextureCoordinate::from_values(u, v),
+        }
+    }
+
+    /// Reports suspicious data found while loading. Strict loading fails with
+    /// it, otherwise it is recorded as a warning at the position of the data
+    /// source.
+    pub(super) fn reportProblem(
+        &self,
+        dataSrc: &dyn DataSource,
+        message: String,
+    ) -> Result<(), LoaderError> {
+        let location = ErrorLocation {
+            offset: dataSrc.position(),
+            ..Default::default()
+        };
+        self.reportProblemAt(message, location)
+    }
+
+    /// Reports suspicious data found while loading, at a known location.
+    pub(super) fn reportProblemAt(
+        &self,
+        message: String,
+        location: ErrorLocation,
+    ) -> Result<(), LoaderError> {
+        if self.strict {
+            return Err(LoaderError::Located(
+                Box::new(LoaderError::FormatError(message)),
+                location,
+            ));
+        }
+        self.warnings
+            .borrow_mut()
+            .push(LoaderWarning { message, location });
+        Ok(())
+    }
+
+    /// Checks that values read from a file are finite numbers.
+    pub(super) fn checkFinite(
+        &self,
+        dataSrc: &dyn DataSource,
+        what: &str,
+        values: &[f32],
+    ) -> Result<(), LoaderError> {
+        let location = ErrorLocation {
+            offset: dataSrc.position(),
+            ..Default::default()
+        };
+        self.checkFiniteAt(what, values, location)
+    }
+
+    /// Checks that values read from a file are finite numbers, at a known
+    /// location.
+    pub(super) fn checkFiniteAt(
+        &self,
+        what: &str,
+        values: &[f32],
+        location: ErrorLocation,
+    ) -> Result<(), LoaderError> {
+        if values.iter().all(|value| value.is_finite()) {
+            return Ok(());
+        }
+        self.reportProblemAt(format!("{what} {values:?} is not finite"), location)
+    }
+
+    /// Checks that a rotation read from a file is a finite unit quaternion.
+    pub(super) fn checkRotation(
+        &self,
+        dataSrc: &dyn DataSource,
+        what: &str,
+        rotation: &CalQuaternion<f32>,
+    ) -> Result<(), LoaderError> {
+        let location = ErrorLocation {
+            offset: dataSrc.position(),
+            ..Default::default()
+        };
+        self.checkRotationAt(what, rotation, location)
+    }
+
+    /// Checks that a rotation read from a file is a finite unit quaternion,
+    /// at a known location.
+    pub(super) fn checkRotationAt(
+        &self,
+        what: &str,
+        rotation: &CalQuaternion<f32>,
+        location: ErrorLocation,
+    ) -> Result<(), LoaderError> {
+        let values = [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s];
+        if !values.iter().all(|value| value.is_finite()) {
+            return self.checkFiniteAt(what, &values, location);
+        }
+        let magnitude = rotation.magnitude();
+        if (magnitude - 1.0).abs() <= rotationMagnitudeTolerance {
+            return Ok(());
+        }
+        self.reportProblemAt(
+            format!("{what} {values:?} is not normalised, its magnitude is {magnitude}"),
+            location,
+        )
+    }
+
+    /// Reads a count, treating a negative one as empty unless loading
+    /// strictly, and failing if it is over a limit.
+    pub(super) fn readCount(
+        &self,
+        dataSrc: &mut dyn DataSource,
+        what: &str,
+        limit: usize,
+    ) -> Result<usize, LoaderError> {
+        let count = dataSrc.readInteger()?;
+        if count < 0 {
+            self.reportProblem(dataSrc, format!("{what} {count} is negative"))?;
+            return Ok(0);
+        }
+        checkLimit(what, count as usize, limit)?;
+        Ok(count as usize)
+    }
+
+    /// Fails if loading has been cancelled, and otherwise reports the
+    /// progress of loading to the callback, if there is one.
+    pub(super) fn reportProgress(
+        &self,
+        dataSrc: &dyn DataSource,
+        stage: LoadStage,
+        index: usize,
+        count: usize,
+    ) -> Result<(), LoaderError> {
+        self.reportProgressAt(stage, index, count, dataSrc.position())
+    }
+
+    /// Fails if loading has been cancelled, and otherwise reports the
+    /// progress of loading, with the number of bytes read if it is known, to
+    /// the callback, if there is one.
+    pub(super) fn reportProgressAt(
+        &self,
+        stage: LoadStage,
+        index: usize,
+        count: usize,
+        bytesRead: Option<u64>,
+    ) -> Result<(), LoaderError> {
+        if self.cancellation.is_cancelled() {
+            return Err(LoaderError::Cancelled);
+        }
+        if let Some(ProgressCallback(callback)) = &self.progress {
+            callback(&LoadProgress {
+                stage,
+                index,
+                count,
+                bytes_read: bytesRead,
+            });
+        }
+        Ok(())
+    }
+
+    /// Fails if loading has been cancelled, checking only once every
+    /// `CANCELLATION_STRIDE` items of a loop, such as the vertices of a
+    /// submesh.
+    pub(super) fn checkCancelled(&self, index: usize) -> Result<(), LoaderError> {
+        if index.is_multiple_of(CANCELLATION_STRIDE) && self.cancellation.is_cancelled() {
+            return Err(LoaderError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Reads a string no longer than the limit.
+    pub(super) fn readString(&self, dataSrc: &mut dyn DataSource) -> Result<String, LoaderError> {
+        Ok(dataSrc.readStringWithMaxLength(self.limits.max_string_length)?)
+    }
+
+    /// Records the file being loaded in the warnings found since there were
+    /// `warningCount` of them.
+    pub(super) fn locateWarnings(&self, warningCount: usize, file: &Path, asset: AssetKind) {
+        for warning in self.warnings.borrow_mut().iter_mut().skip(warningCount) {
+            warning.location.file = Some(file.to_path_buf());
+            warning.location.asset = Some(asset);
+        }
+    }
+
+    /// Checks that a binary file was read to its end.
+    pub(super) fn checkUnusedBytes<R: Read>(
+        &self,
+        source: &mut BufReaderSource<R>,
+    ) -> Result<(), LoaderError> {
+        let unusedByteCount = source.unused_byte_count()?;
+        if unusedByteCount == 0 {
+            return Ok(());
+        }
+        self.reportProblem(
+            source,
+            format!("{unusedByteCount} bytes left after the end of the data"),
+        )
+    }
+}
+
+/// How far the magnitude of a rotation read from a file may be from 1.
+const rotationMagnitudeTolerance: f32 = 1e-3;
+
+/// Suspicious data found by loading that was not strict.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoaderWarning {
+    pub message: String,
+    pub location: ErrorLocation,
+}
+
+impl fmt::Display for LoaderWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location == ErrorLocation::default() {
+            true => write!(f, "{}", self.message),
+            false => write!(f, "{} ({})", self.message, self.location),
+        }
+    }
+}
+
+/// A rotation of 90 degrees about the X axis.
+fn xAxisRotation() -> CalQuaternion<f32> {
+    CalQuaternion::new(0.7071067811, 0.7071067811, 0.0, 0.0)
+}
 
+/// The kind of asset a loader was reading when an error happened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    Skeleton,
+    Animation,
+    AnimatedMorph,
+    Mesh,
+    Material,
+    /// A file holding a whole model, such as a glTF file.
+    Model,
+    /// A file holding other files, such as a tar archive.
+    Archive,
+}
+
+impl fmt::Display for AssetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetKind::Skeleton => write!(f, "skeleton"),
+            AssetKind::Animation => write!(f, "animation"),
+            AssetKind::AnimatedMorph => write!(f, "animated morph"),
+            AssetKind::Mesh => write!(f, "mesh"),
+            AssetKind::Material => write!(f, "material"),
+            AssetKind::Model => write!(f, "model"),
+            AssetKind::Archive => write!(f, "archive"),
+        }
+    }
+}
+
+/// Where in a file a loader error happened. Every field is optional, as
+/// loading from a data source knows nothing of files, and the XML formats
+/// have no meaningful byte offsets for some errors.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ErrorLocation {
+    pub file: Option<PathBuf>,
+    pub asset: Option<AssetKind>,
+    pub offset: Option<u64>,
+    /// The elements being read, outermost first, such as "submesh 2".
+    pub elements: Vec<String>,
+    /// Where the file was listed, such as the line of a model description.
+    pub listing: Option<Box<ErrorLocation>>,
+}
+
+impl fmt::Display for ErrorLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        match (&self.asset, &self.file) {
+            (Some(asset), Some(file)) => parts.push(format!("{asset} {}", file.display())),
+            (Some(asset), None) => parts.push(asset.to_string()),
+            (None, Some(file)) => parts.push(file.display().to_string()),
+            (None, None) => {}
+        }
+        parts.extend(self.elements.iter().cloned());
+        if let Some(offset) = self.offset {
+            parts.push(format!("byte {offset}"));
+        }
+        if let Some(listing) = &self.listing {
+            parts.push(format!("listed in {listing}"));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[derive(Debug)]
 pub enum LoaderError {
     IoError(std::io::Error),
     MagicError,
     VersionError,
     FormatError(String),
+    /// Loading was cancelled with the cancellation token of the options.
+    Cancelled,
+    /// Another loader error, with where in the file it happened.
+    Located(Box<LoaderError>, ErrorLocation),
+}
+
+impl LoaderError {
+    fn with_location(self, update: impl FnOnce(&mut ErrorLocation)) -> Self {
+        let (error, mut location) = match self {
+            LoaderError::Located(error, location) => (error, location),
+            error => (Box::new(error), ErrorLocation::default()),
+        };
+        update(&mut location);
+        LoaderError::Located(error, location)
+    }
+
+    /// Records the file and the kind of asset being loaded.
+    pub fn in_file(self, file: &Path, asset: AssetKind) -> Self {
+        self.with_location(|location| {
+            location.file = Some(file.to_path_buf());
+            location.asset = Some(asset);
+        })
+    }
+
+    /// Records the byte offset the error was found at, unless a more precise
+    /// one has already been recorded.
+    pub fn at_offset(self, offset: u64) -> Self {
+        self.with_location(|location| {
+            location.offset.get_or_insert(offset);
+        })
+    }
+
+    /// Records an element that was being read, such as "vertex 451". Callers
+    /// add elements from the innermost outwards.
+    pub fn in_element(self, element: impl Into<String>) -> Self {
+        self.with_location(|location| location.elements.insert(0, element.into()))
+    }
+
+    /// Records where the file the error happened in was listed, such as the
+    /// line of a model description.
+    pub(super) fn in_listing(self, update: impl FnOnce(&mut ErrorLocation)) -> Self {
+        self.with_location(|location| update(location.listing.get_or_insert_default()))
+    }
+
+    /// Returns the error without any location.
+    pub fn kind(&self) -> &LoaderError {
+        match self {
+            LoaderError::Located(error, _) => error.kind(),
+            error => error,
+        }
+    }
+
+    /// Returns where the error happened, if it is known.
+    pub fn location(&self) -> Option<&ErrorLocation> {
+        match self {
+            LoaderError::Located(_, location) => Some(location),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoaderError::IoError(e) => write!(f, "I/O error: {e}"),
+            LoaderError::MagicError => write!(f, "incorrect magic number"),
+            LoaderError::VersionError => write!(f, "incompatible file version"),
+            LoaderError::FormatError(e) => write!(f, "{e}"),
+            LoaderError::Cancelled => write!(f, "loading was cancelled"),
+            LoaderError::Located(error, location) => write!(f, "{error} ({location})"),
+        }
+    }
+}
+
+impl std::error::Error for LoaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.kind() {
+            LoaderError::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl From<std::io::Error> for LoaderError {
@@ -106,6 +784,7 @@ impl From<CoreError> for LoaderError {
  * This function loads a core animation instance from a file.
  *
  * @param strFilename The file to load the core animation instance from.
+ * @param options The conversions to apply while loading.
  *
  * @return One of the following values:
  *         \li a pointer to the core animation
@@ -113,28 +792,116 @@ impl From<CoreError> for LoaderError {
  *****************************************************************************/
 
 pub fn loadCoreAnimation(
-    filename: &PathBuf,
+    filename: &Path,
     skel: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimation>>, LoaderError> {
+    loadCoreAnimationFromFileSystem(&NATIVE_FILE_SYSTEM, filename, skel, options)
+}
+
+/*****************************************************************************/
+/** Loads a core animation instance.
+ *
+ * This function loads a core animation instance from a file of a file system,
+ * such as a directory or an archive.
+ *
+ * @param fileSystem The file system to find the file in.
+ * @param filename The file to load the core animation instance from.
+ * @param skel The skeleton the animation is for.
+ * @param options The conversions to apply while loading.
+ *****************************************************************************/
+pub fn loadCoreAnimationFromFileSystem(
+    fileSystem: &dyn FileSystem,
+    filename: &Path,
+    skel: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
 ) -> Result<Rc<RefCell<CalCoreAnimation>>, LoaderError> {
     let magic: String = String::from_utf8_lossy(ANIMATION_XMLFILE_MAGIC)
         .trim_matches(char::from(0))
         .to_lowercase()
         .to_owned();
     if filename.to_str().unwrap().ends_with(magic.as_str()) {
-        todo!();
+        let text = readXmlFile(fileSystem, filename, AssetKind::Animation)?;
+        return xmlformat::loadXmlCoreAnimationFromText(&text, skel, options)
+            .map_err(|e| e.in_file(filename, AssetKind::Animation));
     }
 
-    let buff_reader = BufReader::new(fs::File::open(filename)?);
+    let mut source = BufReaderSource::new(openFile(fileSystem, filename, AssetKind::Animation)?);
 
-    let mut source = BufReaderSource::new(buff_reader);
-
-    let coreanim = loadCoreAnimationFromSource(&mut source, skel)?;
+    let warningCount = options.warnings.borrow().len();
+    let coreanim = loadCoreAnimationFromSource(&mut source, skel, options)
+        .map_err(|e| e.in_file(filename, AssetKind::Animation))?;
 
-    source.report_unused_bytes(filename);
+    options
+        .checkUnusedBytes(&mut source)
+        .map_err(|e| e.in_file(filename, AssetKind::Animation))?;
+    options.locateWarnings(warningCount, filename, AssetKind::Animation);
 
     Ok(coreanim)
 }
 
+/*****************************************************************************/
+/** Loads a core animated morph instance.
+ *
+ * This function loads a core animated morph instance from a file.
+ *
+ * @param strFilename The file to load the core animated morph instance from.
+ * @param options The checks to apply while loading.
+ *
+ * @return One of the following values:
+ *         \li a pointer to the core animated morph
+ *         \li \b 0 if an error happened
+ *****************************************************************************/
+pub fn loadCoreAnimatedMorph(
+    filename: &Path,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimatedMorph>>, LoaderError> {
+    loadCoreAnimatedMorphFromFileSystem(&NATIVE_FILE_SYSTEM, filename, options)
+}
+
+/*****************************************************************************/
+/** Loads a core animated morph instance.
+ *
+ * This function loads a core animated morph instance from a file of a file
+ * system, such as a directory or an archive.
+ *
+ * @param fileSystem The file system to find the file in.
+ * @param filename The file to load the core animated morph instance from.
+ * @param options The checks to apply while loading.
+ *****************************************************************************/
+pub fn loadCoreAnimatedMorphFromFileSystem(
+    fileSystem: &dyn FileSystem,
+    filename: &Path,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimatedMorph>>, LoaderError> {
+    let magic: String = String::from_utf8_lossy(ANIMATEDMORPH_XMLFILE_MAGIC)
+        .trim_matches(char::from(0))
+        .to_lowercase()
+        .to_owned();
+    if filename.to_str().unwrap().ends_with(magic.as_str()) {
+        let text = readXmlFile(fileSystem, filename, AssetKind::AnimatedMorph)?;
+        let warningCount = options.warnings.borrow().len();
+        let coreanimatedmorph = xmlformat::loadXmlCoreAnimatedMorphFromText(&text, options)
+            .map_err(|e| e.in_file(filename, AssetKind::AnimatedMorph))?;
+        options.locateWarnings(warningCount, filename, AssetKind::AnimatedMorph);
+        return Ok(coreanimatedmorph);
+    }
+
+    let mut source =
+        BufReaderSource::new(openFile(fileSystem, filename, AssetKind::AnimatedMorph)?);
+
+    let warningCount = options.warnings.borrow().len();
+    let coreanimatedmorph = loadCoreAnimatedMorphFromSource(&mut source, options)
+        .map_err(|e| e.in_file(filename, AssetKind::AnimatedMorph))?;
+
+    options
+        .checkUnusedBytes(&mut source)
+        .map_err(|e| e.in_file(filename, AssetKind::AnimatedMorph))?;
+    options.locateWarnings(warningCount, filename, AssetKind::AnimatedMorph);
+
+    Ok(coreanimatedmorph)
+}
+
 //174
 /*****************************************************************************/
 /** Loads a core material instance.
@@ -142,28 +909,58 @@ pub fn loadCoreAnimation(
  * This function loads a core material instance from a file.
  *
  * @param strFilename The file to load the core material instance from.
+ * @param options The checks to apply while loading.
  *
  * @return One of the following values:
  *         \li a pointer to the core material
  *         \li \b 0 if an error happened
  *****************************************************************************/
 
-pub fn loadCoreMaterial(filename: &PathBuf) -> Result<CalCoreMaterial, LoaderError> {
+pub fn loadCoreMaterial(
+    filename: &Path,
+    options: &LoaderOptions,
+) -> Result<CalCoreMaterial, LoaderError> {
+    loadCoreMaterialFromFileSystem(&NATIVE_FILE_SYSTEM, filename, options)
+}
+
+/*****************************************************************************/
+/** Loads a core material instance.
+ *
+ * This function loads a core material instance from a file of a file system,
+ * such as a directory or an archive.
+ *
+ * @param fileSystem The file system to find the file in.
+ * @param filename The file to load the core material instance from.
+ * @param options The checks to apply while loading.
+ *****************************************************************************/
+pub fn loadCoreMaterialFromFileSystem(
+    fileSystem: &dyn FileSystem,
+    filename: &Path,
+    options: &LoaderOptions,
+) -> Result<CalCoreMaterial, LoaderError> {
     let magic: String = String::from_utf8_lossy(MATERIAL_XMLFILE_MAGIC)
         .trim_matches(char::from(0))
         .to_lowercase()
         .to_owned();
     if filename.to_str().unwrap().ends_with(magic.as_str()) {
-        return xmlformat::loadXmlCoreMaterial(filename);
+        let text = readXmlFile(fileSystem, filename, AssetKind::Material)?;
+        let warningCount = options.warnings.borrow().len();
+        let coremat = xmlformat::loadXmlCoreMaterialFromText(&text, options)
+            .map_err(|e| e.in_file(filename, AssetKind::Material))?;
+        options.locateWarnings(warningCount, filename, AssetKind::Material);
+        return Ok(coremat);
     }
 
-    let buff_reader = BufReader::new(fs::File::open(filename)?);
+    let mut source = BufReaderSource::new(openFile(fileSystem, filename, AssetKind::Material)?);
 
-    let mut source = BufReaderSource::new(buff_reader);
+    let warningCount = options.warnings.borrow().len();
+    let coremat = loadCoreMaterialFromSource(&mut source, options)
+        .map_err(|e| e.in_file(filename, AssetKind::Material))?;
 
-    let coremat = loadCoreMaterialFromSource(&mut source)?;
-
-    source.report_unused_bytes(filename);
+    options
+        .checkUnusedBytes(&mut source)
+        .map_err(|e| e.in_file(filename, AssetKind::Material))?;
+    options.locateWarnings(warningCount, filename, AssetKind::Material);
 
     // coremat.setFilename(filename);
 
@@ -176,28 +973,55 @@ pub fn loadCoreMaterial(filename: &PathBuf) -> Result<CalCoreMaterial, LoaderErr
  * This function loads a core mesh instance from a file.
  *
  * @param strFilename The file to load the core mesh instance from.
+ * @param options The conversions to apply while loading.
  *
  * @return One of the following values:
  *         \li a pointer to the core mesh
  *         \li \b 0 if an error happened
  *****************************************************************************/
-pub fn loadCoreMesh(filename: &PathBuf) -> Result<Rc<RefCell<CalCoreMesh>>, LoaderError> {
+pub fn loadCoreMesh(
+    filename: &Path,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreMesh>>, LoaderError> {
+    loadCoreMeshFromFileSystem(&NATIVE_FILE_SYSTEM, filename, options)
+}
+
+/*****************************************************************************/
+/** Loads a core mesh instance.
+ *
+ * This function loads a core mesh instance from a file of a file system,
+ * such as a directory or an archive.
+ *
+ * @param fileSystem The file system to find the file in.
+ * @param filename The file to load the core mesh instance from.
+ * @param options The conversions to apply while loading.
+ *****************************************************************************/
+pub fn loadCoreMeshFromFileSystem(
+    fileSystem: &dyn FileSystem,
+    filename: &Path,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreMesh>>, LoaderError> {
     let magic: String = String::from_utf8_lossy(MESH_XMLFILE_MAGIC)
         .trim_matches(char::from(0))
         .to_lowercase()
         .to_owned();
     if filename.to_str().unwrap().ends_with(magic.as_str()) {
-        todo!();
-        // loadXmlCoreMesh(strFilename);
+        let text = readXmlFile(fileSystem, filename, AssetKind::Mesh)?;
+        let coremesh = xmlformat::loadXmlCoreMeshFromText(&text, options)
+            .map_err(|e| e.in_file(filename, AssetKind::Mesh))?;
+        return Ok(Rc::new(RefCell::new(coremesh)));
     }
 
-    let buff_reader = BufReader::new(fs::File::open(filename)?);
+    let mut source = BufReaderSource::new(openFile(fileSystem, filename, AssetKind::Mesh)?);
 
-    let mut source = BufReaderSource::new(buff_reader);
+    let warningCount = options.warnings.borrow().len();
+    let coremesh = loadCoreMeshFromSource(&mut source, options)
+        .map_err(|e| e.in_file(filename, AssetKind::Mesh))?;
 
-    let coremesh = loadCoreMeshFromSource(&mut source)?;
-
-    source.report_unused_bytes(filename);
+    options
+        .checkUnusedBytes(&mut source)
+        .map_err(|e| e.in_file(filename, AssetKind::Mesh))?;
+    options.locateWarnings(warningCount, filename, AssetKind::Mesh);
 
     //if(coremesh) coremesh->setFilename( strFilename );
 
@@ -210,32 +1034,244 @@ pub fn loadCoreMesh(filename: &PathBuf) -> Result<Rc<RefCell<CalCoreMesh>>, Load
  * This function loads a core skeleton instance from a file.
  *
  * @param strFilename The file to load the core skeleton instance from.
+ * @param options The conversions to apply while loading.
  *
  * @return One of the following values:
  *         \li a pointer to the core skeleton
  *         \li \b 0 if an error happened
  *****************************************************************************/
 pub fn loadCoreSkeleton(
-    filename: &PathBuf,
+    filename: &Path,
+    skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
+) -> Result<(), LoaderError> {
+    loadCoreSkeletonFromFileSystem(&NATIVE_FILE_SYSTEM, filename, skeleton, options)
+}
+
+/*****************************************************************************/
+/** Loads a core skeleton instance.
+ *
+ * This function loads a core skeleton instance from a file of a file system,
+ * such as a directory or an archive.
+ *
+ * @param fileSystem The file system to find the file in.
+ * @param filename The file to load the core skeleton instance from.
+ * @param skeleton The skeleton to add the loaded bones to.
+ * @param options The conversions to apply while loading.
+ *****************************************************************************/
+pub fn loadCoreSkeletonFromFileSystem(
+    fileSystem: &dyn FileSystem,
+    filename: &Path,
     skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
 ) -> Result<(), LoaderError> {
     let magic: String = String::from_utf8_lossy(SKELETON_XMLFILE_MAGIC)
         .trim_matches(char::from(0))
         .to_lowercase()
         .to_owned();
     if filename.to_str().unwrap().ends_with(magic.as_str()) {
-        todo!();
+        let text = readXmlFile(fileSystem, filename, AssetKind::Skeleton)?;
+        return xmlformat::loadXmlCoreSkeletonFromText(&text, skeleton, options)
+            .map_err(|e| e.in_file(filename, AssetKind::Skeleton));
     }
 
-    let buff_reader = BufReader::new(fs::File::open(filename)?);
+    let mut source = BufReaderSource::new(openFile(fileSystem, filename, AssetKind::Skeleton)?);
+
+    let warningCount = options.warnings.borrow().len();
+    loadCoreSkeletonFromSource(&mut source, skeleton, options)
+        .map_err(|e| e.in_file(filename, AssetKind::Skeleton))?;
+
+    options
+        .checkUnusedBytes(&mut source)
+        .map_err(|e| e.in_file(filename, AssetKind::Skeleton))?;
+    options.locateWarnings(warningCount, filename, AssetKind::Skeleton);
+
+    Ok(())
+}
+
+/// Checks whether a reader holds a Cal3D XML file rather than a binary one,
+/// without consuming anything. Binary files always start with their magic
+/// tag, while XML files start with markup, whitespace or a byte order mark.
+fn readerHoldsXml<R: Read>(reader: &mut BufReader<R>) -> Result<bool, LoaderError> {
+    let buffer = reader.fill_buf()?;
+    Ok(matches!(
+        buffer.first(),
+        Some(b'<' | b' ' | b'\t' | b'\r' | b'\n' | 0xef)
+    ))
+}
+
+fn readXmlText<R: Read>(mut reader: BufReader<R>) -> Result<String, LoaderError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    Ok(text.trim_start_matches('\u{feff}').to_string())
+}
+
+/// Opens a file of a file system for reading, recording the file in the
+/// error if it cannot be.
+fn openFile<'a>(
+    fileSystem: &'a dyn FileSystem,
+    filename: &Path,
+    asset: AssetKind,
+) -> Result<BufReader<Box<dyn Read + 'a>>, LoaderError> {
+    let reader = fileSystem
+        .open(filename)
+        .map_err(|e| LoaderError::from(e).in_file(filename, asset))?;
+    Ok(BufReader::new(reader))
+}
+
+/// Reads the whole of an XML file of a file system.
+fn readXmlFile(
+    fileSystem: &dyn FileSystem,
+    filename: &Path,
+    asset: AssetKind,
+) -> Result<String, LoaderError> {
+    readXmlText(openFile(fileSystem, filename, asset)?).map_err(|e| e.in_file(filename, asset))
+}
+
+/*****************************************************************************/
+/** Loads a core animation instance.
+ *
+ * This function loads a core animation instance from a reader, such as an
+ * open file, an archive entry or a byte slice. Both the binary and the XML
+ * format are accepted.
+ *
+ * @param reader The reader to load the core animation instance from.
+ * @param skel The skeleton the animation is for.
+ * @param options The conversions to apply while loading.
+ *****************************************************************************/
+pub fn loadCoreAnimationFromReader<R: Read>(
+    reader: R,
+    skel: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimation>>, LoaderError> {
+    let mut buff_reader = BufReader::new(reader);
+    if readerHoldsXml(&mut buff_reader)? {
+        return xmlformat::loadXmlCoreAnimationFromText(&readXmlText(buff_reader)?, skel, options);
+    }
 
     let mut source = BufReaderSource::new(buff_reader);
+    let coreanim = loadCoreAnimationFromSource(&mut source, skel, options)?;
+    options.checkUnusedBytes(&mut source)?;
 
-    loadCoreSkeletonFromSource(&mut source, skeleton)?;
+    Ok(coreanim)
+}
 
-    source.report_unused_bytes(filename);
+/*****************************************************************************/
+/** Loads a core animated morph instance.
+ *
+ * This function loads a core animated morph instance from a reader, such as
+ * an open file, an archive entry or a byte slice. Both the binary and the XML
+ * format are accepted.
+ *
+ * @param reader The reader to load the core animated morph instance from.
+ * @param options The checks to apply while loading.
+ *****************************************************************************/
+pub fn loadCoreAnimatedMorphFromReader<R: Read>(
+    reader: R,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimatedMorph>>, LoaderError> {
+    let mut buff_reader = BufReader::new(reader);
+    if readerHoldsXml(&mut buff_reader)? {
+        return xmlformat::loadXmlCoreAnimatedMorphFromText(&readXmlText(buff_reader)?, options);
+    }
 
-    Ok(())
+    let mut source = BufReaderSource::new(buff_reader);
+    let coreanimatedmorph = loadCoreAnimatedMorphFromSource(&mut source, options)?;
+    options.checkUnusedBytes(&mut source)?;
+
+    Ok(coreanimatedmorph)
+}
+
+/*****************************************************************************/
+/** Loads a core material instance.
+ *
+ * This function loads a core material instance from a reader, such as an
+ * open file, an archive entry or a byte slice. Both the binary and the XML
+ * format are accepted.
+ *
+ * @param reader The reader to load the core material instance from.
+ * @param options The checks to apply while loading.
+ *****************************************************************************/
+pub fn loadCoreMaterialFromReader<R: Read>(
+    reader: R,
+    options: &LoaderOptions,
+) -> Result<CalCoreMaterial, LoaderError> {
+    let mut buff_reader = BufReader::new(reader);
+    if readerHoldsXml(&mut buff_reader)? {
+        return xmlformat::loadXmlCoreMaterialFromText(&readXmlText(buff_reader)?, options);
+    }
+
+    let mut source = BufReaderSource::new(buff_reader);
+    let coremat = loadCoreMaterialFromSource(&mut source, options)?;
+    options.checkUnusedBytes(&mut source)?;
+
+    Ok(coremat)
+}
+
+/*****************************************************************************/
+/** Loads a core mesh instance.
+ *
+ * This function loads a core mesh instance from a reader, such as an open
+ * file, an archive entry or a byte slice. Both the binary and the XML format
+ * are accepted.
+ *
+ * @param reader The reader to load the core mesh instance from.
+ * @param options The conversions to apply while loading.
+ *****************************************************************************/
+pub fn loadCoreMeshFromReader<R: Read>(
+    reader: R,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreMesh>>, LoaderError> {
+    let mut buff_reader = BufReader::new(reader);
+    let coremesh = if readerHoldsXml(&mut buff_reader)? {
+        xmlformat::loadXmlCoreMeshFromText(&readXmlText(buff_reader)?, options)?
+    } else {
+        let mut source = BufReaderSource::new(buff_reader);
+        let coremesh = loadCoreMeshFromSource(&mut source, options)?;
+        options.checkUnusedBytes(&mut source)?;
+        coremesh
+    };
+
+    Ok(Rc::new(RefCell::new(coremesh)))
+}
+
+/*****************************************************************************/
+/** Loads a core skeleton instance.
+ *
+ * This function loads a core skeleton instance from a reader, such as an
+ * open file, an archive entry or a byte slice. Both the binary and the XML
+ * format are accepted.
+ *
+ * @param reader The reader to load the core skeleton instance from.
+ * @param skeleton The skeleton to add the loaded bones to.
+ * @param options The conversions to apply while loading.
+ *****************************************************************************/
+pub fn loadCoreSkeletonFromReader<R: Read>(
+    reader: R,
+    skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
+) -> Result<(), LoaderError> {
+    let mut buff_reader = BufReader::new(reader);
+    if readerHoldsXml(&mut buff_reader)? {
+        return xmlformat::loadXmlCoreSkeletonFromText(
+            &readXmlText(buff_reader)?,
+            skeleton,
+            options,
+        );
+    }
+
+    let mut source = BufReaderSource::new(buff_reader);
+    loadCoreSkeletonFromSource(&mut source, skeleton, options)?;
+    options.checkUnusedBytes(&mut source)
+}
+
+/// Records the position of a data source in an error that happened while
+/// reading from it.
+fn locateSourceError(dataSrc: &dyn DataSource, error: LoaderError) -> LoaderError {
+    match dataSrc.position() {
+        Some(offset) => error.at_offset(offset),
+        None => error,
+    }
 }
 
 //552
@@ -245,6 +1281,7 @@ pub fn loadCoreSkeleton(
  * This function loads a core animation instance from a data source.
  *
  * @param dataSrc The data source to load the core animation instance from.
+ * @param options The conversions to apply while loading.
  *
  * @return One of the following values:
  *         \li a pointer to the core animation
@@ -254,6 +1291,17 @@ pub fn loadCoreSkeleton(
 pub fn loadCoreAnimationFromSource(
     dataSrc: &mut dyn DataSource,
     skel: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimation>>, LoaderError> {
+    let result = loadCoreAnimationData(dataSrc, skel, options);
+    result.map_err(|e| locateSourceError(dataSrc, e))
+}
+
+/// Loads a core animation instance, without the position of any error.
+fn loadCoreAnimationData(
+    dataSrc: &mut dyn DataSource,
+    skel: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
 ) -> Result<Rc<RefCell<CalCoreAnimation>>, LoaderError> {
     let mut magic: [u8; 4] = [0; 4];
     let magic_len = magic.len();
@@ -280,6 +1328,7 @@ pub fn loadCoreAnimationFromSource(
 
     // get the duration of the core animation
     let duration = dataSrc.readFloat()?;
+    options.checkFinite(dataSrc, "Animation duration", &[duration])?;
 
     // check for a valid duration
     if duration <= 0.0 {
@@ -299,6 +1348,11 @@ pub fn loadCoreAnimationFromSource(
             "Animation track count {trackCount} is negative"
         )));
     }
+    checkLimit(
+        "Animation track count",
+        trackCount as usize,
+        options.limits.max_tracks,
+    )?;
 
     // read flags
     let mut _flags = 0;
@@ -309,19 +1363,154 @@ pub fn loadCoreAnimationFromSource(
     // load all core bones
     let mut animations: Vec<Rc<RefCell<CalCoreTrack>>> = Vec::new();
 
+    let trackCount = trackCount as usize;
     for trackId in 0..trackCount {
+        options.reportProgress(dataSrc, LoadStage::Tracks, trackId, trackCount)?;
+
         // load the core track
-        let pCoreTrack = loadCoreTrack(dataSrc, skel, version, useAnimationCompression)?;
+        let pCoreTrack = loadCoreTrack(dataSrc, skel, version, useAnimationCompression, options)
+            .map_err(|e| e.in_element(format!("track {trackId}")))?;
 
         // add the core track to the core animation instance
         animations.push(pCoreTrack);
     }
+    options.reportProgress(dataSrc, LoadStage::Tracks, trackCount, trackCount)?;
 
     Ok(Rc::new(RefCell::new(CalCoreAnimation::new(
         duration, animations,
     ))))
 }
 
+//634
+/*****************************************************************************/
+/** Loads a core animated morph instance.
+ *
+ * This function loads a core animated morph instance from a data source.
+ *
+ * @param dataSrc The data source to load the core animated morph instance from.
+ * @param options The checks to apply while loading.
+ *
+ * @return One of the following values:
+ *         \li a pointer to the core animated morph
+ *         \li \b 0 if an error happened
+ *****************************************************************************/
+pub fn loadCoreAnimatedMorphFromSource(
+    dataSrc: &mut dyn DataSource,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimatedMorph>>, LoaderError> {
+    let result = loadCoreAnimatedMorphData(dataSrc, options);
+    result.map_err(|e| locateSourceError(dataSrc, e))
+}
+
+/// Loads a core animated morph instance, without the position of any error.
+fn loadCoreAnimatedMorphData(
+    dataSrc: &mut dyn DataSource,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimatedMorph>>, LoaderError> {
+    let mut magic: [u8; 4] = [0; 4];
+    let magic_len = magic.len();
+    dataSrc.readBytes(&mut magic, magic_len)?;
+    if magic != *ANIMATEDMORPH_FILE_MAGIC {
+        return Err(LoaderError::MagicError);
+    }
+
+    let version = dataSrc.readInteger()?;
+    if !(EARLIEST_COMPATIBLE_FILE_VERSION..=CURRENT_FILE_VERSION).contains(&version) {
+        return Err(LoaderError::VersionError);
+    }
+
+    // get the duration of the core animated morph
+    let duration = dataSrc.readFloat()?;
+    options.checkFinite(dataSrc, "Animated morph duration", &[duration])?;
+
+    // check for a valid duration
+    if duration <= 0.0 {
+        return Err(LoaderError::FormatError(format!(
+            "Animated morph duration {duration} is negative"
+        )));
+    }
+
+    // read the number of tracks
+    let trackCount = dataSrc.readInteger()?;
+    if trackCount <= 0 {
+        return Err(LoaderError::FormatError(format!(
+            "Animated morph track count {trackCount} is negative"
+        )));
+    }
+    checkLimit(
+        "Animated morph track count",
+        trackCount as usize,
+        options.limits.max_tracks,
+    )?;
+
+    // load all core tracks
+    let mut tracks: Vec<CalCoreMorphTrack> = Vec::new();
+
+    let trackCount = trackCount as usize;
+    for trackId in 0..trackCount {
+        options.reportProgress(dataSrc, LoadStage::Tracks, trackId, trackCount)?;
+
+        let coreTrack = loadCoreMorphTrack(dataSrc, options)
+            .map_err(|e| e.in_element(format!("track {trackId}")))?;
+
+        tracks.push(coreTrack);
+    }
+    options.reportProgress(dataSrc, LoadStage::Tracks, trackCount, trackCount)?;
+
+    Ok(Rc::new(RefCell::new(CalCoreAnimatedMorph::new(
+        duration, tracks,
+    ))))
+}
+
+//1596
+/*****************************************************************************/
+/** Loads a core morph track instance.
+ *
+ * This function loads a core morph track instance from a data source.
+ *
+ * @param dataSrc The data source to load the core morph track instance from.
+ *
+ * @return One of the following values:
+ *         \li a pointer to the core morph track
+ *         \li \b 0 if an error happened
+ *****************************************************************************/
+fn loadCoreMorphTrack(
+    dataSrc: &mut dyn DataSource,
+    options: &LoaderOptions,
+) -> Result<CalCoreMorphTrack, LoaderError> {
+    // read the morph name
+    let morphName = options.readString(dataSrc)?;
+
+    // read the number of keyframes
+    let keyframeCount = dataSrc.readInteger()?;
+    if keyframeCount <= 0 {
+        return Err(LoaderError::FormatError(format!(
+            "Morph track {morphName} keyframe count {keyframeCount} is negative"
+        )));
+    }
+    checkLimit(
+        "Morph track keyframe count",
+        keyframeCount as usize,
+        options.limits.max_keyframes,
+    )?;
+
+    // load all core keyframes
+    let mut keyframes = Vec::new();
+    for keyframeId in 0..keyframeCount {
+        options.checkCancelled(keyframeId as usize)?;
+        (|| -> Result<(), LoaderError> {
+            let time = dataSrc.readFloat()?;
+            let weight = dataSrc.readFloat()?;
+            options.checkFinite(dataSrc, "Morph keyframe time and weight", &[time, weight])?;
+            keyframes.push(CalCoreMorphKeyframe::new(time, weight));
+            Ok(())
+        })()
+        .map_err(|e| e.in_element(format!("keyframe {keyframeId}")))?;
+    }
+
+    Ok(CalCoreMorphTrack::new(morphName, keyframes))
+}
+
 //763
 /*****************************************************************************/
 /** Loads a core material instance.
@@ -329,13 +1518,24 @@ pub fn loadCoreAnimationFromSource(
  * This function loads a core material instance from a data source.
  *
  * @param dataSrc The data source to load the core material instance from.
+ * @param options The checks to apply while loading.
  *
  * @return One of the following values:
  *         \li a pointer to the core material
  *         \li \b 0 if an error happened
  *****************************************************************************/
-fn loadCoreMaterialFromSource(
+pub fn loadCoreMaterialFromSource(
     dataSrc: &mut dyn DataSource,
+    options: &LoaderOptions,
+) -> Result<CalCoreMaterial, LoaderError> {
+    let result = loadCoreMaterialData(dataSrc, options);
+    result.map_err(|e| locateSourceError(dataSrc, e))
+}
+
+/// Loads a core material instance, without the position of any error.
+fn loadCoreMaterialData(
+    dataSrc: &mut dyn DataSource,
+    options: &LoaderOptions,
 ) -> Result<CalCoreMaterial, LoaderError> {
     use super::material::{Color, Map};
 
@@ -343,7 +1543,6 @@ fn loadCoreMaterialFromSource(
     let magic_len = magic.len();
     dataSrc.readBytes(&mut magic, magic_len)?;
     if &magic != MATERIAL_FILE_MAGIC.as_slice() {
-        println!("{magic:?} {MATERIAL_FILE_MAGIC:?}");
         return Err(LoaderError::MagicError);
     }
 
@@ -374,6 +1573,7 @@ fn loadCoreMaterialFromSource(
 
     // get the shininess factor of the core material
     let shininess = dataSrc.readFloat()?;
+    options.checkFinite(dataSrc, "Material shininess", &[shininess])?;
 
     // read the number of maps
     let mapCount = dataSrc.readInteger()?;
@@ -383,15 +1583,23 @@ fn loadCoreMaterialFromSource(
         )));
     }
 
+    checkLimit(
+        "Map count",
+        mapCount as usize,
+        options.limits.max_texture_coordinate_sets,
+    )?;
+
     let mut maps = Vec::with_capacity(mapCount as usize);
 
     // load all maps
     for mapId in 0..mapCount {
+        let inMap = |e: LoaderError| e.in_element(format!("map {mapId}"));
+
         // read the filename of the map
-        let strName = dataSrc.readString()?;
+        let strName = options.readString(dataSrc).map_err(inMap)?;
 
         let mapType = match hasMaterialTypes {
-            true => dataSrc.readString()?,
+            true => options.readString(dataSrc).map_err(inMap)?,
             false => String::from(""),
         };
 
@@ -413,12 +1621,25 @@ fn loadCoreMaterialFromSource(
  * This function loads a core mesh instance from a data source.
  *
  * @param dataSrc The data source to load the core mesh instance from.
+ * @param options The conversions to apply while loading.
  *
  * @return One of the following values:
  *         \li a pointer to the core mesh
  *         \li \b 0 if an error happened
  *****************************************************************************/
-fn loadCoreMeshFromSource(dataSrc: &mut dyn DataSource) -> Result<CalCoreMesh, LoaderError> {
+pub fn loadCoreMeshFromSource(
+    dataSrc: &mut dyn DataSource,
+    options: &LoaderOptions,
+) -> Result<CalCoreMesh, LoaderError> {
+    let result = loadCoreMeshData(dataSrc, options);
+    result.map_err(|e| locateSourceError(dataSrc, e))
+}
+
+/// Loads a core mesh instance, without the position of any error.
+fn loadCoreMeshData(
+    dataSrc: &mut dyn DataSource,
+    options: &LoaderOptions,
+) -> Result<CalCoreMesh, LoaderError> {
     let mut magic: [u8; 4] = [0; 4];
     let magic_len = magic.len();
     dataSrc.readBytes(&mut magic, magic_len)?;
@@ -431,15 +1652,19 @@ fn loadCoreMeshFromSource(dataSrc: &mut dyn DataSource) -> Result<CalCoreMesh, L
         return Err(LoaderError::VersionError);
     }
 
-    let subMeshCount = dataSrc.readInteger()?;
+    let subMeshCount = options.readCount(dataSrc, "Submesh count", options.limits.max_submeshes)?;
 
     let mut subMeshes = Vec::new();
 
     for i in 0..subMeshCount {
-        let pCoreSubmesh = loadCoreSubmesh(dataSrc, version)?;
+        options.reportProgress(dataSrc, LoadStage::Submeshes, i, subMeshCount)?;
+
+        let pCoreSubmesh = loadCoreSubmesh(dataSrc, version, options)
+            .map_err(|e| e.in_element(format!("submesh {i}")))?;
 
         subMeshes.push(pCoreSubmesh);
     }
+    options.reportProgress(dataSrc, LoadStage::Submeshes, subMeshCount, subMeshCount)?;
 
     let pCoreMesh = CalCoreMesh::new(subMeshes);
 
@@ -453,14 +1678,26 @@ fn loadCoreMeshFromSource(dataSrc: &mut dyn DataSource) -> Result<CalCoreMesh, L
  * This function loads a core skeleton instance from a data source.
  *
  * @param dataSrc The data source to load the core skeleton instance from.
+ * @param options The conversions to apply while loading.
  *
  * @return One of the following values:
  *         \li a pointer to the core skeleton
  *         \li \b 0 if an error happened
  *****************************************************************************/
-fn loadCoreSkeletonFromSource(
+pub fn loadCoreSkeletonFromSource(
     dataSrc: &mut dyn DataSource,
     skel: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
+) -> Result<(), LoaderError> {
+    let result = loadCoreSkeletonData(dataSrc, skel, options);
+    result.map_err(|e| locateSourceError(dataSrc, e))
+}
+
+/// Loads a core skeleton instance, without the position of any error.
+fn loadCoreSkeletonData(
+    dataSrc: &mut dyn DataSource,
+    skel: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
 ) -> Result<(), LoaderError> {
     let mut magic: [u8; 4] = [0; 4];
     let magic_len = magic.len();
@@ -480,10 +1717,15 @@ fn loadCoreSkeletonFromSource(
             "Bonecount {bone_count} is not positive",
         )));
     }
+    checkLimit("Bone count", bone_count as usize, options.limits.max_bones)?;
 
     let mut skeleton = skel.borrow_mut();
-    for bone_id in 0..bone_count as usize {
-        let bone = loadCoreBones(dataSrc, version, skel.clone())?;
+    let bone_count = bone_count as usize;
+    for bone_id in 0..bone_count {
+        options.reportProgress(dataSrc, LoadStage::Bones, bone_id, bone_count)?;
+
+        let bone = loadCoreBones(dataSrc, version, skel.clone(), options)
+            .map_err(|e| e.in_element(format!("bone {bone_id}")))?;
 
         let name = bone.borrow().getName().to_string();
 
@@ -492,6 +1734,7 @@ fn loadCoreSkeletonFromSource(
         // FIXME: This seems redundant, as it's called from within addCoreBone above.
         skeleton.mapCoreBoneName(bone_id, name)?;
     }
+    options.reportProgress(dataSrc, LoadStage::Bones, bone_count, bone_count)?;
 
     skeleton.calculateState();
     Ok(())
@@ -512,6 +1755,7 @@ fn loadCoreBones(
     dataSrc: &mut dyn DataSource,
     version: i32,
     skeleton: Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
 ) -> Result<Rc<RefCell<CalCoreBone>>, LoaderError> {
     let hasNodeLights = version >= FIRST_FILE_VERSION_WITH_NODE_LIGHTS;
 
@@ -521,7 +1765,7 @@ fn loadCoreBones(
     //   }
 
     // read the name of the bone
-    let strName = dataSrc.readString()?;
+    let strName = options.readString(dataSrc)?;
 
     // get the translation of the bone
     let tx = dataSrc.readFloat()?;
@@ -545,6 +1789,13 @@ fn loadCoreBones(
     let rzBoneSpace = dataSrc.readFloat()?;
     let rwBoneSpace = dataSrc.readFloat()?;
 
+    options.checkFinite(dataSrc, "Bone translation", &[tx, ty, tz])?;
+    options.checkFinite(
+        dataSrc,
+        "Bone space translation",
+        &[txBoneSpace, tyBoneSpace, tzBoneSpace],
+    )?;
+
     // get the parent bone id
     let parentId = dataSrc.readInteger()?;
 
@@ -558,22 +1809,19 @@ fn loadCoreBones(
 
     let mut rot = CalQuaternion::<f32>::new(rw, rx, ry, rz);
     let rotbs = CalQuaternion::<f32>::new(rwBoneSpace, rxBoneSpace, ryBoneSpace, rzBoneSpace);
+    options.checkRotation(dataSrc, "Bone rotation", &rot)?;
+    options.checkRotation(dataSrc, "Bone space rotation", &rotbs)?;
     let mut trans = CalVector::new(tx, ty, tz);
 
-    if (loadingMode & LOADER_ROTATE_X_AXIS) == LOADER_ROTATE_X_AXIS {
-        if parentId == -1 {
-            // only root bone necessary
-
-            // Root bone must have quaternion rotated
-            let x_axis_90 = CalQuaternion::new(0.7071067811, 0.7071067811, 0.0, 0.0);
-            // rot *= x_axis_90;
-            rot = rot.mul(x_axis_90);
-            // Root bone must have translation rotated also
-            // trans *= x_axis_90;
-            trans = x_axis_90.mul(trans);
-        }
+    if parentId == -1 {
+        // only root bone necessary
+        // Root bone must have quaternion and translation rotated
+        (rot, trans) = options.rotateRootTransform(rot, trans);
     }
 
+    // the meshes are rotated too, so bone space must undo that
+    let rotbs = options.rotateBoneSpace(rotbs);
+
     // check if an error happened
     //   if !dataSrc.ok()  {
     //     dataSrc.setError();
@@ -587,6 +1835,7 @@ fn loadCoreBones(
             "Child count {childCount} is not positive",
         )));
     }
+    checkLimit("Child count", childCount as usize, options.limits.max_bones)?;
 
     let mut childs = Vec::<i32>::with_capacity(childCount as usize);
 
@@ -618,12 +1867,148 @@ fn loadCoreBones(
         // name: strName, m_parentId, m_translation,
     )));
 
-    Ok(pCoreBone)
-}
+    Ok(pCoreBone)
+}
+
+//1158
+fn usesAnimationCompression(version: i32) -> bool {
+    version >= FIRST_FILE_VERSION_WITH_ANIMATION_COMPRESSION
+}
+
+// Compressed keyframes store their time as a count of frames at this rate.
+pub(super) const keyframeTimeResolution: f32 = 30.0;
+pub(super) const keyframeTimeMax: u32 = 0xffff;
+
+// Low range translations are signed fixed point, this many bits per axis.
+pub(super) const translationLowRangeBits: u32 = 21;
+pub(super) const translationLowRangeScale: f32 = 512.0;
+
+// Rotations are stored as the three smallest components of the quaternion.
+pub(super) const rotationComponentBits: u32 = 15;
+pub(super) const rotationComponentRange: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+const maxCompressedKeyframeBytes: usize = 12 + 6 + 2;
+
+fn TranslationWritten(
+    lastCoreKeyframe: &Option<Rc<CalCoreKeyframe>>,
+    translationRequired: bool,
+    translationIsDynamic: bool,
+) -> bool {
+    translationRequired && (lastCoreKeyframe.is_none() || translationIsDynamic)
+}
+
+/*****************************************************************************/
+/** Returns the number of bytes used by a compressed keyframe.
+ *
+ * A compressed keyframe is an optional translation (12 bytes of floats if the
+ * track needs the high range, otherwise 8 bytes of fixed point), followed by a
+ * 6 byte rotation and a 2 byte time.
+ *****************************************************************************/
+fn compressedKeyframeRequiredBytes(
+    lastCoreKeyframe: &Option<Rc<CalCoreKeyframe>>,
+    translationRequired: bool,
+    highRangeRequired: bool,
+    translationIsDynamic: bool,
+) -> usize {
+    let mut bytesRequired = 0;
+    if TranslationWritten(lastCoreKeyframe, translationRequired, translationIsDynamic) {
+        bytesRequired += if highRangeRequired { 12 } else { 8 };
+    }
+    bytesRequired + 6 + 2
+}
+
+fn readLittleEndianBits(buf: &[u8]) -> u64 {
+    buf.iter()
+        .rev()
+        .fold(0u64, |bits, byte| (bits << 8) | *byte as u64)
+}
+
+fn signExtend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+/*****************************************************************************/
+/** Decodes a compressed keyframe.
+ *
+ * This function decodes the keyframe in buf, filling in the translation from
+ * the previous keyframe or the core bone when the track does not store one.
+ *
+ * @return The number of bytes consumed, the keyframe time in frames, and the
+ *         translation and rotation of the keyframe.
+ *****************************************************************************/
+fn readCompressedKeyframe(
+    buf: &[u8],
+    coreboneOrNull: &Option<Rc<RefCell<CalCoreBone>>>,
+    lastCoreKeyframe: &Option<Rc<CalCoreKeyframe>>,
+    translationRequired: bool,
+    highRangeRequired: bool,
+    translationIsDynamic: bool,
+) -> (usize, u32, CalVector<f32>, CalQuaternion<f32>) {
+    let mut pos = 0;
+
+    // Read in the translation or get it from the skeleton or zero it.
+    let translation = if translationRequired {
+        match lastCoreKeyframe {
+            Some(last) if !translationIsDynamic => *last.getTranslation(),
+            _ => {
+                if highRangeRequired {
+                    let mut values = [0.0f32; 3];
+                    for value in values.iter_mut() {
+                        *value = f32::from_le_bytes([
+                            buf[pos],
+                            buf[pos + 1],
+                            buf[pos + 2],
+                            buf[pos + 3],
+                        ]);
+                        pos += 4;
+                    }
+                    CalVector::new(values[0], values[1], values[2])
+                } else {
+                    let bits = readLittleEndianBits(&buf[pos..pos + 8]);
+                    pos += 8;
+                    let mask = (1u64 << translationLowRangeBits) - 1;
+                    let axis = |i: u32| {
+                        let raw = (bits >> (i * translationLowRangeBits)) & mask;
+                        signExtend(raw, translationLowRangeBits) as f32 / translationLowRangeScale
+                    };
+                    CalVector::new(axis(0), axis(1), axis(2))
+                }
+            }
+        }
+    } else {
+        match coreboneOrNull {
+            Some(bone) => *bone.borrow().getTranslation(),
+            None => CalVector::new(0.0, 0.0, 0.0),
+        }
+    };
+
+    // Read in the rotation. The largest component is dropped and rebuilt from
+    // the other three, which are each stored in the range +/- 1/sqrt(2).
+    let bits = readLittleEndianBits(&buf[pos..pos + 6]);
+    pos += 6;
+    let largest = (bits & 0x3) as usize;
+    let maxValue = ((1u64 << rotationComponentBits) - 1) as f32;
+    let mut components = [0.0f32; 4];
+    let mut sumSquares = 0.0;
+    let mut shift = 2;
+    for (i, component) in components.iter_mut().enumerate() {
+        if i == largest {
+            continue;
+        }
+        let raw = (bits >> shift) & ((1u64 << rotationComponentBits) - 1);
+        shift += rotationComponentBits;
+        *component = (raw as f32 / maxValue * 2.0 - 1.0) * rotationComponentRange;
+        sumSquares += *component * *component;
+    }
+    components[largest] = (1.0 - sumSquares).max(0.0).sqrt();
+    let rotation = CalQuaternion::new(components[3], components[0], components[1], components[2]);
+
+    // Read in the time.
+    let keyframeTime = buf[pos] as u32 + buf[pos + 1] as u32 * 256;
+    pos += 2;
 
-//1158
-fn usesAnimationCompression(version: i32) -> bool {
-    version >= FIRST_FILE_VERSION_WITH_ANIMATION_COMPRESSION
+    (pos, keyframeTime, translation, rotation)
 }
 
 //1191
@@ -647,63 +2032,87 @@ pub fn loadCoreKeyframe(
     highRangeRequired: bool,
     translationIsDynamic: bool,
     useAnimationCompression: bool,
+    options: &LoaderOptions,
 ) -> Result<CalCoreKeyframe, LoaderError> {
+    let (keyframe, _) = readCoreKeyframe(
+        dataSrc,
+        coreboneOrNull,
+        version,
+        prevCoreKeyframe,
+        translationRequired,
+        highRangeRequired,
+        translationIsDynamic,
+        useAnimationCompression,
+        options,
+    )?;
+    Ok(keyframe)
+}
+
+/// Loads a core keyframe, also returning whether its translation was taken
+/// from the skeleton rather than read.
+fn readCoreKeyframe(
+    dataSrc: &mut dyn DataSource,
+    coreboneOrNull: &Option<Rc<RefCell<CalCoreBone>>>,
+    version: i32,
+    prevCoreKeyframe: &Option<Rc<CalCoreKeyframe>>,
+    translationRequired: bool,
+    highRangeRequired: bool,
+    translationIsDynamic: bool,
+    useAnimationCompression: bool,
+    options: &LoaderOptions,
+) -> Result<(CalCoreKeyframe, bool), LoaderError> {
     let time: f32;
     let translation: CalVector<f32>;
     let rotation: CalQuaternion<f32>;
+    let translationFromSkeleton: bool;
 
     if useAnimationCompression {
-        todo!();
-    //       unsigned int bytesRequired = compressedKeyframeRequiredBytes( prevCoreKeyframe, translationRequired, highRangeRequired, translationIsDynamic );
-    //       assert( bytesRequired < 100 );
-    //       unsigned char buf[ 100 ];
-    //       if( !dataSrc.readBytes( buf, bytesRequired ) ) {
-    //          CalError::setLastError(CalError::INVALID_FILE_FORMAT, __FILE__, __LINE__);
-    //          return NULL;
-    //       }
-    //       CalVector vec;
-    //       CalQuaternion quat;
-    //       unsigned int bytesRead = readCompressedKeyframe( buf, bytesRequired, coreboneOrNull,
-    //          & vec, & quat, prevCoreKeyframe,
-    //          translationRequired, highRangeRequired, translationIsDynamic,
-    //          useAnimationCompression);
-    //       if( bytesRead != bytesRequired ) {
-    //          CalError::setLastError(CalError::INVALID_FILE_FORMAT, __FILE__, __LINE__);
-    //          return NULL;
-    //       }
-    //       tx = vec.x;
-    //       ty = vec.y;
-    //       tz = vec.z;
-    //       rx = quat.x;
-    //       ry = quat.y;
-    //       rz = quat.z;
-    //       rw = quat.w;
-    //       if(version < Cal::FIRST_FILE_VERSION_WITH_ANIMATION_COMPRESSION6 ) {
-    //          if(version >= Cal::FIRST_FILE_VERSION_WITH_ANIMATION_COMPRESSION4 ) {
-    //             if( version >= Cal::FIRST_FILE_VERSION_WITH_ANIMATION_COMPRESSION5 ) {
-    //                if( TranslationWritten( prevCoreKeyframe, translationRequired, translationIsDynamic ) ) {
-    //                   dataSrc.readFloat(tx);
-    //                   dataSrc.readFloat(ty);
-    //                   dataSrc.readFloat(tz);
-    //                }
-    //             }
-
-    //             // get the rotation of the bone
-    //             dataSrc.readFloat(rx);
-    //             dataSrc.readFloat(ry);
-    //             dataSrc.readFloat(rz);
-    //             dataSrc.readFloat(rw);
-    //          }
-    //       }
+        let bytesRequired = compressedKeyframeRequiredBytes(
+            prevCoreKeyframe,
+            translationRequired,
+            highRangeRequired,
+            translationIsDynamic,
+        );
+        let mut buf = [0u8; maxCompressedKeyframeBytes];
+        dataSrc.readBytes(&mut buf[..bytesRequired], bytesRequired)?;
+
+        let (bytesRead, keyframeTime, vec, quat) = readCompressedKeyframe(
+            &buf[..bytesRequired],
+            coreboneOrNull,
+            prevCoreKeyframe,
+            translationRequired,
+            highRangeRequired,
+            translationIsDynamic,
+        );
+        if bytesRead != bytesRequired {
+            return Err(LoaderError::FormatError(format!(
+                "Compressed keyframe read {bytesRead} bytes, expected {bytesRequired}"
+            )));
+        }
+
+        time = keyframeTime as f32 / keyframeTimeResolution;
+        translation = vec;
+        rotation = quat;
+        translationFromSkeleton = !translationRequired && coreboneOrNull.is_some();
+
+        // Versions before FIRST_FILE_VERSION_WITH_ANIMATION_COMPRESSION6 wrote an
+        // uncompressed copy of the rotation after the keyframe, but all of those
+        // versions predate the compression flag, so there is nothing more to read.
     } else {
         time = dataSrc.readFloat()?;
+        options.checkFinite(dataSrc, "Keyframe time", &[time])?;
 
         // get the translation of the bone
         let mut tx = dataSrc.readFloat()?;
         let mut ty = dataSrc.readFloat()?;
         let mut tz = dataSrc.readFloat()?;
 
-        if coreboneOrNull.is_some() && TranslationInvalid(tx, ty, tz) {
+        translationFromSkeleton = coreboneOrNull.is_some() && TranslationInvalid(tx, ty, tz);
+        if translationFromSkeleton {
+            options.reportProblem(
+                dataSrc,
+                String::from("Keyframe translation is invalid, using the bone translation"),
+            )?;
             let bone = coreboneOrNull.as_ref().unwrap().borrow();
             let tv = bone.getTranslation();
             tx = tv.x;
@@ -711,6 +2120,7 @@ pub fn loadCoreKeyframe(
             tz = tv.z;
         }
 
+        options.checkFinite(dataSrc, "Keyframe translation", &[tx, ty, tz])?;
         translation = CalVector::<f32>::new(tx, ty, tz);
 
         // get the rotation of the bone
@@ -720,12 +2130,13 @@ pub fn loadCoreKeyframe(
         let rw = dataSrc.readFloat()?;
 
         rotation = CalQuaternion::<f32>::new(rw, rx, ry, rz);
+        options.checkRotation(dataSrc, "Keyframe rotation", &rotation)?;
     }
 
     // allocate a new core keyframe instance
     let pCoreKeyframe = CalCoreKeyframe::new(time, translation, rotation);
 
-    Ok(pCoreKeyframe)
+    Ok((pCoreKeyframe, translationFromSkeleton))
 }
 
 //1671
@@ -743,295 +2154,477 @@ pub fn loadCoreKeyframe(
 fn loadCoreSubmesh(
     dataSrc: &mut dyn DataSource,
     version: i32,
+    options: &LoaderOptions,
 ) -> Result<Rc<RefCell<CalCoreSubmesh>>, LoaderError> {
-    use super::submesh::{Face, PhysicalProperty, Spring, TextureCoordinate};
+    use super::submesh::{Face, PhysicalProperty, SlaveAttribute, Spring, TextureCoordinate};
     use super::submorphtarget::BlendVertex;
     use std::mem;
 
     let hasVertexColors = version >= FIRST_FILE_VERSION_WITH_VERTEX_COLORS;
     let hasMorphTargetsInMorphFiles =
         version >= FIRST_FILE_VERSION_WITH_MORPH_TARGETS_IN_MORPH_FILES;
+    let hasVertexSlaveAttributes = version >= FIRST_FILE_VERSION_WITH_VERTEX_SLAVES_ATTRIBUTES;
 
     // get the material thread id of the submesh
     let coreMaterialThreadId = dataSrc.readInteger()?;
 
     // get the number of vertices, faces, level-of-details and springs
-    let vertexCount = dataSrc.readInteger()? as usize;
+    let limits = &options.limits;
+    let vertexCount = options.readCount(dataSrc, "Vertex count", limits.max_vertices)?;
 
-    let faceCount = dataSrc.readInteger()? as usize;
+    let faceCount = options.readCount(dataSrc, "Face count", limits.max_faces)?;
 
-    let lodCount = dataSrc.readInteger()?;
+    let lodCount = options.readCount(dataSrc, "LOD count", i32::MAX as usize)? as i32;
 
-    let springCount = dataSrc.readInteger()? as usize;
+    let springCount = options.readCount(dataSrc, "Spring count", limits.max_springs)?;
 
     // get the number of texture coordinates per vertex
-    let textureCoordinateCount = dataSrc.readInteger()? as usize;
+    let textureCoordinateCount = options.readCount(
+        dataSrc,
+        "Texture coordinate count",
+        limits.max_texture_coordinate_sets,
+    )?;
 
     let mut morphCount = 0;
     if hasMorphTargetsInMorphFiles {
-        morphCount = dataSrc.readInteger()? as usize;
+        morphCount = options.readCount(dataSrc, "Morph target count", limits.max_morph_targets)?;
     }
 
-    // allocate a new core submesh instance
-    let pCoreSubmesh = CalCoreSubmesh::new(
-        coreMaterialThreadId,
-        lodCount,
+    // get the names and sizes of the vertex slave attributes, whose values
+    // are added as the vertices are read
+    let mut slaveAttributes: Vec<SlaveAttribute> = Vec::new();
+    if hasVertexSlaveAttributes {
+        let slaveAttributeCount = options.readCount(
+            dataSrc,
+            "Slave attribute count",
+            limits.max_slave_attributes,
+        )?;
+        for slaveAttributeId in 0..slaveAttributeCount {
+            let inSlaveAttribute =
+                |e: LoaderError| e.in_element(format!("slave attribute {slaveAttributeId}"));
+
+            let name = options.readString(dataSrc).map_err(inSlaveAttribute)?;
+            let componentCount = dataSrc
+                .readInteger()
+                .map_err(|e| inSlaveAttribute(e.into()))?;
+            if componentCount <= 0 {
+                return Err(LoaderError::FormatError(format!(
+                    "Invalid component count {componentCount} of slave attribute {name}"
+                )));
+            }
+            checkLimit(
+                "Slave attribute component count",
+                componentCount as usize,
+                options.limits.max_slave_attribute_components,
+            )?;
+            slaveAttributes.push(SlaveAttribute::new(name, componentCount as usize, 0));
+        }
+    }
+
+    let slaveComponentCount: usize = slaveAttributes.iter().map(|a| a.componentCount).sum();
+    checkSubmeshValues(
         vertexCount,
-        textureCoordinateCount,
-        faceCount,
-        springCount,
-    );
+        textureCoordinateCount + slaveComponentCount + morphCount,
+        limits,
+    )?;
 
-    let coreSubmesh = Rc::new(RefCell::new(pCoreSubmesh));
+    // load all vertices and their influences, growing the vectors as they are
+    // read so that a file cannot claim more vertices than it holds
+    let mut has_non_white_vertex_colors = false;
 
-    let mut pCoreSubmesh = coreSubmesh.borrow_mut();
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut textureCoordinates: Vec<Vec<TextureCoordinate>> =
+        vec![Vec::new(); textureCoordinateCount];
+    let mut physicalProperties: Vec<PhysicalProperty> = Vec::new();
 
-    // reserve memory for all the submesh data
-    // This is done insize ::new() in the Rust implementation
-    // pCoreSubmesh->reserve(vertexCount, textureCoordinateCount, faceCount, springCount);
+    for vertexId in 0..vertexCount {
+        options.checkCancelled(vertexId)?;
+        (|| -> Result<(), LoaderError> {
+            let mut vertex = Vertex::default();
+
+            // load data of the vertex
+            vertex.position.x = dataSrc.readFloat()?;
+            vertex.position.y = dataSrc.readFloat()?;
+            vertex.position.z = dataSrc.readFloat()?;
+            vertex.normal.x = dataSrc.readFloat()?;
+            vertex.normal.y = dataSrc.readFloat()?;
+            vertex.normal.z = dataSrc.readFloat()?;
+            options.checkFinite(
+                dataSrc,
+                "Vertex position and normal",
+                &[
+                    vertex.position.x,
+                    vertex.position.y,
+                    vertex.position.z,
+                    vertex.normal.x,
+                    vertex.normal.y,
+                    vertex.normal.z,
+                ],
+            )?;
+            vertex.position = options.rotateVector(vertex.position);
+            vertex.normal = options.rotateVector(vertex.normal);
+            vertex.vertexColor.x = 1.0;
+            vertex.vertexColor.y = 1.0;
+            vertex.vertexColor.z = 1.0;
+            if hasVertexColors {
+                vertex.vertexColor.x = dataSrc.readFloat()?;
+                vertex.vertexColor.y = dataSrc.readFloat()?;
+                vertex.vertexColor.z = dataSrc.readFloat()?;
+                if vertex.vertexColor.x != 1.0
+                    || vertex.vertexColor.y != 1.0
+                    || vertex.vertexColor.z != 1.0
+                {
+                    has_non_white_vertex_colors = true;
+                }
+            }
 
-    // load the tangent space enable flags.
-    for textureCoordinateId in 0..textureCoordinateCount {
-        pCoreSubmesh.enableTangents(textureCoordinateId, false);
-    }
+            // load all slave attributes of the vertex
+            for slaveAttribute in slaveAttributes.iter_mut() {
+                for _ in 0..slaveAttribute.componentCount {
+                    slaveAttribute.values.push(dataSrc.readFloat()?);
+                }
+            }
+            vertex.collapseId = dataSrc.readInteger()?;
+            vertex.faceCollapseCount = dataSrc.readInteger()?;
 
-    // load all vertices and their influences
-    let mut has_non_white_vertex_colors = false;
+            // load all texture coordinates of the vertex
+            for textureCoordinateId in 0..textureCoordinateCount {
+                let u = dataSrc.readFloat()?;
+                let v = dataSrc.readFloat()?;
+                options.checkFinite(dataSrc, "Texture coordinate", &[u, v])?;
+                let textureCoordinate = options.textureCoordinate(u, v);
 
-    let mut textureCoordinates: Vec<Vec<TextureCoordinate>> =
-        vec![vec![TextureCoordinate::new(); vertexCount]; textureCoordinateCount];
+                textureCoordinates[textureCoordinateId].push(textureCoordinate);
+            }
 
-    // let vertexVector =
-    for vertexId in 0..vertexCount {
-        let vertex = pCoreSubmesh.getVectorVertexMut().get_mut(vertexId).unwrap(); // REFERENCE
-
-        // load data of the vertex
-        vertex.position.x = dataSrc.readFloat()?;
-        vertex.position.y = dataSrc.readFloat()?;
-        vertex.position.z = dataSrc.readFloat()?;
-        vertex.normal.x = dataSrc.readFloat()?;
-        vertex.normal.y = dataSrc.readFloat()?;
-        vertex.normal.z = dataSrc.readFloat()?;
-        vertex.vertexColor.x = 1.0;
-        vertex.vertexColor.y = 1.0;
-        vertex.vertexColor.z = 1.0;
-        if hasVertexColors {
-            vertex.vertexColor.x = dataSrc.readFloat()?;
-            vertex.vertexColor.y = dataSrc.readFloat()?;
-            vertex.vertexColor.z = dataSrc.readFloat()?;
-            if vertex.vertexColor.x != 1.0
-                || vertex.vertexColor.y != 1.0
-                || vertex.vertexColor.z != 1.0
-            {
-                has_non_white_vertex_colors = true;
+            // get the number of influences
+            let influenceCount = dataSrc.readInteger()?;
+            if influenceCount < 0 {
+                return Err(LoaderError::FormatError(format!(
+                    "Invalid influence count {influenceCount}"
+                )));
             }
-        }
-        vertex.collapseId = dataSrc.readInteger()?;
-        vertex.faceCollapseCount = dataSrc.readInteger()?;
+            checkLimit(
+                "Influence count",
+                influenceCount as usize,
+                options.limits.max_bones,
+            )?;
+            let influenceCount = influenceCount as usize;
+
+            // reserve memory for the influences in the vertex
+            vertex
+                .vectorInfluence
+                .resize(influenceCount, Influence::default());
+
+            // load all influences of the vertex
+            for influenceId in 0..influenceCount {
+                let inInfluence = |e: SourceError| {
+                    LoaderError::from(e).in_element(format!("influence {influenceId}"))
+                };
+
+                // load data of the influence
+                vertex.vectorInfluence[influenceId].boneId =
+                    dataSrc.readInteger().map_err(inInfluence)?;
+                vertex.vectorInfluence[influenceId].weight =
+                    dataSrc.readFloat().map_err(inInfluence)?;
+
+                let influence = &vertex.vectorInfluence[influenceId];
+                if influence.boneId < 0 {
+                    options.reportProblem(
+                        dataSrc,
+                        format!(
+                            "Influence of vertex {vertexId} has negative bone ID {}",
+                            influence.boneId
+                        ),
+                    )?;
+                }
+                options.checkFinite(dataSrc, "Influence weight", &[influence.weight])?;
+            }
+
+            vertices.push(vertex);
 
-        // load all texture coordinates of the vertex
-        for textureCoordinateId in 0..textureCoordinateCount {
-            let mut textureCoordinate =
-                TextureCoordinate::from_values(dataSrc.readFloat()?, dataSrc.readFloat()?);
+            // load the physical property of the vertex if there are springs in the core submesh
+            if springCount > 0 {
+                // load data of the physical property
+                let weight = dataSrc.readFloat()?;
+                options.checkFinite(dataSrc, "Physical property weight", &[weight])?;
 
-            // load data of the influence
-            if (loadingMode & LOADER_INVERT_V_COORD) != 0 {
-                textureCoordinate.v = 1.0 - textureCoordinate.v;
+                physicalProperties.push(PhysicalProperty::new(weight));
             }
 
-            textureCoordinates[textureCoordinateId][vertexId] = textureCoordinate;
+            Ok(())
+        })()
+        .map_err(|e| e.in_element(format!("vertex {vertexId}")))?;
+    }
 
-            // Can't set them while vertex is in scope, as unable to borrow another mut reference.
-            // set texture coordinate in the core submesh instance
-            // pCoreSubmesh.setTextureCoordinate(vertexId, textureCoordinateId, textureCoordinate);
-        }
+    // load all springs
+    let mut springs: Vec<Spring> = Vec::new();
+    for springId in 0..springCount {
+        let inSpring =
+            |e: SourceError| LoaderError::from(e).in_element(format!("spring {springId}"));
 
-        // get the number of influences
-        let influenceCount = dataSrc.readInteger()?;
-        if influenceCount < 0 {
-            return Err(LoaderError::FormatError(format!(
-                "Invalid influence count {influenceCount}"
-            )));
-        }
-        let influenceCount = influenceCount as usize;
-
-        // reserve memory for the influences in the vertex
-        vertex
-            .vectorInfluence
-            .resize(influenceCount, Influence::default());
-
-        // load all influences of the vertex
-        for influenceId in 0..influenceCount {
-            // load data of the influence
-            vertex.vectorInfluence[influenceId].boneId = dataSrc.readInteger()?;
-            vertex.vectorInfluence[influenceId].weight = dataSrc.readFloat()?;
-        }
+        // load data of the spring
+        let id1 = dataSrc.readInteger().map_err(inSpring)?;
+        let id2 = dataSrc.readInteger().map_err(inSpring)?;
+        let springCoefficient = dataSrc.readFloat().map_err(inSpring)?;
+        let idleLength = dataSrc.readFloat().map_err(inSpring)?;
 
-        // set vertex in the core submesh instance
-        // FIXME: This shouldn't need to be done. The vertex is being modified by reference.
-        // pCoreSubmesh.setVertex(vertexId, vertex);
+        let spring = Spring::from_values([id1, id2], springCoefficient, idleLength);
+        springs.push(spring);
+    }
 
-        // load the physical property of the vertex if there are springs in the core submesh
-        if springCount > 0 {
-            // load data of the physical property
-            let weight = dataSrc.readFloat()?;
+    // allocate a new core submesh instance now that its vertices are read;
+    // the faces are set once they are read too
+    let pCoreSubmesh = CalCoreSubmesh::new(
+        coreMaterialThreadId,
+        lodCount,
+        vertexCount,
+        textureCoordinateCount,
+        0,
+        springCount,
+    );
+
+    let coreSubmesh = Rc::new(RefCell::new(pCoreSubmesh));
 
-            let physicalProperty = PhysicalProperty::new(weight);
+    let mut pCoreSubmesh = coreSubmesh.borrow_mut();
 
-            // set the physical property in the core submesh instance
-            pCoreSubmesh.setPhysicalProperty(vertexId, physicalProperty);
-        }
+    // load the tangent space enable flags.
+    for textureCoordinateId in 0..textureCoordinateCount {
+        pCoreSubmesh.enableTangents(textureCoordinateId, false);
     }
+
+    *pCoreSubmesh.getVectorVertexMut() = vertices;
     pCoreSubmesh.setAllTextureCoordinates(textureCoordinates);
 
+    for (vertexId, physicalProperty) in physicalProperties.into_iter().enumerate() {
+        pCoreSubmesh.setPhysicalProperty(vertexId, physicalProperty);
+    }
+
     pCoreSubmesh.setHasNonWhiteVertexColors(has_non_white_vertex_colors);
 
-    // load all springs
-    for springId in 0..springCount {
-        // load data of the spring
-        let id1 = dataSrc.readInteger()?;
-        let id2 = dataSrc.readInteger()?;
-        let springCoefficient = dataSrc.readFloat()?;
-        let idleLength = dataSrc.readFloat()?;
+    for slaveAttribute in slaveAttributes {
+        pCoreSubmesh.addSlaveAttribute(slaveAttribute);
+    }
 
-        let spring = Spring::from_values([id1, id2], springCoefficient, idleLength);
+    for (springId, spring) in springs.into_iter().enumerate() {
         // set spring in the core submesh instance
         pCoreSubmesh.setSpring(springId, spring);
     }
 
     for morphId in 0..morphCount {
-        // if !morphTarget.reserve(vertexCount) {
-        //     return Err(LoaderError::FormatError(format!("Unknown reserve error")));
-        // }
+        (|| -> Result<(), LoaderError> {
+            // if !morphTarget.reserve(vertexCount) {
+            //     return Err(LoaderError::FormatError(format!("Unknown reserve error")));
+            // }
 
-        let morphName = dataSrc.readString()?;
-        // morphTarget.setName(morphName);
+            let morphName = options.readString(dataSrc)?;
+            // morphTarget.setName(morphName);
 
-        let mut morphTarget =
-            CalCoreSubMorphTarget::new(coreSubmesh.clone(), vertexCount, morphName);
-
-        let mut cpt = 0;
-        let nbBlendVertex = dataSrc.readInteger()?;
-        if nbBlendVertex <= 0 {
-            return Err(LoaderError::FormatError(format!(
-                "Invalid nbBlendVertex {nbBlendVertex}"
-            )));
-        }
+            let mut morphTarget =
+                CalCoreSubMorphTarget::new(coreSubmesh.clone(), vertexCount, morphName);
 
-        let mut blendVertId = dataSrc.readInteger()? as usize;
+            let mut cpt = 0;
+            let nbBlendVertex = dataSrc.readInteger()?;
+            if nbBlendVertex <= 0 {
+                return Err(LoaderError::FormatError(format!(
+                    "Invalid nbBlendVertex {nbBlendVertex}"
+                )));
+            }
 
-        for blendVertI in 0..vertexCount {
-            let mut Vertex = BlendVertex::new(textureCoordinateCount);
+            let mut blendVertId = dataSrc.readInteger()? as usize;
 
-            let copyOrig = blendVertI < blendVertId;
+            for blendVertI in 0..vertexCount {
+                let mut Vertex = BlendVertex::new(textureCoordinateCount);
 
-            if !copyOrig {
-                Vertex.position = CalVectorFromDataSrc(dataSrc)?;
-                Vertex.normal = CalVectorFromDataSrc(dataSrc)?;
+                let copyOrig = blendVertI < blendVertId;
 
-                for textureCoordinateId in 0..textureCoordinateCount {
-                    let mut textureCoordinate =
-                        TextureCoordinate::from_values(dataSrc.readFloat()?, dataSrc.readFloat()?);
+                if !copyOrig {
+                    Vertex.position = options.rotateVector(CalVectorFromDataSrc(dataSrc)?);
+                    Vertex.normal = options.rotateVector(CalVectorFromDataSrc(dataSrc)?);
 
-                    if loadingMode & LOADER_INVERT_V_COORD != 0 {
-                        textureCoordinate.v = 1.0 - textureCoordinate.v;
+                    for _ in 0..textureCoordinateCount {
+                        let u = dataSrc.readFloat()?;
+                        let v = dataSrc.readFloat()?;
+                        Vertex.textureCoords.push(options.textureCoordinate(u, v));
                     }
-                    Vertex.textureCoords.push(textureCoordinate);
-                }
 
-                morphTarget.setBlendVertex(blendVertI, &Vertex);
-                cpt += 1;
-                if cpt < nbBlendVertex {
-                    blendVertId = dataSrc.readInteger()? as usize;
-                } else {
-                    blendVertId = vertexCount;
+                    morphTarget.setBlendVertex(blendVertI, &Vertex);
+                    cpt += 1;
+                    if cpt < nbBlendVertex {
+                        blendVertId = dataSrc.readInteger()? as usize;
+                    } else {
+                        blendVertId = vertexCount;
+                    }
                 }
             }
-        }
-        // TODO: Don't setCoreSubmesh in this function, as it's assigned at morphTarget creation.
-        pCoreSubmesh.addCoreSubMorphTarget(morphTarget);
+            // TODO: Don't setCoreSubmesh in this function, as it's assigned at morphTarget creation.
+            pCoreSubmesh.addCoreSubMorphTarget(morphTarget);
+
+            Ok(())
+        })()
+        .map_err(|e| e.in_element(format!("morph target {morphId}")))?;
     }
 
     // load all faces
+    let mut faces: Vec<Face> = Vec::new();
     let mut justOnce = 0;
     let mut flipModel = false;
     for faceId in 0..faceCount {
-        // load data of the face
-
-        let mut tmp = [0; 3];
-        tmp[0] = dataSrc.readInteger()?;
-        tmp[1] = dataSrc.readInteger()?;
-        tmp[2] = dataSrc.readInteger()?;
-
-        if mem::size_of::<crate::CalIndex>() == 2 {
-            if tmp[0] > 65535 || tmp[1] > 65535 || tmp[2] > 65535 {
-                return Err(LoaderError::FormatError(format!(
-                    "Invalid index in mesh face {faceId}: [{},{},{}]",
-                    tmp[0], tmp[1], tmp[2]
-                )));
+        (|| -> Result<(), LoaderError> {
+            // load data of the face
+
+            let mut tmp = [0; 3];
+            tmp[0] = dataSrc.readInteger()?;
+            tmp[1] = dataSrc.readInteger()?;
+            tmp[2] = dataSrc.readInteger()?;
+
+            if mem::size_of::<crate::CalIndex>() == 2 {
+                if tmp[0] > 65535 || tmp[1] > 65535 || tmp[2] > 65535 {
+                    return Err(LoaderError::FormatError(format!(
+                        "Invalid index in mesh face {faceId}: [{},{},{}]",
+                        tmp[0], tmp[1], tmp[2]
+                    )));
+                }
             }
-        }
 
-        let mut face = Face::new(tmp);
-
-        // check if left-handed coord system is used by the object
-        // can be done only once since the object has one system for all faces
-        if justOnce == 0 {
-            // get vertexes of first face
-            let vectorVertex = pCoreSubmesh.getVectorVertex();
-            let v1 = &vectorVertex[tmp[0] as usize];
-            let v2 = &vectorVertex[tmp[1] as usize];
-            let v3 = &vectorVertex[tmp[2] as usize];
-
-            let point1 = CalVector::<f32>::new(v1.position.x, v1.position.y, v1.position.z);
-            let point2 = CalVector::<f32>::new(v2.position.x, v2.position.y, v2.position.z);
-            let point3 = CalVector::<f32>::new(v3.position.x, v3.position.y, v3.position.z);
-
-            // gets vectors (v1-v2) and (v3-v2)
-            let vect1 = point1 - point2;
-            let vect2 = point3 - point2;
-
-            // calculates normal of face
-            let cross = CalVector::cross(vect1, vect2);
-            let crossLength = cross.magnitude();
-            if crossLength == 0.0 {
-                return Err(LoaderError::FormatError(format!("Face normal invalid")));
-            }
-            let faceNormal = cross / crossLength;
+            let mut face = Face::new(tmp);
 
-            // compare the calculated normal with the normal of a vertex
-            let maxNorm = v1.normal;
+            // check if left-handed coord system is used by the object
+            // can be done only once since the object has one system for all faces
+            if justOnce == 0 {
+                flipModel =
+                    faceNeedsFlip(pCoreSubmesh.getVectorVertex(), &tmp, options.flip_winding)?;
 
-            // if the two vectors point to the same direction then the poly needs flipping
-            // so if the dot product > 0 it needs flipping
-            if faceNormal.dot(maxNorm) > 0.0 {
-                flipModel = true;
+                justOnce = 1;
             }
 
-            // flip the winding order if the loading flags request it
-            if (loadingMode & LOADER_FLIP_WINDING) != 0 {
-                flipModel = !flipModel;
+            // flip if needed
+            if flipModel {
+                let tmp = face.vertexId[1];
+                face.vertexId[1] = face.vertexId[2];
+                face.vertexId[2] = tmp;
             }
 
-            justOnce = 1;
-        }
-
-        // flip if needed
-        if flipModel {
-            let tmp = face.vertexId[1];
-            face.vertexId[1] = face.vertexId[2];
-            face.vertexId[2] = tmp;
-        }
+            faces.push(face);
 
-        // set face in the core submesh instance
-        pCoreSubmesh.setFace(faceId, face);
+            Ok(())
+        })()
+        .map_err(|e| e.in_element(format!("face {faceId}")))?;
     }
+
+    // set the faces in the core submesh instance
+    pCoreSubmesh.setAllFaces(faces);
+
     // Must be dropped before we return the value it refers to.
     drop(pCoreSubmesh);
 
     Ok(coreSubmesh)
 }
 
+/*****************************************************************************/
+/** Checks the bone IDs of the influences of a core mesh.
+ *
+ * This function reports every influence of a core mesh on a bone that is not
+ * in a skeleton with the given number of bones. Strict loading fails on the
+ * first one, otherwise they are recorded as warnings.
+ *
+ * @param pCoreMesh The core mesh that was loaded.
+ * @param boneCount The number of bones in the skeleton of the mesh.
+ * @param filename The file the core mesh was loaded from.
+ * @param options The options the core mesh was loaded with.
+ *****************************************************************************/
+pub(super) fn checkInfluenceBoneIds(
+    pCoreMesh: &CalCoreMesh,
+    boneCount: usize,
+    filename: &Path,
+    options: &LoaderOptions,
+) -> Result<(), LoaderError> {
+    for (submeshId, pCoreSubmesh) in pCoreMesh.getCoreSubmeshes().iter().enumerate() {
+        for (vertexId, vertex) in pCoreSubmesh.borrow().getVectorVertex().iter().enumerate() {
+            for influence in vertex.vectorInfluence.iter() {
+                // negative IDs are reported while loading
+                if usize::try_from(influence.boneId).is_ok_and(|boneId| boneId >= boneCount) {
+                    options.reportProblemAt(
+                        format!(
+                            "Influence on bone {}, which is not in the skeleton",
+                            influence.boneId
+                        ),
+                        ErrorLocation {
+                            file: Some(filename.to_path_buf()),
+                            asset: Some(AssetKind::Mesh),
+                            offset: None,
+                            elements: vec![
+                                format!("submesh {submeshId}"),
+                                format!("vertex {vertexId}"),
+                            ],
+                            listing: None,
+                        },
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Checks whether the faces of a submesh need their winding flipped.
+ *
+ * This function compares the normal of the first face of a submesh with the
+ * normal of its first vertex to detect if a left-handed coordinate system is
+ * used by the object. The object has one system for all faces, so the result
+ * applies to every face of the submesh.
+ *
+ * @param vectorVertex The vertices of the submesh.
+ * @param vertexId The vertex ids of the first face.
+ * @param flipWinding Whether the loader options ask for reversed winding.
+ *****************************************************************************/
+pub(super) fn faceNeedsFlip(
+    vectorVertex: &[Vertex],
+    vertexId: &[crate::CalIndex; 3],
+    flipWinding: bool,
+) -> Result<bool, LoaderError> {
+    // get vertexes of first face
+    let vertex = |id: crate::CalIndex| {
+        vectorVertex.get(id as usize).ok_or_else(|| {
+            LoaderError::FormatError(format!("Invalid vertex index {id} in mesh face"))
+        })
+    };
+    let v1 = vertex(vertexId[0])?;
+    let v2 = vertex(vertexId[1])?;
+    let v3 = vertex(vertexId[2])?;
+
+    let point1 = CalVector::<f32>::new(v1.position.x, v1.position.y, v1.position.z);
+    let point2 = CalVector::<f32>::new(v2.position.x, v2.position.y, v2.position.z);
+    let point3 = CalVector::<f32>::new(v3.position.x, v3.position.y, v3.position.z);
+
+    // gets vectors (v1-v2) and (v3-v2)
+    let vect1 = point1 - point2;
+    let vect2 = point3 - point2;
+
+    // calculates normal of face
+    let cross = CalVector::cross(vect1, vect2);
+    let crossLength = cross.magnitude();
+    if crossLength == 0.0 {
+        return Err(LoaderError::FormatError(format!("Face normal invalid")));
+    }
+    let faceNormal = cross / crossLength;
+
+    // compare the calculated normal with the normal of a vertex
+    let maxNorm = v1.normal;
+
+    // if the two vectors point to the same direction then the poly needs flipping
+    // so if the dot product > 0 it needs flipping
+    let mut flipModel = faceNormal.dot(maxNorm) > 0.0;
+
+    // flip the winding order if the loading flags request it
+    if flipWinding {
+        flipModel = !flipModel;
+    }
+
+    Ok(flipModel)
+}
+
 //2051
 /*****************************************************************************/
 /** Loads a core track instance.
@@ -1049,6 +2642,7 @@ pub fn loadCoreTrack(
     skeleton: &Rc<RefCell<CalCoreSkeleton>>,
     version: i32,
     use_animation_compression: bool,
+    options: &LoaderOptions,
 ) -> Result<Rc<RefCell<CalCoreTrack>>, LoaderError> {
     // if(!dataSrc.ok())
     // {
@@ -1084,13 +2678,20 @@ pub fn loadCoreTrack(
 
         // Read the number of keyframes.
         keyframe_count = dataSrc.readInteger()?;
-        if keyframe_count <= 0 {
-            return Err(LoaderError::FormatError(format!(
-                "Keyframe count {keyframe_count} is negative"
-            )));
-        }
     }
 
+    // a track needs at least one keyframe to be evaluated
+    if keyframe_count <= 0 {
+        return Err(LoaderError::FormatError(format!(
+            "Keyframe count {keyframe_count} is not positive"
+        )));
+    }
+    checkLimit(
+        "Keyframe count",
+        keyframe_count as usize,
+        options.limits.max_keyframes,
+    )?;
+
     if core_bone_id < 0 {
         return Err(LoaderError::FormatError(format!(
             "Core bone ID {core_bone_id} is negative"
@@ -1104,6 +2705,15 @@ pub fn loadCoreTrack(
     let skel = skeleton.borrow();
 
     let cb = skel.getCoreBone(core_bone_id);
+    if cb.is_none() {
+        options.reportProblem(
+            dataSrc,
+            format!("Track of bone {core_bone_id}, which is not in the skeleton"),
+        )?;
+    }
+
+    // Check for anim rotation
+    let rotatesKeyframes = options.rotatesKeyframes(&cb, core_bone_id)?;
 
     // load all core keyframes
 
@@ -1111,9 +2721,11 @@ pub fn loadCoreTrack(
 
     let mut lastCoreKeyframe: Option<Rc<CalCoreKeyframe>> = None;
     for keyframeId in 0..keyframe_count {
+        options.checkCancelled(keyframeId as usize)?;
+
         // load the core keyframe
 
-        let mut pCoreKeyframe = loadCoreKeyframe(
+        let (pCoreKeyframe, translationFromSkeleton) = readCoreKeyframe(
             dataSrc,
             &cb,
             version,
@@ -1122,33 +2734,19 @@ pub fn loadCoreTrack(
             high_range_required,
             translation_is_dynamic,
             use_animation_compression,
-        )?;
+            options,
+        )
+        .map_err(|e| e.in_element(format!("keyframe {keyframeId}")))?;
+        let pCoreKeyframe = Rc::new(pCoreKeyframe);
 
-        if (loadingMode & LOADER_ROTATE_X_AXIS) == LOADER_ROTATE_X_AXIS {
-            // Check for anim rotation
-            let bone = skel
-                .getCoreBone(core_bone_id)
-                .ok_or(LoaderError::FormatError(format!(
-                    "Invalid bone ID {core_bone_id} in animation"
-                )))?;
-
-            if bone.borrow().getParentId() == -1 {
-                // root bone
-                // rotate root bone quaternion
-                let mut rot = pCoreKeyframe.getRotation().clone();
-                let x_axis_90 = CalQuaternion::new(0.7071067811, 0.7071067811, 0.0, 0.0);
-                rot = rot.mul(&x_axis_90);
-                pCoreKeyframe.setRotation(&rot);
-                // rotate root bone displacement
-                let vec = x_axis_90.mul(pCoreKeyframe.getTranslation());
-                pCoreKeyframe.setTranslation(&vec);
-            }
-        }
+        // later keyframes may copy the translation read for this one
+        lastCoreKeyframe = Some(pCoreKeyframe.clone());
 
-        let p2 = Rc::new(pCoreKeyframe);
-        lastCoreKeyframe = Some(p2.clone());
         // add the core keyframe to the core track instance
-        core_key_frames.push(p2);
+        core_key_frames.push(match rotatesKeyframes {
+            true => options.rotateKeyframe(pCoreKeyframe, translationFromSkeleton),
+            false => pCoreKeyframe,
+        });
     }
     drop(skel);
 
@@ -1159,7 +2757,7 @@ pub fn loadCoreTrack(
     // pCoreTrack.setHighRangeRequired( highRangeRequired );
     // pCoreTrack.setTranslationIsDynamic( translationIsDynamic );
 
-    let pCoreTrack = CalCoreTrack::new(
+    let mut pCoreTrack = CalCoreTrack::new(
         core_bone_id,
         translation_required,
         high_range_required,
@@ -1167,14 +2765,21 @@ pub fn loadCoreTrack(
         core_key_frames,
     );
 
-    if collapseSequencesOn {
-        pCoreTrack.collapseSequences(translationTolerance, rotationToleranceDegrees);
+    if options.collapse_sequences {
+        pCoreTrack.collapseSequences(
+            options.translation_tolerance,
+            options.rotation_tolerance_degrees,
+        );
     }
-    if loadingCompressionOn {
+    if options.compress_tracks {
         // This function MIGHT call setTranslationRequired() on the track.
         // Alas, you may be passing me NULL for skel, in which case compress() won't update the
         // translationRequired flag; instead it will leave it, as above.
-        pCoreTrack.compress(translationTolerance, rotationToleranceDegrees, skeleton);
+        pCoreTrack.compress(
+            options.translation_tolerance,
+            options.rotation_tolerance_degrees,
+            skeleton,
+        );
     }
 
     Ok(Rc::new(RefCell::new(pCoreTrack)))
@@ -1182,6 +2787,614 @@ pub fn loadCoreTrack(
 
 const InvalidCoord: f32 = 1e10;
 
-fn TranslationInvalid(x: f32, y: f32, z: f32) -> bool {
+pub(super) fn TranslationInvalid(x: f32, y: f32, z: f32) -> bool {
     return x == InvalidCoord && y == InvalidCoord && z == InvalidCoord;
 }
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::panic::AssertUnwindSafe;
+
+    use super::*;
+    use crate::core::testdata;
+    use crate::core::{DataSink, Endianness, MemorySource, saver};
+
+    /// Loads a binary asset in the given byte order and saves it again in
+    /// little endian, to compare what was loaded.
+    fn reloadAsset(
+        extension: &str,
+        data: &[u8],
+        endianness: Endianness,
+        skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+    ) -> Vec<u8> {
+        let mut dataSrc = MemorySource::with_endianness(data, endianness);
+        let options = LoaderOptions::default();
+        let little = Endianness::LittleEndian;
+        match extension {
+            "csf" => {
+                let loaded = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+                loadCoreSkeletonFromSource(&mut dataSrc, &loaded, &options).unwrap();
+                testdata::saveToBuffer(little, |s| {
+                    saver::saveCoreSkeletonToSink(s, &loaded.borrow())
+                })
+            }
+            "caf" => {
+                let loaded = loadCoreAnimationFromSource(&mut dataSrc, skeleton, &options).unwrap();
+                testdata::saveToBuffer(little, |s| {
+                    saver::saveCoreAnimationToSink(s, &loaded.borrow())
+                })
+            }
+            "cmf" => {
+                let loaded = loadCoreMeshFromSource(&mut dataSrc, &options).unwrap();
+                testdata::saveToBuffer(little, |s| saver::saveCoreMeshToSink(s, &loaded))
+            }
+            "crf" => {
+                let loaded = loadCoreMaterialFromSource(&mut dataSrc, &options).unwrap();
+                testdata::saveToBuffer(little, |s| saver::saveCoreMaterialToSink(s, &loaded))
+            }
+            "cpf" => {
+                let loaded = loadCoreAnimatedMorphFromSource(&mut dataSrc, &options).unwrap();
+                testdata::saveAnimatedMorphToBuffer(little, &loaded.borrow())
+            }
+            _ => unreachable!("unknown asset type {extension}"),
+        }
+    }
+
+    #[test]
+    fn big_endian_assets_load_like_little_endian_ones() {
+        let skeleton = testdata::skeleton();
+        let littleFiles = testdata::binaryFiles(Endianness::LittleEndian);
+        let bigFiles = testdata::binaryFiles(Endianness::BigEndian);
+        assert_eq!(littleFiles.len(), bigFiles.len());
+
+        for ((extension, little), (_, big)) in littleFiles.iter().zip(&bigFiles) {
+            assert_ne!(little, big, "{extension} is saved in the same byte order");
+            let fromLittle = reloadAsset(extension, little, Endianness::LittleEndian, &skeleton);
+            let fromBig = reloadAsset(extension, big, Endianness::BigEndian, &skeleton);
+            assert_eq!(fromLittle, fromBig, "{extension} loads differently");
+        }
+    }
+
+    #[test]
+    fn readers_load_like_memory_sources() {
+        let skeleton = testdata::skeleton();
+        let options = LoaderOptions::default();
+        let little = Endianness::LittleEndian;
+        for (extension, data) in testdata::binaryFiles(little) {
+            let fromReader = match extension {
+                "csf" => {
+                    let loaded = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+                    loadCoreSkeletonFromReader(&data[..], &loaded, &options).unwrap();
+                    testdata::saveToBuffer(little, |s| {
+                        saver::saveCoreSkeletonToSink(s, &loaded.borrow())
+                    })
+                }
+                "caf" => {
+                    let loaded =
+                        loadCoreAnimationFromReader(&data[..], &skeleton, &options).unwrap();
+                    testdata::saveToBuffer(little, |s| {
+                        saver::saveCoreAnimationToSink(s, &loaded.borrow())
+                    })
+                }
+                "cmf" => {
+                    let loaded = loadCoreMeshFromReader(&data[..], &options).unwrap();
+                    testdata::saveToBuffer(little, |s| {
+                        saver::saveCoreMeshToSink(s, &loaded.borrow())
+                    })
+                }
+                "crf" => {
+                    let loaded = loadCoreMaterialFromReader(&data[..], &options).unwrap();
+                    testdata::saveToBuffer(little, |s| saver::saveCoreMaterialToSink(s, &loaded))
+                }
+                "cpf" => {
+                    let loaded = loadCoreAnimatedMorphFromReader(&data[..], &options).unwrap();
+                    testdata::saveAnimatedMorphToBuffer(little, &loaded.borrow())
+                }
+                _ => unreachable!("unknown asset type {extension}"),
+            };
+            let fromSource = reloadAsset(extension, &data, little, &skeleton);
+            assert_eq!(fromReader, fromSource, "{extension} loads differently");
+        }
+    }
+
+    #[test]
+    fn loader_options_convert_the_assets() {
+        let near = |a: CalVector<f32>, b: CalVector<f32>| (a - b).magnitude() < 1e-5;
+        let options = LoaderOptions {
+            rotate_x_axis: true,
+            invert_v_coord: true,
+            flip_winding: true,
+            ..Default::default()
+        };
+
+        // the root bone is turned from Z up to Y up, its child is left alone
+        let skeleton = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+        loadCoreSkeletonFromReader(testdata::SKELETON_XML.as_bytes(), &skeleton, &options).unwrap();
+        let root = skeleton.borrow().getCoreBone(0).unwrap();
+        assert!(near(
+            *root.borrow().getTranslation(),
+            CalVector::new(1.0, 3.0, -2.0)
+        ));
+        let child = skeleton.borrow().getCoreBone(1).unwrap();
+        assert_eq!(
+            *child.borrow().getTranslation(),
+            CalVector::new(0.0, 1.0, 0.0)
+        );
+
+        // a translation read from the file is rotated even if it happens to
+        // match the rotated skeleton, one taken from the skeleton is not
+        let animation = loadCoreAnimationFromReader(
+            r#"<HEADER MAGIC="XAF" VERSION="1400" />
+            <ANIMATION DURATION="1" NUMTRACKS="1">
+                <TRACK BONEID="0" NUMKEYFRAMES="2" TRANSLATIONREQUIRED="1" TRANSLATIONISDYNAMIC="1">
+                    <KEYFRAME TIME="0">
+                        <TRANSLATION>1 3 -2</TRANSLATION>
+                        <ROTATION>0 0 0 1</ROTATION>
+                    </KEYFRAME>
+                    <KEYFRAME TIME="1">
+                        <ROTATION>0 0 0 1</ROTATION>
+                    </KEYFRAME>
+                </TRACK>
+            </ANIMATION>"#
+                .as_bytes(),
+            &skeleton,
+            &options,
+        )
+        .unwrap();
+        let animation = animation.borrow();
+        let track = animation.getListCoreTrack()[0].borrow();
+        let translation = |keyframeId| *track.getCoreKeyframe(keyframeId).unwrap().getTranslation();
+        assert!(near(translation(0), CalVector::new(1.0, -2.0, -3.0)));
+        assert!(near(translation(1), CalVector::new(1.0, 3.0, -2.0)));
+
+        let data = testdata::saveToBuffer(Endianness::LittleEndian, |s| {
+            saver::saveCoreMeshToSink(s, &testdata::mesh().borrow())
+        });
+        let binary = loadCoreMeshFromSource(&mut MemorySource::new(data), &options).unwrap();
+        let xml = loadCoreMeshFromReader(testdata::MESH_XML.as_bytes(), &options).unwrap();
+        let xml = xml.borrow();
+        for mesh in [&binary, &*xml] {
+            let submesh = mesh.getCoreSubmeshes()[0].borrow();
+            let position = submesh.getVectorVertex()[2].position;
+            assert!(near(position, CalVector::new(0.0, 0.0, -1.0)));
+            let uv = &submesh.getVectorVectorTextureCoordinate()[0][1];
+            assert_eq!((uv.u, uv.v), (1.0, 1.0));
+            assert_eq!(submesh.getVectorFace()[0].vertexId, [0, 2, 1]);
+        }
+    }
+
+    #[test]
+    fn slave_attributes_over_the_limit_are_rejected() {
+        let mut options = LoaderOptions::default();
+        options.limits.max_slave_attributes = 0;
+        let data = testdata::saveToBuffer(Endianness::LittleEndian, |s| {
+            saver::saveCoreMeshToSink(s, &testdata::mesh().borrow())
+        });
+        let binaryResult = loadCoreMeshFromSource(&mut MemorySource::new(data), &options);
+        let xmlResult = loadCoreMeshFromReader(testdata::MESH_XML.as_bytes(), &options);
+        for result in [binaryResult.map(drop), xmlResult.map(drop)] {
+            let error = result.err().unwrap().kind().to_string();
+            assert!(error.contains("Slave attribute count"), "{error}");
+        }
+    }
+
+    #[test]
+    fn bytes_after_the_data_are_reported_by_the_reader_loaders() {
+        let mut data = testdata::saveToBuffer(Endianness::LittleEndian, |s| {
+            saver::saveCoreMaterialToSink(s, &testdata::material())
+        });
+        data.extend_from_slice(&[0; 3]);
+
+        let options = LoaderOptions::default();
+        loadCoreMaterialFromReader(data.as_slice(), &options).unwrap();
+        let warnings = options.warnings.borrow();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("3 bytes left"));
+
+        let strict = LoaderOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(loadCoreMaterialFromReader(data.as_slice(), &strict).is_err());
+    }
+
+    #[test]
+    fn xml_values_that_are_not_finite_are_reported() {
+        let strict = LoaderOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let material = testdata::MATERIAL_XML.replace("<SHININESS>0.5", "<SHININESS>inf");
+        assert!(loadCoreMaterialFromReader(material.as_bytes(), &strict).is_err());
+        let animatedMorph = testdata::ANIMATED_MORPH_XML.replace("<WEIGHT>1<", "<WEIGHT>NaN<");
+        assert!(loadCoreAnimatedMorphFromReader(animatedMorph.as_bytes(), &strict).is_err());
+
+        let options = LoaderOptions::default();
+        loadCoreAnimatedMorphFromReader(animatedMorph.as_bytes(), &options).unwrap();
+        assert_eq!(options.warnings.borrow().len(), 2);
+    }
+
+    /// A compressed animation of one track, laid out field by field after the
+    /// reference Cal3D format rather than produced by the saver, so that the
+    /// reader and the writer cannot agree on a wrong layout.
+    #[rustfmt::skip]
+    const COMPRESSED_ANIMATION: &[u8] = &[
+        b'C', b'A', b'F', 0, // magic
+        0x78, 0x05, 0, 0, // version 1400
+        1, 0, 0, 0, // compressed
+        0, 0, 0x80, 0x3f, // duration 1
+        1, 0, 0, 0, // one track
+        0, 0, 0, 0, // flags
+        // bone 1, translation required and dynamic, 2 keyframes
+        0x01, 0xa0, 0x02, 0x00,
+        // keyframe 0: translation (256, -512, 1152) / 512 in 21 bits each
+        0x00, 0x01, 0x00, 0xc0, 0xff, 0x03, 0x12, 0x00,
+        // rotation with w largest (index 3), x, y, z at the middle 16384
+        0x03, 0x00, 0x01, 0x80, 0x00, 0x40,
+        0x00, 0x00, // frame 0
+        // keyframe 1: no translation
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        // rotation with w largest, x and y at 16384, z at 27969 (about 0.5)
+        0x03, 0x00, 0x01, 0x80, 0x41, 0x6d,
+        0x0f, 0x00, // frame 15
+    ];
+
+    #[test]
+    fn compressed_animations_decode_the_reference_layout() {
+        let skeleton = testdata::skeleton();
+        let animation = loadCoreAnimationFromSource(
+            &mut MemorySource::new(COMPRESSED_ANIMATION),
+            &skeleton,
+            &LoaderOptions::default(),
+        )
+        .unwrap();
+
+        {
+            let animation = animation.borrow();
+            assert_eq!(animation.getDuration(), 1.0);
+            let track = animation.getListCoreTrack()[0].borrow();
+            assert_eq!(track.getCoreBoneId(), 1);
+            assert!(track.getTranslationRequired());
+            assert!(!track.getHighRangeRequired());
+            assert!(track.getTranslationIsDynamic());
+
+            let expected = [
+                (
+                    0.0,
+                    CalVector::new(0.5, -1.0, 2.25),
+                    CalQuaternion::new(1.0, 0.0, 0.0, 0.0),
+                ),
+                (
+                    0.5,
+                    CalVector::new(0.0, 0.0, 0.0),
+                    CalQuaternion::new(0.866, 0.0, 0.0, 0.5),
+                ),
+            ];
+            assert_eq!(track.getCoreKeyframeCount(), expected.len());
+            for (keyframeId, (time, translation, rotation)) in expected.iter().enumerate() {
+                let keyframe = track.getCoreKeyframe(keyframeId).unwrap();
+                assert!((keyframe.getTime() - time).abs() < 1e-6);
+                assert_eq!(keyframe.getTranslation(), translation);
+                assert!((keyframe.getRotation() - rotation).magnitude() < 1e-3);
+            }
+        }
+
+        // the saver writes the same bytes back
+        let saved = testdata::saveToBuffer(Endianness::LittleEndian, |s| {
+            saver::saveCompressedCoreAnimationToSink(s, &animation.borrow())
+        });
+        assert_eq!(saved, COMPRESSED_ANIMATION);
+    }
+
+    #[test]
+    fn assets_in_the_wrong_byte_order_are_rejected() {
+        let skeleton = testdata::skeleton();
+        let options = LoaderOptions::default();
+        for (extension, big) in testdata::binaryFiles(Endianness::BigEndian) {
+            let result = loadAsset(extension, &big, &skeleton, &options);
+            assert!(result.is_err(), "{extension} loads in the wrong byte order");
+        }
+    }
+
+    /// Loads a little endian binary asset, or an XML one from a reader, into
+    /// a new skeleton for skeletons.
+    fn loadAsset(
+        extension: &str,
+        data: &[u8],
+        skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+        options: &LoaderOptions,
+    ) -> Result<(), LoaderError> {
+        let mut dataSrc = MemorySource::new(data);
+        match extension {
+            "csf" => {
+                let skeleton = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+                loadCoreSkeletonFromSource(&mut dataSrc, &skeleton, options)
+            }
+            "caf" => loadCoreAnimationFromSource(&mut dataSrc, skeleton, options).map(drop),
+            "cmf" => loadCoreMeshFromSource(&mut dataSrc, options).map(drop),
+            "crf" => loadCoreMaterialFromSource(&mut dataSrc, options).map(drop),
+            "cpf" => loadCoreAnimatedMorphFromSource(&mut dataSrc, options).map(drop),
+            "xsf" => {
+                let skeleton = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+                loadCoreSkeletonFromReader(data, &skeleton, options)
+            }
+            "xaf" => loadCoreAnimationFromReader(data, skeleton, options).map(drop),
+            "xmf" => loadCoreMeshFromReader(data, options).map(drop),
+            "xpf" => loadCoreAnimatedMorphFromReader(data, options).map(drop),
+            _ => unreachable!("unknown asset type {extension}"),
+        }
+    }
+
+    /// Records the largest allocation made by each thread, so that loading a
+    /// damaged file can be checked not to allocate for counts it never had.
+    struct LargestAllocationRecorder;
+
+    thread_local! {
+        static LARGEST_ALLOCATION: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn recordAllocation(size: usize) {
+        // the thread may be exiting, with nothing left to record
+        let _ = LARGEST_ALLOCATION.try_with(|largest| largest.set(largest.get().max(size)));
+    }
+
+    unsafe impl GlobalAlloc for LargestAllocationRecorder {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            recordAllocation(layout.size());
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            recordAllocation(new_size);
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: LargestAllocationRecorder = LargestAllocationRecorder;
+
+    /// Loads a damaged asset, failing the test with a description of the
+    /// damage if the loader panics or allocates more than the limits allow.
+    fn loadDamagedAsset(
+        extension: &str,
+        data: &[u8],
+        damage: &str,
+        skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+    ) -> Result<(), LoaderError> {
+        // every limit is far above what the sample assets need, and bounds
+        // the allocations of a loader well under a megabyte
+        let options = LoaderOptions {
+            limits: LoadLimits {
+                max_vertices: 256,
+                max_faces: 256,
+                max_springs: 256,
+                max_morph_targets: 16,
+                max_submeshes: 16,
+                max_bones: 16,
+                max_tracks: 16,
+                max_keyframes: 256,
+                max_texture_coordinate_sets: 4,
+                max_slave_attributes: 4,
+                max_slave_attribute_components: 4,
+                max_submesh_values: 4096,
+                max_string_length: 256,
+            },
+            ..Default::default()
+        };
+        let maxAllocation = 1 << 20;
+
+        LARGEST_ALLOCATION.with(|largest| largest.set(0));
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            loadAsset(extension, data, skeleton, &options)
+        }));
+        let largestAllocation = LARGEST_ALLOCATION.with(Cell::get);
+
+        let Ok(result) = result else {
+            panic!("{extension} {damage} panics");
+        };
+        assert!(
+            largestAllocation <= maxAllocation,
+            "{extension} {damage} allocates {largestAllocation} bytes"
+        );
+        result
+    }
+
+    #[test]
+    fn truncated_assets_are_rejected() {
+        let skeleton = testdata::skeleton();
+        for (extension, data) in testdata::binaryFiles(Endianness::LittleEndian) {
+            loadAsset(extension, &data, &skeleton, &LoaderOptions::default()).unwrap();
+            for length in 0..data.len() {
+                let damage = format!("truncated to {length} bytes");
+                let result = loadDamagedAsset(extension, &data[..length], &damage, &skeleton);
+                assert!(result.is_err(), "{extension} {damage} loads");
+            }
+        }
+    }
+
+    #[test]
+    fn bit_flipped_assets_do_not_panic() {
+        let skeleton = testdata::skeleton();
+        for (extension, data) in testdata::binaryFiles(Endianness::LittleEndian) {
+            for bit in 0..data.len() * 8 {
+                let mut damaged = data.clone();
+                damaged[bit / 8] ^= 1 << (bit % 8);
+                let damage = format!("with bit {} of byte {} flipped", bit % 8, bit / 8);
+                let result = loadDamagedAsset(extension, &damaged, &damage, &skeleton);
+                // a flipped value may still be valid, but not a flipped magic
+                if bit < 32 {
+                    assert!(result.is_err(), "{extension} {damage} loads");
+                }
+            }
+        }
+    }
+
+    /// Saves a binary mesh of one submesh claiming the given counts, with
+    /// nothing after the names and sizes of its slave attributes.
+    fn claimedSubmesh(
+        vertexCount: i32,
+        textureCoordinateCount: i32,
+        morphCount: i32,
+        slaveComponentCounts: &[i32],
+    ) -> Vec<u8> {
+        testdata::saveToBuffer(Endianness::LittleEndian, |s| {
+            s.writeBytes(MESH_FILE_MAGIC)?;
+            s.writeInteger(CURRENT_FILE_VERSION)?;
+            s.writeInteger(1)?;
+            s.writeInteger(0)?;
+            s.writeInteger(vertexCount)?;
+            s.writeInteger(0)?;
+            s.writeInteger(0)?;
+            s.writeInteger(0)?;
+            s.writeInteger(textureCoordinateCount)?;
+            s.writeInteger(morphCount)?;
+            s.writeInteger(slaveComponentCounts.len() as i32)?;
+            for (slaveAttributeId, componentCount) in slaveComponentCounts.iter().enumerate() {
+                s.writeString(&format!("slave{slaveAttributeId}"))?;
+                s.writeInteger(*componentCount)?;
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn submeshes_only_allocate_for_what_they_hold() {
+        let xmlSubmesh = |vertexCount: usize, textureCoordinateCount: usize| {
+            format!(
+                r#"<HEADER MAGIC="XMF" VERSION="1400" />
+<MESH NUMSUBMESH="1">
+    <SUBMESH NUMVERTICES="{vertexCount}" NUMFACES="0" NUMLODSTEPS="0" NUMSPRINGS="0" NUMMORPHS="0" NUMTEXCOORDS="{textureCoordinateCount}" MATERIAL="0" />
+</MESH>"#
+            )
+        };
+        let cases = [
+            ("vertices", claimedSubmesh(1 << 20, 0, 0, &[])),
+            ("texture coordinates", claimedSubmesh(1 << 20, 32, 0, &[])),
+            ("morph targets", claimedSubmesh(1 << 12, 0, 1 << 12, &[])),
+            ("slave attributes", claimedSubmesh(1 << 20, 1, 0, &[64; 64])),
+            ("XML vertices", xmlSubmesh(1 << 20, 0).into_bytes()),
+            (
+                "XML texture coordinates",
+                xmlSubmesh(1 << 20, 32).into_bytes(),
+            ),
+        ];
+
+        // the default limits hold the allocations of each to the size of the
+        // data it has, which here is nothing
+        let options = LoaderOptions::default();
+        for (claim, data) in cases {
+            LARGEST_ALLOCATION.with(|largest| largest.set(0));
+            let result = loadCoreMeshFromReader(data.as_slice(), &options);
+            let largestAllocation = LARGEST_ALLOCATION.with(Cell::get);
+
+            assert!(result.is_err(), "a mesh claiming {claim} loads");
+            assert!(
+                largestAllocation <= 1 << 16,
+                "a mesh claiming {claim} allocates {largestAllocation} bytes"
+            );
+        }
+    }
+
+    /// Returns the little endian binary and the XML test assets that report
+    /// progress, with the stage they report.
+    fn assetsWithProgress() -> Vec<(&'static str, Vec<u8>, LoadStage)> {
+        let xmlFiles = [
+            ("xsf", testdata::SKELETON_XML, LoadStage::Bones),
+            ("xaf", testdata::ANIMATION_XML, LoadStage::Tracks),
+            ("xmf", testdata::MESH_XML, LoadStage::Submeshes),
+            ("xpf", testdata::ANIMATED_MORPH_XML, LoadStage::Tracks),
+        ];
+        let mut assets = Vec::new();
+        for (extension, text, stage) in xmlFiles {
+            assets.push((extension, text.as_bytes().to_vec(), stage));
+        }
+        for (extension, data) in testdata::binaryFiles(Endianness::LittleEndian) {
+            let stage = match extension {
+                "csf" => LoadStage::Bones,
+                "caf" | "cpf" => LoadStage::Tracks,
+                "cmf" => LoadStage::Submeshes,
+                _ => continue,
+            };
+            assets.push((extension, data, stage));
+        }
+        assets
+    }
+
+    #[test]
+    fn progress_is_reported_for_binary_and_xml_assets() {
+        let skeleton = testdata::skeleton();
+        for (extension, data, stage) in assetsWithProgress() {
+            let reports = Rc::new(RefCell::new(Vec::new()));
+            let recorded = reports.clone();
+            let options = LoaderOptions {
+                progress: Some(ProgressCallback::new(move |progress| {
+                    recorded.borrow_mut().push(*progress);
+                })),
+                ..Default::default()
+            };
+            loadAsset(extension, &data, &skeleton, &options).unwrap();
+
+            let reports = reports.take();
+            let count = reports.len() - 1;
+            for (index, progress) in reports.iter().enumerate() {
+                assert_eq!(progress.stage, stage, "{extension}");
+                assert_eq!(
+                    (progress.index, progress.count),
+                    (index, count),
+                    "{extension}"
+                );
+            }
+            assert!(
+                reports.is_sorted_by_key(|progress| progress.bytes_read),
+                "{extension}"
+            );
+            assert_eq!(
+                reports[count].bytes_read,
+                Some(data.len() as u64),
+                "{extension}"
+            );
+        }
+    }
+
+    #[test]
+    fn cancelling_stops_at_the_next_vertex_or_keyframe() {
+        let skeleton = testdata::skeleton();
+        for (extension, data, stage) in assetsWithProgress() {
+            // the bones are only checked between bones
+            if stage == LoadStage::Bones {
+                continue;
+            }
+
+            let cancellation = CancellationToken::new();
+            let token = cancellation.clone();
+            let options = LoaderOptions {
+                progress: Some(ProgressCallback::new(move |_| token.cancel())),
+                cancellation,
+                ..Default::default()
+            };
+            let error = loadAsset(extension, &data, &skeleton, &options).unwrap_err();
+            assert!(
+                matches!(error.kind(), LoaderError::Cancelled),
+                "{extension}"
+            );
+
+            // cancelled before the first submesh or track is done with
+            let element = match stage {
+                LoadStage::Submeshes => "submesh 0",
+                _ => "track 0",
+            };
+            assert_eq!(error.location().unwrap().elements, [element], "{extension}");
+        }
+
+        let options = LoaderOptions::default();
+        options.cancellation.cancel();
+        assert!(options.checkCancelled(CANCELLATION_STRIDE - 1).is_ok());
+        assert!(options.checkCancelled(CANCELLATION_STRIDE).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Color {
     red: u8,
     green: u8,
@@ -7,7 +7,7 @@ pub struct Color {
 }
 
 impl Color {
-    pub(super) fn new(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+    pub fn new(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
         Color {
             red,
             green,
@@ -15,9 +15,25 @@ impl Color {
             alpha,
         }
     }
+
+    pub fn red(&self) -> u8 {
+        self.red
+    }
+
+    pub fn green(&self) -> u8 {
+        self.green
+    }
+
+    pub fn blue(&self) -> u8 {
+        self.blue
+    }
+
+    pub fn alpha(&self) -> u8 {
+        self.alpha
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct Map {
     pub strFilename: String,
     pub mapType: String,
@@ -25,7 +41,7 @@ pub struct Map {
 }
 
 impl Map {
-    pub(super) fn new(strFilename: String, mapType: String, userData: i32) -> Self {
+    pub fn new(strFilename: String, mapType: String, userData: i32) -> Self {
         Map {
             strFilename,
             mapType,
@@ -34,7 +50,7 @@ impl Map {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct CalCoreMaterial {
     m_ambientColor: Color,
     m_diffuseColor: Color,
@@ -42,7 +58,7 @@ pub struct CalCoreMaterial {
     m_shininess: f32,
     m_vectorMap: Vec<Map>,
     // Cal::UserData    m_userData;
-    // std::string      m_name;
+    m_name: String,
     // std::string      m_filename;
 }
 
@@ -60,9 +76,79 @@ impl CalCoreMaterial {
             m_specularColor,
             m_shininess,
             m_vectorMap,
+            m_name: String::new(),
         }
     }
 
+    /// Returns the material with a different ambient color, for building a
+    /// material up from `CalCoreMaterial::default()`.
+    pub fn with_ambient_color(mut self, ambientColor: Color) -> Self {
+        self.m_ambientColor = ambientColor;
+        self
+    }
+
+    /// Returns the material with a different diffuse color.
+    pub fn with_diffuse_color(mut self, diffuseColor: Color) -> Self {
+        self.m_diffuseColor = diffuseColor;
+        self
+    }
+
+    /// Returns the material with a different specular color.
+    pub fn with_specular_color(mut self, specularColor: Color) -> Self {
+        self.m_specularColor = specularColor;
+        self
+    }
+
+    /// Returns the material with a different shininess.
+    pub fn with_shininess(mut self, shininess: f32) -> Self {
+        self.m_shininess = shininess;
+        self
+    }
+
+    /// Returns the material with a map added after its other maps.
+    pub fn with_map(mut self, map: Map) -> Self {
+        self.m_vectorMap.push(map);
+        self
+    }
+
+    /// Returns the material with a different name.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.m_name = name.into();
+        self
+    }
+
+    pub fn getAmbientColor(&self) -> &Color {
+        &self.m_ambientColor
+    }
+
+    pub fn setAmbientColor(&mut self, ambientColor: Color) {
+        self.m_ambientColor = ambientColor;
+    }
+
+    pub fn getDiffuseColor(&self) -> &Color {
+        &self.m_diffuseColor
+    }
+
+    pub fn setDiffuseColor(&mut self, diffuseColor: Color) {
+        self.m_diffuseColor = diffuseColor;
+    }
+
+    pub fn getSpecularColor(&self) -> &Color {
+        &self.m_specularColor
+    }
+
+    pub fn setSpecularColor(&mut self, specularColor: Color) {
+        self.m_specularColor = specularColor;
+    }
+
+    pub fn getShininess(&self) -> f32 {
+        self.m_shininess
+    }
+
+    pub fn setShininess(&mut self, shininess: f32) {
+        self.m_shininess = shininess;
+    }
+
     pub fn getMaps(&self) -> &Vec<Map> {
         &self.m_vectorMap
     }
@@ -70,4 +156,92 @@ impl CalCoreMaterial {
     pub fn getMapsMut(&mut self) -> &mut Vec<Map> {
         &mut self.m_vectorMap
     }
+
+    pub fn getMapCount(&self) -> usize {
+        self.m_vectorMap.len()
+    }
+
+    //158 cpp
+    /*****************************************************************************/
+    /** Returns a specified map texture filename.
+     *
+     * This function returns the texture filename for a specified map ID of the
+     * core material instance.
+     *
+     * @param mapId The ID of the map.
+     *
+     * @return One of the following values:
+     *         \li the filename of the map texture
+     *         \li \b None if an error happened
+     *****************************************************************************/
+    pub fn getMapFilename(&self, mapId: usize) -> Option<&str> {
+        self.m_vectorMap
+            .get(mapId)
+            .map(|map| map.strFilename.as_str())
+    }
+
+    /*****************************************************************************/
+    /** Returns a specified map type.
+     *
+     * This function returns the type, such as "Diffuse Color", of a specified
+     * map ID of the core material instance.
+     *
+     * @param mapId The ID of the map.
+     *
+     * @return One of the following values:
+     *         \li the type of the map
+     *         \li \b None if an error happened
+     *****************************************************************************/
+    pub fn getMapType(&self, mapId: usize) -> Option<&str> {
+        self.m_vectorMap.get(mapId).map(|map| map.mapType.as_str())
+    }
+
+    //200 cpp
+    /*****************************************************************************/
+    /** Provides access to the user data of a specified map.
+     *
+     * This function returns the user data stored in the specified map of the
+     * core material instance.
+     *
+     * @param mapId The ID of the map.
+     *
+     * @return One of the following values:
+     *         \li the user data stored in the specified map
+     *         \li \b None if an error happened
+     *****************************************************************************/
+    pub fn getMapUserData(&self, mapId: usize) -> Option<i32> {
+        self.m_vectorMap.get(mapId).map(|map| map.userData)
+    }
+
+    //298 cpp
+    /*****************************************************************************/
+    /** Stores user data.
+     *
+     * This function stores user data in a specified map of the core material
+     * instance.
+     *
+     * @param mapId The ID of the map.
+     * @param userData The user data that should be stored.
+     *
+     * @return One of the following values:
+     *         \li \b true if successful
+     *         \li \b false if an error happened
+     *****************************************************************************/
+    pub fn setMapUserData(&mut self, mapId: usize, userData: i32) -> bool {
+        match self.m_vectorMap.get_mut(mapId) {
+            Some(map) => {
+                map.userData = userData;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn getName(&self) -> &str {
+        &self.m_name
+    }
+
+    pub fn setName(&mut self, name: String) {
+        self.m_name = name;
+    }
 }
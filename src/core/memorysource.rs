@@ -0,0 +1,170 @@
+use std::borrow::Cow;
+use std::io;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use super::datasource::{DataSource, Endianness, SourceError};
+
+/// A data source reading from a buffer already in memory, either borrowed as
+/// a `&[u8]` or owned as a `Vec<u8>`.
+pub struct MemorySource<'a> {
+    buffer: Cow<'a, [u8]>,
+    position: usize,
+    endianness: Endianness,
+}
+
+impl<'a> MemorySource<'a> {
+    pub fn new(buffer: impl Into<Cow<'a, [u8]>>) -> Self {
+        Self::with_endianness(buffer, Endianness::LittleEndian)
+    }
+
+    pub fn with_endianness(buffer: impl Into<Cow<'a, [u8]>>, endianness: Endianness) -> Self {
+        MemorySource {
+            buffer: buffer.into(),
+            position: 0,
+            endianness,
+        }
+    }
+
+    /// Returns the number of bytes that have not been read yet.
+    pub fn remaining(&self) -> usize {
+        self.buffer.len() - self.position
+    }
+
+    fn take(&mut self, length: usize) -> Result<&[u8], SourceError> {
+        if length > self.remaining() {
+            return Err(SourceError::IoError(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "expected {length} bytes at offset {} but only {} left",
+                    self.position,
+                    self.remaining()
+                ),
+            )));
+        }
+        let bytes = &self.buffer[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+}
+
+impl<'a> From<&'a [u8]> for MemorySource<'a> {
+    fn from(buffer: &'a [u8]) -> Self {
+        MemorySource::new(buffer)
+    }
+}
+
+impl From<Vec<u8>> for MemorySource<'static> {
+    fn from(buffer: Vec<u8>) -> Self {
+        MemorySource::new(buffer)
+    }
+}
+
+impl DataSource for MemorySource<'_> {
+    fn ok(&self) -> bool {
+        true
+    }
+
+    fn setError(&mut self) {}
+
+    fn readBytes(&mut self, pBuffer: &mut [u8], length: usize) -> Result<(), SourceError> {
+        let bytes = self.take(length)?;
+        pBuffer[..length].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn readByte(&mut self) -> Result<u8, SourceError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn readFloat(&mut self) -> Result<f32, SourceError> {
+        let endianness = self.endianness;
+        let bytes = self.take(4)?;
+        Ok(match endianness {
+            Endianness::LittleEndian => LittleEndian::read_f32(bytes),
+            Endianness::BigEndian => BigEndian::read_f32(bytes),
+        })
+    }
+
+    fn readShort(&mut self) -> Result<i16, SourceError> {
+        let endianness = self.endianness;
+        let bytes = self.take(2)?;
+        Ok(match endianness {
+            Endianness::LittleEndian => LittleEndian::read_i16(bytes),
+            Endianness::BigEndian => BigEndian::read_i16(bytes),
+        })
+    }
+
+    fn readInteger(&mut self) -> Result<i32, SourceError> {
+        let endianness = self.endianness;
+        let bytes = self.take(4)?;
+        Ok(match endianness {
+            Endianness::LittleEndian => LittleEndian::read_i32(bytes),
+            Endianness::BigEndian => BigEndian::read_i32(bytes),
+        })
+    }
+
+    fn readStringWithMaxLength(&mut self, maxLength: usize) -> Result<String, SourceError> {
+        let length = self.readInteger()?;
+        if length <= 0 || length as usize > maxLength {
+            return Err(SourceError::FormatError(format!(
+                "string length {length} fails sanity check {maxLength}"
+            )));
+        }
+
+        let mut buf = self.take(length as usize)?.to_vec();
+
+        // strings are stored with a trailing null
+        while buf.last() == Some(&0) {
+            buf.pop();
+        }
+
+        Ok(String::from_utf8(buf)?)
+    }
+
+    fn position(&self) -> Option<u64> {
+        Some(self.position as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_read_in_the_byte_order_given() {
+        let data = [
+            0x01, // byte
+            0x02, 0x03, // short
+            0x04, 0x05, 0x06, 0x07, // integer
+            0x00, 0x00, 0x80, 0x3f, // float, 1 in little-endian
+        ];
+
+        let mut little = MemorySource::new(&data[..]);
+        assert_eq!(little.readByte().unwrap(), 0x01);
+        assert_eq!(little.readShort().unwrap(), 0x0302);
+        assert_eq!(little.readInteger().unwrap(), 0x07060504);
+        assert_eq!(little.readFloat().unwrap(), 1.0);
+        assert_eq!(little.remaining(), 0);
+
+        let mut big = MemorySource::with_endianness(&data[..], Endianness::BigEndian);
+        assert_eq!(big.readByte().unwrap(), 0x01);
+        assert_eq!(big.readShort().unwrap(), 0x0203);
+        assert_eq!(big.readInteger().unwrap(), 0x04050607);
+        assert_eq!(big.readFloat().unwrap().to_bits(), 0x0000803f);
+    }
+
+    #[test]
+    fn reading_past_the_end_fails_without_consuming() {
+        let mut source = MemorySource::new(vec![5, 0, 0, 0, b'a', b'b', 0]);
+        assert_eq!(source.position(), Some(0));
+        let error = source.readString().unwrap_err().to_string();
+        assert!(error.contains("expected 5 bytes at offset 4"), "{error}");
+        assert_eq!(source.remaining(), 3);
+
+        let mut source = MemorySource::new(vec![3, 0, 0, 0, b'a', b'b', 0]);
+        assert_eq!(source.readString().unwrap(), "ab");
+        assert_eq!(source.position(), Some(7));
+        assert!(source.readByte().is_err());
+    }
+}
@@ -19,4 +19,12 @@ impl CalCoreMesh {
     pub fn getCoreSubmeshes(&self) -> &Vec<Rc<RefCell<CalCoreSubmesh>>> {
         &self.m_vectorCoreSubmesh
     }
+
+    pub fn getName(&self) -> &str {
+        &self.m_name
+    }
+
+    pub fn setName(&mut self, name: String) {
+        self.m_name = name;
+    }
 }
@@ -1,23 +1,48 @@
+mod animatedmorph;
 mod animation;
 mod bone;
 mod bufreadersource;
+pub mod bvh;
+pub mod cfg;
+mod datasink;
 mod datasource;
+pub mod gltf;
+mod json;
 mod keyframe;
 mod loader;
 mod material;
+mod memorysource;
 mod mesh;
 mod model;
+mod morphkeyframe;
+mod morphtrack;
+mod saver;
 mod skeleton;
 mod submesh;
 mod submorphtarget;
+#[cfg(test)]
+pub(crate) mod testdata;
 mod track;
+pub mod vfs;
+mod writersink;
 mod xmlformat;
 
+pub use animatedmorph::CalCoreAnimatedMorph;
 pub use animation::*;
 pub use bone::*;
+pub use bufreadersource::BufReaderSource;
+pub use datasink::{DataSink, SinkError};
+pub use datasource::{DataSource, Endianness, SourceError};
 pub use keyframe::CalCoreKeyframe;
 pub use loader::*;
+pub use material::{CalCoreMaterial, Color, Map};
+pub use memorysource::MemorySource;
 pub use mesh::CalCoreMesh;
 pub use model::*;
+pub use morphkeyframe::CalCoreMorphKeyframe;
+pub use morphtrack::CalCoreMorphTrack;
+pub use saver::*;
 pub use skeleton::CalCoreSkeleton;
-pub use submesh::CalCoreSubmesh;
+pub use submesh::{CalCoreSubmesh, SlaveAttribute};
+pub use track::CalCoreTrack;
+pub use writersink::WriterSink;
@@ -1,13 +1,17 @@
 use std::cell::RefCell;
-use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::path::Path;
 use std::rc::Rc;
 
+use super::animatedmorph::CalCoreAnimatedMorph;
 use super::animation::CalCoreAnimation;
+use super::cfg::{self, ModelDescription};
 use super::loader;
 use super::material::CalCoreMaterial;
 use super::mesh::CalCoreMesh;
 use super::skeleton::CalCoreSkeleton;
+use super::vfs::{FileSystem, NATIVE_FILE_SYSTEM};
 
 #[derive(Debug)]
 pub enum CoreError {
@@ -19,17 +23,19 @@ pub struct CalCoreModel {
     // std::string                           m_strName;
     pCoreSkeleton: Rc<RefCell<CalCoreSkeleton>>,
     m_vectorCoreAnimation: Vec<Rc<RefCell<CalCoreAnimation>>>,
-    // std::vector<CalCoreAnimatedMorph *>   m_vectorCoreAnimatedMorph;
+    m_vectorCoreAnimatedMorph: Vec<Rc<RefCell<CalCoreAnimatedMorph>>>,
     m_vectorCoreMesh: Vec<Rc<RefCell<CalCoreMesh>>>,
     // std::vector<CalCoreMeshPtr>           m_vectorMorphMesh;
     m_vectorCoreMaterial: Vec<CalCoreMaterial>,
     m_mapmapCoreMaterialThread: BTreeMap<i32, BTreeMap<i32, i32>>,
     // Cal::UserData                         m_userData;
-    // std::map<std::string, int>            m_animationName;
-    // std::map<std::string, int>            m_animatedMorphName;
-    // std::map<std::string, int>            m_materialName;
-    // std::map<std::string, int>            m_meshName;
+    m_animationName: BTreeMap<String, usize>,
+    m_animatedMorphName: BTreeMap<String, usize>,
+    m_materialName: BTreeMap<String, i32>,
+    m_meshName: BTreeMap<String, usize>,
     // unsigned int                          m_magic;
+    m_loaderOptions: loader::LoaderOptions,
+    m_fileSystem: Option<Rc<dyn FileSystem>>,
 }
 
 impl CalCoreModel {
@@ -49,6 +55,56 @@ impl CalCoreModel {
         &self.m_vectorCoreMesh
     }
 
+    /*****************************************************************************/
+    /** Returns the loader options.
+     *
+     * This function returns the options used when loading the assets of the
+     * core model instance.
+     *****************************************************************************/
+    pub fn getLoaderOptions(&self) -> &loader::LoaderOptions {
+        &self.m_loaderOptions
+    }
+
+    /*****************************************************************************/
+    /** Sets the loader options.
+     *
+     * This function sets the options used when loading the assets of the core
+     * model instance. It should be called before any asset is loaded, as every
+     * asset of a model has to be converted the same way.
+     *
+     * @param options The options to load with.
+     *****************************************************************************/
+    pub fn setLoaderOptions(&mut self, options: loader::LoaderOptions) {
+        self.m_loaderOptions = options;
+    }
+
+    /*****************************************************************************/
+    /** Returns the file system.
+     *
+     * This function returns the file system the assets of the core model
+     * instance are loaded from, which is the file system of the process
+     * unless another one has been set.
+     *****************************************************************************/
+    pub fn getFileSystem(&self) -> &dyn FileSystem {
+        match &self.m_fileSystem {
+            Some(fileSystem) => fileSystem.as_ref(),
+            None => &NATIVE_FILE_SYSTEM,
+        }
+    }
+
+    /*****************************************************************************/
+    /** Sets the file system.
+     *
+     * This function sets the file system the assets, model descriptions and
+     * material maps of the core model instance are loaded from, such as a
+     * directory or an archive.
+     *
+     * @param fileSystem The file system to load from.
+     *****************************************************************************/
+    pub fn setFileSystem(&mut self, fileSystem: Rc<dyn FileSystem>) {
+        self.m_fileSystem = Some(fileSystem);
+    }
+
     pub fn getCoreAnimationCount(&self) -> usize {
         return self.m_vectorCoreAnimation.len();
     }
@@ -63,7 +119,7 @@ impl CalCoreModel {
      *
      * @return \li the assigned animation \b ID of the added core animation
      *****************************************************************************/
-    fn addCoreAnimation(&mut self, pCoreAnimation: Rc<RefCell<CalCoreAnimation>>) -> usize {
+    pub fn addCoreAnimation(&mut self, pCoreAnimation: Rc<RefCell<CalCoreAnimation>>) -> usize {
         let num = self.m_vectorCoreAnimation.len();
 
         // FIXME: Can Rc be null in Rust? No. Unclear if this is necessary for now.
@@ -78,6 +134,31 @@ impl CalCoreModel {
         num
     }
 
+    //140
+    /*****************************************************************************/
+    /** Adds a core animated morph (different from a morph animation).
+     *
+     * This function adds a core animated morph to the core model instance.
+     *
+     * @param pCoreAnimatedMorph A pointer to the core animated morph that should
+     *                           be added.
+     *
+     * @return \li the assigned animated morph \b ID of the added core animated
+     *         morph
+     *****************************************************************************/
+    pub fn addCoreAnimatedMorph(
+        &mut self,
+        pCoreAnimatedMorph: Rc<RefCell<CalCoreAnimatedMorph>>,
+    ) -> usize {
+        let num = self.m_vectorCoreAnimatedMorph.len();
+        self.m_vectorCoreAnimatedMorph.push(pCoreAnimatedMorph);
+        num
+    }
+
+    pub fn getCoreAnimatedMorphCount(&self) -> usize {
+        self.m_vectorCoreAnimatedMorph.len()
+    }
+
     //278
     /*****************************************************************************/
     /** Adds a core material.
@@ -173,6 +254,234 @@ impl CalCoreModel {
         return self.m_vectorCoreAnimation.get(coreAnimationId);
     }
 
+    //498
+    /*****************************************************************************/
+    /** Provides access to a core animated morph.
+     *
+     * This function returns the core animated morph with the given ID.
+     *
+     * @param coreAnimatedMorphId The ID of the core animated morph that should be
+     *                            returned.
+     *
+     * @return One of the following values:
+     *         \li a pointer to the core animated morph
+     *         \li \b 0 if an error happened
+     *****************************************************************************/
+    pub fn getCoreAnimatedMorph(
+        &self,
+        coreAnimatedMorphId: usize,
+    ) -> Option<&Rc<RefCell<CalCoreAnimatedMorph>>> {
+        self.m_vectorCoreAnimatedMorph.get(coreAnimatedMorphId)
+    }
+
+    //1750
+    /*****************************************************************************/
+    /** Creates or overwrites a string-to-animated-morph ID mapping
+     *
+     * This function makes an animated morph ID reference-able by a string name.
+     * The animated morph has to be added first, as an error is returned for an
+     * ID that is not valid. If there is already an animated morph with this
+     * name, it will be overwritten without warning.
+     *
+     * @param strAnimatedMorphName The string that will be associated with the ID.
+     * @param coreAnimatedMorphId The ID number of the animated morph to be
+     *                            referenced by the string.
+     *****************************************************************************/
+    pub fn addAnimatedMorphName(
+        &mut self,
+        strAnimatedMorphName: &str,
+        coreAnimatedMorphId: usize,
+    ) -> Result<(), CoreError> {
+        // check if the core animated morph id is valid
+        let Some(pCoreAnimatedMorph) = self.m_vectorCoreAnimatedMorph.get(coreAnimatedMorphId)
+        else {
+            return Err(CoreError::OtherError(format!(
+                "Animated morph id {coreAnimatedMorphId} outside range 0..{}",
+                self.m_vectorCoreAnimatedMorph.len()
+            )));
+        };
+
+        pCoreAnimatedMorph
+            .borrow_mut()
+            .setName(strAnimatedMorphName.to_string());
+        self.m_animatedMorphName
+            .insert(strAnimatedMorphName.to_string(), coreAnimatedMorphId);
+
+        Ok(())
+    }
+
+    //1778
+    /*****************************************************************************/
+    /** Retrieves the ID of the animated morph referenced by a string
+     *
+     * This function returns an animated morph ID
+     *
+     * @param strAnimatedMorphName A string that is associated with an animated
+     *                             morph ID number.
+     *
+     * @return Returns:
+     *         \li \b None if there is no animated morph ID associated with the
+     *         input string
+     *         \li \b the ID number of the animated morph asssociated with the
+     *         input string
+     *****************************************************************************/
+    pub fn getCoreAnimatedMorphId(&self, strAnimatedMorphName: &str) -> Option<usize> {
+        self.m_animatedMorphName.get(strAnimatedMorphName).copied()
+    }
+
+    //1660
+    /*****************************************************************************/
+    /** Creates or overwrites a string-to-animation ID mapping
+     *
+     * This function makes an animation ID reference-able by a string name.
+     * If there is already an animation with this name, it will be overwritten
+     * without warning.
+     *
+     * @param strAnimationName The string that will be associated with the ID.
+     * @param coreAnimationId The ID number of the animation to be referenced by
+     *                        the string.
+     *****************************************************************************/
+    pub fn addAnimationName(
+        &mut self,
+        strAnimationName: &str,
+        coreAnimationId: usize,
+    ) -> Result<(), CoreError> {
+        // check if the core animation id is valid
+        let Some(pCoreAnimation) = self.m_vectorCoreAnimation.get(coreAnimationId) else {
+            return Err(CoreError::OtherError(format!(
+                "Animation id {coreAnimationId} outside range 0..{}",
+                self.m_vectorCoreAnimation.len()
+            )));
+        };
+
+        pCoreAnimation
+            .borrow_mut()
+            .setName(strAnimationName.to_string());
+        self.m_animationName
+            .insert(strAnimationName.to_string(), coreAnimationId);
+
+        Ok(())
+    }
+
+    //1689
+    /*****************************************************************************/
+    /** Retrieves the ID of the animation referenced by a string
+     *
+     * This function returns an animation ID
+     *
+     * @param strAnimationName A string that is associated with an animation ID
+     *                         number.
+     *
+     * @return Returns:
+     *         \li \b None if there is no animation ID associated with the
+     *         input string
+     *         \li \b the ID number of the animation asssociated with the input
+     *         string
+     *****************************************************************************/
+    pub fn getCoreAnimationId(&self, strAnimationName: &str) -> Option<usize> {
+        self.m_animationName.get(strAnimationName).copied()
+    }
+
+    //1812
+    /*****************************************************************************/
+    /** Creates or overwrites a string-to-core-mesh ID mapping
+     *
+     * This function makes a core mesh ID reference-able by a string name.
+     * If there is already a mesh with this name, it will be overwritten
+     * without warning.
+     *
+     * @param strMeshName The string that will be associated with the ID.
+     * @param coreMeshId The core ID number of the mesh to be referenced by the
+     *                   string.
+     *****************************************************************************/
+    pub fn addMeshName(&mut self, strMeshName: &str, coreMeshId: usize) -> Result<(), CoreError> {
+        // check if the core mesh id is valid
+        let Some(pCoreMesh) = self.m_vectorCoreMesh.get(coreMeshId) else {
+            return Err(CoreError::OtherError(format!(
+                "Mesh id {coreMeshId} outside range 0..{}",
+                self.m_vectorCoreMesh.len()
+            )));
+        };
+
+        pCoreMesh.borrow_mut().setName(strMeshName.to_string());
+        self.m_meshName.insert(strMeshName.to_string(), coreMeshId);
+
+        Ok(())
+    }
+
+    //1839
+    /*****************************************************************************/
+    /** Retrieves the ID of the core mesh referenced by a string
+     *
+     * This function returns a core mesh ID
+     *
+     * @param strMeshName A string that is associated with a core mesh ID number.
+     *
+     * @return Returns:
+     *         \li \b None if there is no core mesh ID associated with the
+     *         input string
+     *         \li \b the core ID number of the mesh asssociated with the input
+     *         string
+     *****************************************************************************/
+    pub fn getCoreMeshId(&self, strMeshName: &str) -> Option<usize> {
+        self.m_meshName.get(strMeshName).copied()
+    }
+
+    //1862
+    /*****************************************************************************/
+    /** Creates or overwrites a string-to-core-material ID mapping
+     *
+     * This function makes a core material ID reference-able by a string name.
+     * If there is already a material with this name, it will be overwritten
+     * without warning.
+     *
+     * @param strMaterialName The string that will be associated with the ID.
+     * @param coreMaterialId The core ID number of the material to be referenced
+     *                       by the string.
+     *****************************************************************************/
+    pub fn addMaterialName(
+        &mut self,
+        strMaterialName: &str,
+        coreMaterialId: i32,
+    ) -> Result<(), CoreError> {
+        // check if the core material id is valid
+        let materialCount = self.m_vectorCoreMaterial.len();
+        let Some(pCoreMaterial) = usize::try_from(coreMaterialId)
+            .ok()
+            .and_then(|id| self.m_vectorCoreMaterial.get_mut(id))
+        else {
+            return Err(CoreError::OtherError(format!(
+                "Material id {coreMaterialId} outside range 0..{materialCount}"
+            )));
+        };
+
+        pCoreMaterial.setName(strMaterialName.to_string());
+        self.m_materialName
+            .insert(strMaterialName.to_string(), coreMaterialId);
+
+        Ok(())
+    }
+
+    //1889
+    /*****************************************************************************/
+    /** Retrieves the ID of the core material referenced by a string
+     *
+     * This function returns a core material ID. It is named apart from
+     * `getCoreMaterialId`, which looks a material up by thread and set.
+     *
+     * @param strMaterialName A string that is associated with a core material
+     *                        ID number.
+     *
+     * @return Returns:
+     *         \li \b None if there is no core material ID associated with the
+     *         input string
+     *         \li \b the core ID number of the material asssociated with the
+     *         input string
+     *****************************************************************************/
+    pub fn getCoreMaterialIdByName(&self, strMaterialName: &str) -> Option<i32> {
+        self.m_materialName.get(strMaterialName).copied()
+    }
+
     /*****************************************************************************/
     /** Provides access to a core mesh.
      *
@@ -243,7 +552,7 @@ impl CalCoreModel {
      *         \li the assigned \b ID of the loaded core animation
      *         \li \b -1 if an error happened
      *****************************************************************************/
-    pub fn loadCoreAnimation(&mut self, filename: &PathBuf) -> Result<usize, loader::LoaderError> {
+    pub fn loadCoreAnimation(&mut self, filename: &Path) -> Result<usize, loader::LoaderError> {
         // FIXME Check if skeleton has been loaded.
         // the core skeleton has to be loaded already
         //   if(!m_pCoreSkeleton)  {
@@ -252,12 +561,64 @@ impl CalCoreModel {
         //   }
 
         // load a new core animation
-        let pCoreAnimation = loader::loadCoreAnimation(filename, &self.pCoreSkeleton)?;
+        let pCoreAnimation = loader::loadCoreAnimationFromFileSystem(
+            self.getFileSystem(),
+            filename,
+            &self.pCoreSkeleton,
+            &self.m_loaderOptions,
+        )?;
 
         // add core animation to this core model
         Ok(self.addCoreAnimation(pCoreAnimation))
     }
 
+    //1007
+    /*****************************************************************************/
+    /** Loads a core animated morph.
+     *
+     * This function loads a core animated morph from a file.
+     *
+     * @param strFilename The file from which the core animated morph should be
+     *                    loaded from.
+     *
+     * @return One of the following values:
+     *         \li the assigned \b ID of the loaded core animated morph
+     *         \li \b -1 if an error happened
+     *****************************************************************************/
+    pub fn loadCoreAnimatedMorph(&mut self, filename: &Path) -> Result<usize, loader::LoaderError> {
+        // load a new core animated morph
+        let pCoreAnimatedMorph = loader::loadCoreAnimatedMorphFromFileSystem(
+            self.getFileSystem(),
+            filename,
+            &self.m_loaderOptions,
+        )?;
+
+        // add core animated morph to this core model
+        Ok(self.addCoreAnimatedMorph(pCoreAnimatedMorph))
+    }
+
+    /*****************************************************************************/
+    /** Loads a core animated morph and names it.
+     *
+     * This function loads a core animated morph from a file and makes its ID
+     * reference-able by the given name, see `addAnimatedMorphName`.
+     *
+     * @param strFilename The file from which the core animated morph should be
+     *                    loaded from.
+     * @param strAnimatedMorphName The name to assign to the animated morph.
+     *
+     * @return The assigned \b ID of the loaded core animated morph.
+     *****************************************************************************/
+    pub fn loadCoreAnimatedMorphNamed(
+        &mut self,
+        filename: &Path,
+        strAnimatedMorphName: &str,
+    ) -> Result<usize, loader::LoaderError> {
+        let id = self.loadCoreAnimatedMorph(filename)?;
+        self.addAnimatedMorphName(strAnimatedMorphName, id)?;
+        Ok(id)
+    }
+
     //1016
     /*****************************************************************************/
     /** Loads a core material.
@@ -271,7 +632,7 @@ impl CalCoreModel {
      *         \li the assigned \b ID of the loaded core material
      *         \li \b -1 if an error happened
      *****************************************************************************/
-    pub fn loadCoreMaterial(&mut self, filename: &PathBuf) -> Result<i32, loader::LoaderError> {
+    pub fn loadCoreMaterial(&mut self, filename: &Path) -> Result<i32, loader::LoaderError> {
         // FIXME Check if skeleton has been loaded.
         // the core skeleton has to be loaded already
         //   if(!m_pCoreSkeleton)  {
@@ -279,7 +640,11 @@ impl CalCoreModel {
         //     return -1;
         //   }
 
-        let pCoreMaterial = loader::loadCoreMaterial(filename)?;
+        let pCoreMaterial = loader::loadCoreMaterialFromFileSystem(
+            self.getFileSystem(),
+            filename,
+            &self.m_loaderOptions,
+        )?;
 
         Ok(self.addCoreMaterial(pCoreMaterial))
     }
@@ -296,7 +661,7 @@ impl CalCoreModel {
      *         \li the assigned \b ID of the loaded core mesh
      *         \li \b -1 if an error happened
      *****************************************************************************/
-    pub fn loadCoreMesh(&mut self, filename: &PathBuf) -> Result<usize, loader::LoaderError> {
+    pub fn loadCoreMesh(&mut self, filename: &Path) -> Result<usize, loader::LoaderError> {
         // FIXME Check if skeleton has been loaded.
         // the core skeleton has to be loaded already
         //   if(!m_pCoreSkeleton)  {
@@ -304,11 +669,83 @@ impl CalCoreModel {
         //     return -1;
         //   }
 
-        let pCoreMesh = loader::loadCoreMesh(filename)?;
+        let pCoreMesh = loader::loadCoreMeshFromFileSystem(
+            self.getFileSystem(),
+            filename,
+            &self.m_loaderOptions,
+        )?;
+
+        // the influences can only be checked against a skeleton that is loaded
+        let boneCount = self.pCoreSkeleton.borrow().getVectorCoreBone().len();
+        if boneCount > 0 {
+            loader::checkInfluenceBoneIds(
+                &pCoreMesh.borrow(),
+                boneCount,
+                filename,
+                &self.m_loaderOptions,
+            )?;
+        }
 
         Ok(self.addCoreMesh(pCoreMesh))
     }
 
+    /*****************************************************************************/
+    /** Loads a model description.
+     *
+     * This function loads every file listed in a model description (.cfg)
+     * file, see the `cfg` module for the format. Relative file names are found
+     * relative to the description, and every file is read through the file
+     * system of the core model instance.
+     *
+     * @param filename The model description file.
+     *
+     * @return The settings of the description, such as the scale and the IDs
+     *         of the loaded assets, or the reason it could not be loaded, with
+     *         the line of the description it happened on.
+     *****************************************************************************/
+    pub fn load_from_cfg(
+        &mut self,
+        filename: &Path,
+    ) -> Result<ModelDescription, loader::LoaderError> {
+        cfg::loadCfg(filename, self)
+    }
+
+    /*****************************************************************************/
+    /** Opens the file of a material map.
+     *
+     * This function opens the file of a map of a core material through the
+     * file system of the core model instance, the same way the assets of a
+     * model description are found.
+     *
+     * @param directory The directory the map filename is relative to, such as
+     *                  the path of a model description.
+     * @param coreMaterialId The ID of the core material.
+     * @param mapId The ID of the map.
+     *
+     * @return The file of the map, or the reason it could not be opened.
+     *****************************************************************************/
+    pub fn openMapFile(
+        &self,
+        directory: &Path,
+        coreMaterialId: i32,
+        mapId: usize,
+    ) -> Result<Box<dyn Read + '_>, loader::LoaderError> {
+        let Some(strFilename) = usize::try_from(coreMaterialId)
+            .ok()
+            .and_then(|id| self.m_vectorCoreMaterial.get(id))
+            .and_then(|material| material.getMapFilename(mapId))
+        else {
+            return Err(loader::LoaderError::FormatError(format!(
+                "Core material {coreMaterialId} has no map {mapId}"
+            )));
+        };
+
+        let filename = directory.join(strFilename);
+        self.getFileSystem().open(&filename).map_err(|e| {
+            loader::LoaderError::from(e).in_file(&filename, loader::AssetKind::Material)
+        })
+    }
+
     //1404
     /*****************************************************************************/
     /** Loads the core skeleton.
@@ -322,8 +759,13 @@ impl CalCoreModel {
      *         \li \b true if successful
      *         \li \b false if an error happened
      *****************************************************************************/
-    pub fn loadCoreSkeleton(&mut self, filename: &PathBuf) -> Result<(), loader::LoaderError> {
-        loader::loadCoreSkeleton(filename, &self.pCoreSkeleton)?;
+    pub fn loadCoreSkeleton(&mut self, filename: &Path) -> Result<(), loader::LoaderError> {
+        loader::loadCoreSkeletonFromFileSystem(
+            self.getFileSystem(),
+            filename,
+            &self.pCoreSkeleton,
+            &self.m_loaderOptions,
+        )?;
         Ok(())
     }
 
@@ -365,4 +807,273 @@ impl CalCoreModel {
 
         return true;
     }
+
+    /*****************************************************************************/
+    /** Returns the IDs of the core material threads.
+     *
+     * This function returns the IDs of every core material thread of the core
+     * model instance, in ascending order.
+     *****************************************************************************/
+    pub fn getCoreMaterialThreadIds(&self) -> Vec<i32> {
+        self.m_mapmapCoreMaterialThread.keys().copied().collect()
+    }
+
+    /*****************************************************************************/
+    /** Returns the IDs of the core material sets.
+     *
+     * This function returns the IDs of every core material set used by any core
+     * material thread of the core model instance, in ascending order.
+     *****************************************************************************/
+    pub fn getCoreMaterialSetIds(&self) -> Vec<i32> {
+        let setIds: BTreeSet<i32> = self
+            .m_mapmapCoreMaterialThread
+            .values()
+            .flat_map(|coreMaterialThread| coreMaterialThread.keys().copied())
+            .collect();
+        setIds.into_iter().collect()
+    }
+
+    /*****************************************************************************/
+    /** Provides access to a core material thread.
+     *
+     * This function returns the core material IDs of a core material thread,
+     * by core material set ID.
+     *
+     * @param coreMaterialThreadId The ID of the core material thread.
+     *
+     * @return One of the following values:
+     *         \li the core material IDs of the thread
+     *         \li \b None if the thread does not exist
+     *****************************************************************************/
+    pub fn getCoreMaterialThread(&self, coreMaterialThreadId: i32) -> Option<&BTreeMap<i32, i32>> {
+        self.m_mapmapCoreMaterialThread.get(&coreMaterialThreadId)
+    }
+
+    /*****************************************************************************/
+    /** Removes a core material thread.
+     *
+     * This function removes a core material thread and every core material set
+     * entry in it.
+     *
+     * @param coreMaterialThreadId The ID of the core material thread.
+     *
+     * @return One of the following values:
+     *         \li \b true if successful
+     *         \li \b false if the thread does not exist
+     *****************************************************************************/
+    pub fn removeCoreMaterialThread(&mut self, coreMaterialThreadId: i32) -> bool {
+        self.m_mapmapCoreMaterialThread
+            .remove(&coreMaterialThreadId)
+            .is_some()
+    }
+
+    /*****************************************************************************/
+    /** Removes a core material set.
+     *
+     * This function removes the entries of a core material set from every core
+     * material thread. The threads themselves are kept.
+     *
+     * @param coreMaterialSetId The ID of the core material set.
+     *
+     * @return One of the following values:
+     *         \li \b true if successful
+     *         \li \b false if no thread has an entry for the set
+     *****************************************************************************/
+    pub fn removeCoreMaterialSet(&mut self, coreMaterialSetId: i32) -> bool {
+        let mut removed = false;
+        for coreMaterialThread in self.m_mapmapCoreMaterialThread.values_mut() {
+            removed |= coreMaterialThread.remove(&coreMaterialSetId).is_some();
+        }
+        removed
+    }
+
+    /*****************************************************************************/
+    /** Creates a core material set from the core material IDs.
+     *
+     * This function maps every core material thread to the core material with
+     * the same ID in the given core material set, creating the threads as
+     * needed. Files without any material set information, such as those of the
+     * Cal3D exporters, expect this mapping.
+     *
+     * @param coreMaterialSetId The ID of the core material set to fill.
+     *****************************************************************************/
+    pub fn createDefaultMaterialSet(&mut self, coreMaterialSetId: i32) {
+        for coreMaterialId in 0..self.m_vectorCoreMaterial.len() as i32 {
+            self.createCoreMaterialThread(coreMaterialId);
+            self.setCoreMaterialId(coreMaterialId, coreMaterialSetId, coreMaterialId);
+        }
+    }
+
+    /*****************************************************************************/
+    /** Returns the core material ID of a submesh in a core material set.
+     *
+     * This function looks up the core material a submesh of a named core mesh
+     * uses in a core material set, through the core material thread of the
+     * submesh.
+     *
+     * @param strMeshName The name of the core mesh, see `addMeshName`.
+     * @param submeshId The ID of the submesh in the core mesh.
+     * @param coreMaterialSetId The ID of the core material set.
+     *
+     * @return One of the following values:
+     *         \li the \b ID of the core material
+     *         \li \b None if the mesh, submesh or material does not exist
+     *****************************************************************************/
+    pub fn getSubmeshCoreMaterialId(
+        &self,
+        strMeshName: &str,
+        submeshId: usize,
+        coreMaterialSetId: i32,
+    ) -> Option<i32> {
+        let pCoreMesh = self.getCoreMesh(self.getCoreMeshId(strMeshName)?)?;
+        let coreMaterialThreadId = pCoreMesh
+            .borrow()
+            .getCoreSubmeshes()
+            .get(submeshId)?
+            .borrow()
+            .getCoreMaterialThreadId();
+        self.getCoreMaterialId(coreMaterialThreadId, coreMaterialSetId)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testdata;
+    use crate::core::vfs::DirectoryFileSystem;
+    use crate::core::{Color, Endianness, Map};
+
+    /// Returns an empty directory for the files of a test.
+    fn testDirectory(test: &str) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(format!("cal3d-{test}-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn animated_morphs_load_by_name() {
+        let directory = testDirectory("morphs");
+        let binary = testdata::saveAnimatedMorphToBuffer(
+            Endianness::LittleEndian,
+            &testdata::animatedMorph().borrow(),
+        );
+        std::fs::write(directory.join("face.xpf"), testdata::ANIMATED_MORPH_XML).unwrap();
+        std::fs::write(directory.join("face.cpf"), binary).unwrap();
+
+        let mut coreModel = CalCoreModel::default();
+        coreModel.setFileSystem(Rc::new(DirectoryFileSystem::new(&directory)));
+        let xmlId = coreModel
+            .loadCoreAnimatedMorphNamed(Path::new("face.xpf"), "xml")
+            .unwrap();
+        let binaryId = coreModel
+            .loadCoreAnimatedMorphNamed(Path::new("face.cpf"), "binary")
+            .unwrap();
+        assert_eq!(coreModel.getCoreAnimatedMorphCount(), 2);
+        assert_eq!(coreModel.getCoreAnimatedMorphId("xml"), Some(xmlId));
+        assert_eq!(coreModel.getCoreAnimatedMorphId("binary"), Some(binaryId));
+        assert_eq!(coreModel.getCoreAnimatedMorphId("missing"), None);
+
+        for animatedMorphId in [xmlId, binaryId] {
+            let animatedMorph = coreModel.getCoreAnimatedMorph(animatedMorphId).unwrap();
+            let animatedMorph = animatedMorph.borrow();
+            assert_eq!(animatedMorph.getDuration(), 2.0);
+            assert_eq!(animatedMorph.getListCoreTrack().len(), 2);
+
+            // the weights are interpolated between the keyframes
+            let smile = animatedMorph.getCoreTrack("smile").unwrap();
+            assert_eq!(smile.getCoreMorphKeyframeCount(), 3);
+            assert_eq!(smile.getState(0.5), Some(0.5));
+            assert_eq!(smile.getState(1.5), Some(0.75));
+            let blink = animatedMorph.getCoreTrack("blink").unwrap();
+            assert_eq!(blink.getState(0.5), Some(1.0));
+            assert!(animatedMorph.getCoreTrack("frown").is_none());
+        }
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn assets_are_looked_up_by_name() {
+        let mut coreModel = CalCoreModel::default();
+        let skeleton = coreModel.getCoreSkeleton().clone();
+        let animationId = coreModel.addCoreAnimation(testdata::animation(&skeleton));
+        let meshId = coreModel.addCoreMesh(testdata::mesh());
+        coreModel.addCoreMaterial(testdata::material());
+        let materialId = coreModel.addCoreMaterial(testdata::material());
+
+        coreModel.addAnimationName("walk", animationId).unwrap();
+        coreModel.addMeshName("body", meshId).unwrap();
+        coreModel.addMaterialName("skin", materialId).unwrap();
+        assert_eq!(coreModel.getCoreAnimationId("walk"), Some(animationId));
+        assert_eq!(coreModel.getCoreMeshId("body"), Some(meshId));
+        assert_eq!(coreModel.getCoreMaterialIdByName("skin"), Some(materialId));
+        assert_eq!(coreModel.getCoreAnimationId("body"), None);
+
+        // the assets are given the names too
+        let animation = coreModel.getCoreAnimation(animationId).unwrap();
+        assert_eq!(animation.borrow().getName(), "walk");
+        assert_eq!(
+            coreModel.getCoreMesh(meshId).unwrap().borrow().getName(),
+            "body"
+        );
+        assert_eq!(coreModel.getCoreMaterials()[1].getName(), "skin");
+
+        // names can be moved to other assets, but not to missing ones
+        coreModel.addMaterialName("skin", 0).unwrap();
+        assert_eq!(coreModel.getCoreMaterialIdByName("skin"), Some(0));
+        assert!(coreModel.addAnimationName("run", 1).is_err());
+        assert!(coreModel.addMeshName("head", 1).is_err());
+        assert!(coreModel.addMaterialName("hair", 2).is_err());
+        assert_eq!(coreModel.getCoreAnimationId("run"), None);
+    }
+
+    #[test]
+    fn material_sets_map_threads_to_materials() {
+        let mut coreModel = CalCoreModel::default();
+        let plain = CalCoreMaterial::default()
+            .with_diffuse_color(Color::new(255, 0, 0, 255))
+            .with_shininess(0.5)
+            .with_map(Map::new("plain.png".to_string(), "diffuse".to_string(), 0))
+            .with_name("plain");
+        assert_eq!(plain.getDiffuseColor().red(), 255);
+        assert_eq!(plain.getAmbientColor(), &Color::default());
+        assert_eq!(plain.getShininess(), 0.5);
+        assert_eq!(plain.getMapFilename(0), Some("plain.png"));
+        assert_eq!(plain.getMapType(0), Some("diffuse"));
+        assert_eq!(plain.getName(), "plain");
+        coreModel.addCoreMaterial(plain);
+        coreModel.addCoreMaterial(testdata::material().with_name("fancy"));
+        let meshId = coreModel.addCoreMesh(testdata::mesh());
+        coreModel.addMeshName("body", meshId).unwrap();
+
+        // set 0 uses the material of the same id, and set 1 swaps them over
+        coreModel.createDefaultMaterialSet(0);
+        coreModel.setCoreMaterialId(0, 1, 1);
+        coreModel.setCoreMaterialId(1, 1, 0);
+        assert_eq!(coreModel.getCoreMaterialThreadIds(), vec![0, 1]);
+        assert_eq!(coreModel.getCoreMaterialSetIds(), vec![0, 1]);
+        assert_eq!(coreModel.getCoreMaterialId(0, 0), Some(0));
+        assert_eq!(coreModel.getCoreMaterialId(0, 1), Some(1));
+        assert_eq!(
+            coreModel.getCoreMaterialThread(1),
+            Some(&BTreeMap::from([(0, 1), (1, 0)]))
+        );
+
+        // the submesh of the mesh uses thread 0
+        assert_eq!(coreModel.getSubmeshCoreMaterialId("body", 0, 0), Some(0));
+        assert_eq!(coreModel.getSubmeshCoreMaterialId("body", 0, 1), Some(1));
+        assert_eq!(coreModel.getSubmeshCoreMaterialId("body", 1, 1), None);
+        assert_eq!(coreModel.getSubmeshCoreMaterialId("head", 0, 1), None);
+
+        assert!(coreModel.removeCoreMaterialSet(1));
+        assert!(!coreModel.removeCoreMaterialSet(1));
+        assert_eq!(coreModel.getCoreMaterialSetIds(), vec![0]);
+        assert_eq!(coreModel.getSubmeshCoreMaterialId("body", 0, 1), None);
+
+        assert!(coreModel.removeCoreMaterialThread(0));
+        assert!(!coreModel.removeCoreMaterialThread(0));
+        assert_eq!(coreModel.getCoreMaterialThreadIds(), vec![1]);
+        assert_eq!(coreModel.getSubmeshCoreMaterialId("body", 0, 0), None);
+        assert!(!coreModel.setCoreMaterialId(0, 0, 0));
+    }
 }
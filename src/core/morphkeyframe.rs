@@ -0,0 +1,22 @@
+pub struct CalCoreMorphKeyframe {
+    m_time: f32,
+    m_weight: f32,
+}
+
+impl CalCoreMorphKeyframe {
+    pub fn new(m_time: f32, m_weight: f32) -> Self {
+        CalCoreMorphKeyframe { m_time, m_weight }
+    }
+
+    pub fn getTime(&self) -> f32 {
+        self.m_time
+    }
+
+    pub fn getWeight(&self) -> f32 {
+        self.m_weight
+    }
+
+    pub fn setWeight(&mut self, weight: f32) {
+        self.m_weight = weight;
+    }
+}
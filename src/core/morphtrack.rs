@@ -0,0 +1,81 @@
+use super::morphkeyframe::CalCoreMorphKeyframe;
+
+pub struct CalCoreMorphTrack {
+    // /// The name of the morph target the weights are for.
+    m_morphName: String,
+
+    // /// List of keyframes, always sorted by time.
+    m_keyframes: Vec<CalCoreMorphKeyframe>,
+}
+
+impl CalCoreMorphTrack {
+    pub fn new(m_morphName: String, mut m_keyframes: Vec<CalCoreMorphKeyframe>) -> Self {
+        m_keyframes.sort_by(|a, b| a.getTime().total_cmp(&b.getTime()));
+        CalCoreMorphTrack {
+            m_morphName,
+            m_keyframes,
+        }
+    }
+
+    pub fn getMorphName(&self) -> &str {
+        &self.m_morphName
+    }
+
+    //103 cpp
+    /*****************************************************************************/
+    /** Returns a specified state.
+     *
+     * This function returns the weight of the morph target at a specified time,
+     * blending linearly between the keyframes around it.
+     *
+     * @param time The time in seconds at which the state should be returned.
+     *
+     * @return The weight of the morph target, or \b None if the track has no
+     *         keyframes.
+     *****************************************************************************/
+    pub fn getState(&self, time: f32) -> Option<f32> {
+        let last = self.m_keyframes.last()?;
+
+        // get the keyframe after the requested time
+        let iteratorCoreMorphKeyframeAfter = self
+            .m_keyframes
+            .partition_point(|keyframe| keyframe.getTime() <= time);
+
+        // check if the time is after the last keyframe
+        if iteratorCoreMorphKeyframeAfter == self.m_keyframes.len() {
+            return Some(last.getWeight());
+        }
+
+        // check if the time is before the first keyframe
+        if iteratorCoreMorphKeyframeAfter == 0 {
+            return Some(self.m_keyframes[0].getWeight());
+        }
+
+        // get the two keyframes around the requested time
+        let pCoreMorphKeyframeBefore = &self.m_keyframes[iteratorCoreMorphKeyframeAfter - 1];
+        let pCoreMorphKeyframeAfter = &self.m_keyframes[iteratorCoreMorphKeyframeAfter];
+
+        // calculate the blending factor between the two keyframe states
+        let blendFactor = (time - pCoreMorphKeyframeBefore.getTime())
+            / (pCoreMorphKeyframeAfter.getTime() - pCoreMorphKeyframeBefore.getTime());
+
+        // blend between the two keyframes
+        Some(
+            pCoreMorphKeyframeBefore.getWeight()
+                + blendFactor
+                    * (pCoreMorphKeyframeAfter.getWeight() - pCoreMorphKeyframeBefore.getWeight()),
+        )
+    }
+
+    pub fn getCoreMorphKeyframeCount(&self) -> usize {
+        self.m_keyframes.len()
+    }
+
+    pub fn getCoreMorphKeyframe(&self, idx: usize) -> Option<&CalCoreMorphKeyframe> {
+        self.m_keyframes.get(idx)
+    }
+
+    pub fn getVectorCoreMorphKeyframes(&self) -> &Vec<CalCoreMorphKeyframe> {
+        &self.m_keyframes
+    }
+}
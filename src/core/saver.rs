@@ -0,0 +1,1014 @@
+use std::fs;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use cgmath::InnerSpace;
+
+use crate::{CalQuaternion, CalVector};
+
+use super::animation::CalCoreAnimation;
+use super::bone::CalCoreBone;
+use super::datasink::{CalVectorToDataSink, DataSink, SinkError};
+use super::datasource::maxStringLength;
+use super::keyframe::CalCoreKeyframe;
+use super::loader;
+use super::material::{CalCoreMaterial, Color};
+use super::mesh::CalCoreMesh;
+use super::skeleton::CalCoreSkeleton;
+use super::submesh::CalCoreSubmesh;
+use super::submorphtarget::{BlendVertex, CalCoreSubMorphTarget};
+use super::track::CalCoreTrack;
+use super::writersink::WriterSink;
+use super::xmlformat;
+
+#[derive(Debug)]
+pub enum SaverError {
+    IoError(std::io::Error),
+    FormatError(String),
+}
+
+impl std::fmt::Display for SaverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaverError::IoError(e) => write!(f, "I/O error: {e}"),
+            SaverError::FormatError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SaverError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SaverError::IoError(e) => Some(e),
+            SaverError::FormatError(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SaverError {
+    fn from(error: std::io::Error) -> Self {
+        SaverError::IoError(error)
+    }
+}
+
+impl From<std::fmt::Error> for SaverError {
+    fn from(_: std::fmt::Error) -> Self {
+        SaverError::FormatError(String::from("Error formatting XML"))
+    }
+}
+
+impl From<SinkError> for SaverError {
+    fn from(error: SinkError) -> Self {
+        match error {
+            SinkError::IoError(e) => SaverError::IoError(e),
+            SinkError::FormatError(e) => SaverError::FormatError(e),
+        }
+    }
+}
+
+fn saveToFile(
+    filename: &PathBuf,
+    save: impl FnOnce(&mut dyn DataSink) -> Result<(), SaverError>,
+) -> Result<(), SaverError> {
+    let mut sink = WriterSink::new(BufWriter::new(fs::File::create(filename)?));
+
+    save(&mut sink)?;
+
+    sink.flush()?;
+    Ok(())
+}
+
+/// Writes a string, refusing one that the loader would not read back: the
+/// loader takes at most maxStringLength bytes, counting the trailing null, and
+/// stops at the first null.
+fn saveString(dataSink: &mut dyn DataSink, what: &str, value: &str) -> Result<(), SaverError> {
+    if value.len() + 1 > maxStringLength as usize {
+        return Err(SaverError::FormatError(format!(
+            "{what} {value:?} is {} bytes long, over the limit of {}",
+            value.len(),
+            maxStringLength - 1
+        )));
+    }
+    if value.contains('\0') {
+        return Err(SaverError::FormatError(format!(
+            "{what} {value:?} contains a null character"
+        )));
+    }
+
+    dataSink.writeString(value)?;
+    Ok(())
+}
+
+/// Rebuilds the child ids of every bone in a skeleton from the parent ids,
+/// which are the authoritative hierarchy.
+pub(super) fn childIdsFromParentIds(
+    pCoreSkeleton: &CalCoreSkeleton,
+) -> Result<Vec<Vec<i32>>, SaverError> {
+    let vectorCoreBone = pCoreSkeleton.getVectorCoreBone();
+    let mut listChildIds = vec![Vec::<i32>::new(); vectorCoreBone.len()];
+    for (boneId, pCoreBone) in vectorCoreBone.iter().enumerate() {
+        let parentId = pCoreBone.borrow().getParentId();
+        if parentId != -1 {
+            let Some(listChildId) = listChildIds.get_mut(parentId as usize) else {
+                return Err(SaverError::FormatError(format!(
+                    "Bone {boneId} has invalid parent id {parentId}"
+                )));
+            };
+            listChildId.push(boneId as i32);
+        }
+    }
+    Ok(listChildIds)
+}
+
+/// Returns the ids of the blend vertices of a morph target that should be
+/// written. Blend vertices that were never set are left as their default and
+/// are skipped, matching the sparse layout the loaders expect, but at least
+/// one is always kept as the loaders reject empty morph targets.
+pub(super) fn storedBlendVertexIds(morphTarget: &CalCoreSubMorphTarget) -> Vec<usize> {
+    let vectorBlendVertex = morphTarget.getVectorBlendVertex();
+    let mut blendVertexIds = vectorBlendVertex
+        .iter()
+        .enumerate()
+        .filter(|(_, blendVertex)| isBlendVertexSet(blendVertex))
+        .map(|(blendVertexId, _)| blendVertexId)
+        .collect::<Vec<_>>();
+
+    if blendVertexIds.is_empty() && !vectorBlendVertex.is_empty() {
+        blendVertexIds.push(0);
+    }
+
+    blendVertexIds
+}
+
+/// Returns whether a blend vertex was set, rather than left as the default
+/// that stands for the unchanged vertex.
+pub(super) fn isBlendVertexSet(blendVertex: &BlendVertex) -> bool {
+    !blendVertex.textureCoords.is_empty()
+        || blendVertex.position != CalVector::new(0.0, 0.0, 0.0)
+        || blendVertex.normal != CalVector::new(0.0, 0.0, 0.0)
+}
+
+/*****************************************************************************/
+/** Saves a core animation instance.
+ *
+ * This function saves a core animation instance to a file, as XML if the
+ * filename ends in .xaf.
+ *
+ * @param filename The name of the file to save the core animation instance
+ *                 to.
+ * @param pCoreAnimation A pointer to the core animation instance that should
+ *                       be saved.
+ *****************************************************************************/
+pub fn saveCoreAnimation(
+    filename: &PathBuf,
+    pCoreAnimation: &CalCoreAnimation,
+) -> Result<(), SaverError> {
+    let magic: String = String::from_utf8_lossy(loader::ANIMATION_XMLFILE_MAGIC)
+        .trim_matches(char::from(0))
+        .to_lowercase()
+        .to_owned();
+    if filename.to_str().unwrap().ends_with(magic.as_str()) {
+        return xmlformat::saveXmlCoreAnimation(filename, pCoreAnimation);
+    }
+
+    saveToFile(filename, |sink| {
+        saveCoreAnimationToSink(sink, pCoreAnimation)
+    })
+}
+
+/*****************************************************************************/
+/** Saves a core animation instance with compressed keyframes.
+ *
+ * This function saves a core animation instance to a binary file, with the
+ * keyframes quantized as read back by the loader for files with the
+ * compression flag set. Keyframe times are rounded to 1/30th of a second.
+ *
+ * @param filename The name of the file to save the core animation instance
+ *                 to.
+ * @param pCoreAnimation A pointer to the core animation instance that should
+ *                       be saved.
+ *****************************************************************************/
+pub fn saveCompressedCoreAnimation(
+    filename: &PathBuf,
+    pCoreAnimation: &CalCoreAnimation,
+) -> Result<(), SaverError> {
+    saveToFile(filename, |sink| {
+        saveCompressedCoreAnimationToSink(sink, pCoreAnimation)
+    })
+}
+
+/*****************************************************************************/
+/** Saves a core material instance.
+ *
+ * This function saves a core material instance to a file, as XML if the
+ * filename ends in .xrf.
+ *
+ * @param filename The name of the file to save the core material instance
+ *                 to.
+ * @param pCoreMaterial A pointer to the core material instance that should
+ *                      be saved.
+ *****************************************************************************/
+pub fn saveCoreMaterial(
+    filename: &PathBuf,
+    pCoreMaterial: &CalCoreMaterial,
+) -> Result<(), SaverError> {
+    let magic: String = String::from_utf8_lossy(loader::MATERIAL_XMLFILE_MAGIC)
+        .trim_matches(char::from(0))
+        .to_lowercase()
+        .to_owned();
+    if filename.to_str().unwrap().ends_with(magic.as_str()) {
+        return xmlformat::saveXmlCoreMaterial(filename, pCoreMaterial);
+    }
+
+    saveToFile(filename, |sink| saveCoreMaterialToSink(sink, pCoreMaterial))
+}
+
+/*****************************************************************************/
+/** Saves a core mesh instance.
+ *
+ * This function saves a core mesh instance to a file, as XML if the
+ * filename ends in .xmf.
+ *
+ * @param filename The name of the file to save the core mesh instance to.
+ * @param pCoreMesh A pointer to the core mesh instance that should be saved.
+ *****************************************************************************/
+pub fn saveCoreMesh(filename: &PathBuf, pCoreMesh: &CalCoreMesh) -> Result<(), SaverError> {
+    let magic: String = String::from_utf8_lossy(loader::MESH_XMLFILE_MAGIC)
+        .trim_matches(char::from(0))
+        .to_lowercase()
+        .to_owned();
+    if filename.to_str().unwrap().ends_with(magic.as_str()) {
+        return xmlformat::saveXmlCoreMesh(filename, pCoreMesh);
+    }
+
+    saveToFile(filename, |sink| saveCoreMeshToSink(sink, pCoreMesh))
+}
+
+/*****************************************************************************/
+/** Saves a core skeleton instance.
+ *
+ * This function saves a core skeleton instance to a file, as XML if the
+ * filename ends in .xsf.
+ *
+ * @param filename The name of the file to save the core skeleton instance
+ *                 to.
+ * @param pCoreSkeleton A pointer to the core skeleton instance that should be
+ *                      saved.
+ *****************************************************************************/
+pub fn saveCoreSkeleton(
+    filename: &PathBuf,
+    pCoreSkeleton: &CalCoreSkeleton,
+) -> Result<(), SaverError> {
+    let magic: String = String::from_utf8_lossy(loader::SKELETON_XMLFILE_MAGIC)
+        .trim_matches(char::from(0))
+        .to_lowercase()
+        .to_owned();
+    if filename.to_str().unwrap().ends_with(magic.as_str()) {
+        return xmlformat::saveXmlCoreSkeleton(filename, pCoreSkeleton);
+    }
+
+    saveToFile(filename, |sink| saveCoreSkeletonToSink(sink, pCoreSkeleton))
+}
+
+/*****************************************************************************/
+/** Saves a core animation instance.
+ *
+ * This function saves a core animation instance to a data sink, in the
+ * format read by loadCoreAnimationFromSource.
+ *
+ * @param dataSink The data sink to save the core animation instance to.
+ * @param pCoreAnimation The core animation instance that should be saved.
+ *****************************************************************************/
+pub fn saveCoreAnimationToSink(
+    dataSink: &mut dyn DataSink,
+    pCoreAnimation: &CalCoreAnimation,
+) -> Result<(), SaverError> {
+    saveCoreAnimationData(dataSink, pCoreAnimation, false)
+}
+
+/*****************************************************************************/
+/** Saves a core animation instance with compressed keyframes.
+ *
+ * This function saves a core animation instance to a data sink, with the
+ * keyframes quantized as read back by loadCoreAnimationFromSource for files
+ * with the compression flag set.
+ *
+ * @param dataSink The data sink to save the core animation instance to.
+ * @param pCoreAnimation The core animation instance that should be saved.
+ *****************************************************************************/
+pub fn saveCompressedCoreAnimationToSink(
+    dataSink: &mut dyn DataSink,
+    pCoreAnimation: &CalCoreAnimation,
+) -> Result<(), SaverError> {
+    saveCoreAnimationData(dataSink, pCoreAnimation, true)
+}
+
+fn saveCoreAnimationData(
+    dataSink: &mut dyn DataSink,
+    pCoreAnimation: &CalCoreAnimation,
+    useAnimationCompression: bool,
+) -> Result<(), SaverError> {
+    // write magic tag and version
+    dataSink.writeBytes(loader::ANIMATION_FILE_MAGIC)?;
+    dataSink.writeInteger(loader::CURRENT_FILE_VERSION)?;
+
+    // write the compression flag
+    if loader::versionHasCompressionFlag(loader::CURRENT_FILE_VERSION) {
+        dataSink.writeInteger(useAnimationCompression as i32)?;
+    }
+
+    // write the duration of the core animation
+    let duration = pCoreAnimation.getDuration();
+    if duration <= 0.0 {
+        return Err(SaverError::FormatError(format!(
+            "Animation duration {duration} is negative"
+        )));
+    }
+    dataSink.writeFloat(duration)?;
+
+    // write the number of tracks
+    let listCoreTrack = pCoreAnimation.getListCoreTrack();
+    if listCoreTrack.is_empty() {
+        return Err(SaverError::FormatError(String::from(
+            "Animation has no tracks",
+        )));
+    }
+    dataSink.writeInteger(listCoreTrack.len() as i32)?;
+
+    // write the flags
+    if loader::CURRENT_FILE_VERSION >= loader::LIBRARY_VERSION {
+        dataSink.writeInteger(0)?;
+    }
+
+    // save all core tracks
+    for pCoreTrack in listCoreTrack.iter() {
+        match useAnimationCompression {
+            true => saveCompressedCoreTrack(dataSink, &pCoreTrack.borrow())?,
+            false => saveCoreTrack(dataSink, &pCoreTrack.borrow())?,
+        }
+    }
+
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core bone instance.
+ *
+ * This function saves a core bone instance to a data sink.
+ *
+ * @param dataSink The data sink to save the core bone instance to.
+ * @param pCoreBone The core bone instance that should be saved.
+ * @param listChildId The ids of the children of the core bone.
+ *****************************************************************************/
+fn saveCoreBone(
+    dataSink: &mut dyn DataSink,
+    pCoreBone: &CalCoreBone,
+    listChildId: &[i32],
+) -> Result<(), SaverError> {
+    // write the name of the bone
+    saveString(dataSink, "bone name", pCoreBone.getName())?;
+
+    // write the translation of the bone
+    CalVectorToDataSink(dataSink, pCoreBone.getTranslation())?;
+
+    // write the rotation of the bone
+    let rotation = pCoreBone.getRotation();
+    dataSink.writeFloat(rotation.v.x)?;
+    dataSink.writeFloat(rotation.v.y)?;
+    dataSink.writeFloat(rotation.v.z)?;
+    dataSink.writeFloat(rotation.s)?;
+
+    // write the translation of the bone
+    CalVectorToDataSink(dataSink, pCoreBone.getTranslationBoneSpace())?;
+
+    // write the rotation of the bone
+    let rotationBoneSpace = pCoreBone.getRotationBoneSpace();
+    dataSink.writeFloat(rotationBoneSpace.v.x)?;
+    dataSink.writeFloat(rotationBoneSpace.v.y)?;
+    dataSink.writeFloat(rotationBoneSpace.v.z)?;
+    dataSink.writeFloat(rotationBoneSpace.s)?;
+
+    // write the parent bone id
+    dataSink.writeInteger(pCoreBone.getParentId())?;
+
+    // write the number of children
+    dataSink.writeInteger(listChildId.len() as i32)?;
+
+    // write all children ids
+    for childId in listChildId {
+        dataSink.writeInteger(*childId)?;
+    }
+
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core keyframe instance.
+ *
+ * This function saves a core keyframe instance to a data sink.
+ *
+ * @param dataSink The data sink to save the core keyframe instance to.
+ * @param pCoreKeyframe The core keyframe instance that should be saved.
+ *****************************************************************************/
+fn saveCoreKeyframe(
+    dataSink: &mut dyn DataSink,
+    pCoreKeyframe: &CalCoreKeyframe,
+) -> Result<(), SaverError> {
+    // write the time of the keyframe
+    dataSink.writeFloat(pCoreKeyframe.getTime())?;
+
+    // write the translation of the keyframe
+    CalVectorToDataSink(dataSink, pCoreKeyframe.getTranslation())?;
+
+    // write the rotation of the keyframe
+    let rotation = pCoreKeyframe.getRotation();
+    dataSink.writeFloat(rotation.v.x)?;
+    dataSink.writeFloat(rotation.v.y)?;
+    dataSink.writeFloat(rotation.v.z)?;
+    dataSink.writeFloat(rotation.s)?;
+
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core material instance.
+ *
+ * This function saves a core material instance to a data sink, in the
+ * format read by loadCoreMaterialFromSource.
+ *
+ * @param dataSink The data sink to save the core material instance to.
+ * @param pCoreMaterial The core material instance that should be saved.
+ *****************************************************************************/
+pub fn saveCoreMaterialToSink(
+    dataSink: &mut dyn DataSink,
+    pCoreMaterial: &CalCoreMaterial,
+) -> Result<(), SaverError> {
+    fn saveColorToSink(dataSink: &mut dyn DataSink, color: &Color) -> Result<(), SaverError> {
+        dataSink.writeByte(color.red())?;
+        dataSink.writeByte(color.green())?;
+        dataSink.writeByte(color.blue())?;
+        dataSink.writeByte(color.alpha())?;
+        Ok(())
+    }
+
+    // write magic tag and version
+    dataSink.writeBytes(loader::MATERIAL_FILE_MAGIC)?;
+    dataSink.writeInteger(loader::CURRENT_FILE_VERSION)?;
+
+    let hasMaterialTypes =
+        loader::CURRENT_FILE_VERSION >= loader::FIRST_FILE_VERSION_WITH_MATERIAL_TYPES;
+
+    // write the ambient, diffuse and specular colors of the core material
+    saveColorToSink(dataSink, pCoreMaterial.getAmbientColor())?;
+    saveColorToSink(dataSink, pCoreMaterial.getDiffuseColor())?;
+    saveColorToSink(dataSink, pCoreMaterial.getSpecularColor())?;
+
+    // write the shininess factor of the core material
+    dataSink.writeFloat(pCoreMaterial.getShininess())?;
+
+    // write the number of maps
+    let maps = pCoreMaterial.getMaps();
+    dataSink.writeInteger(maps.len() as i32)?;
+
+    // write all maps
+    for map in maps.iter() {
+        saveString(dataSink, "map filename", &map.strFilename)?;
+
+        if hasMaterialTypes {
+            saveString(dataSink, "map type", &map.mapType)?;
+        }
+    }
+
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core mesh instance.
+ *
+ * This function saves a core mesh instance to a data sink, in the format read
+ * by loadCoreMeshFromSource.
+ *
+ * @param dataSink The data sink to save the core mesh instance to.
+ * @param pCoreMesh The core mesh instance that should be saved.
+ *****************************************************************************/
+pub fn saveCoreMeshToSink(
+    dataSink: &mut dyn DataSink,
+    pCoreMesh: &CalCoreMesh,
+) -> Result<(), SaverError> {
+    // write magic tag and version
+    dataSink.writeBytes(loader::MESH_FILE_MAGIC)?;
+    dataSink.writeInteger(loader::CURRENT_FILE_VERSION)?;
+
+    // write the number of submeshes
+    let vectorCoreSubmesh = pCoreMesh.getCoreSubmeshes();
+    dataSink.writeInteger(vectorCoreSubmesh.len() as i32)?;
+
+    // write all core submeshes
+    for pCoreSubmesh in vectorCoreSubmesh.iter() {
+        saveCoreSubmesh(dataSink, &pCoreSubmesh.borrow())?;
+    }
+
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core skeleton instance.
+ *
+ * This function saves a core skeleton instance to a data sink, in the format
+ * read by loadCoreSkeletonFromSource.
+ *
+ * @param dataSink The data sink to save the core skeleton instance to.
+ * @param pCoreSkeleton The core skeleton instance that should be saved.
+ *****************************************************************************/
+pub fn saveCoreSkeletonToSink(
+    dataSink: &mut dyn DataSink,
+    pCoreSkeleton: &CalCoreSkeleton,
+) -> Result<(), SaverError> {
+    // write magic tag and version
+    dataSink.writeBytes(loader::SKELETON_FILE_MAGIC)?;
+    dataSink.writeInteger(loader::CURRENT_FILE_VERSION)?;
+
+    // write the number of bones
+    let vectorCoreBone = pCoreSkeleton.getVectorCoreBone();
+    if vectorCoreBone.is_empty() {
+        return Err(SaverError::FormatError(String::from(
+            "Skeleton has no bones",
+        )));
+    }
+    dataSink.writeInteger(vectorCoreBone.len() as i32)?;
+
+    let listChildIds = childIdsFromParentIds(pCoreSkeleton)?;
+
+    // write all core bones
+    for (pCoreBone, listChildId) in vectorCoreBone.iter().zip(listChildIds.iter()) {
+        saveCoreBone(dataSink, &pCoreBone.borrow(), listChildId)?;
+    }
+
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core submesh instance.
+ *
+ * This function saves a core submesh instance to a data sink.
+ *
+ * @param dataSink The data sink to save the core submesh instance to.
+ * @param pCoreSubmesh The core submesh instance that should be saved.
+ *****************************************************************************/
+fn saveCoreSubmesh(
+    dataSink: &mut dyn DataSink,
+    pCoreSubmesh: &CalCoreSubmesh,
+) -> Result<(), SaverError> {
+    let hasVertexColors =
+        loader::CURRENT_FILE_VERSION >= loader::FIRST_FILE_VERSION_WITH_VERTEX_COLORS;
+    let hasMorphTargetsInMorphFiles = loader::CURRENT_FILE_VERSION
+        >= loader::FIRST_FILE_VERSION_WITH_MORPH_TARGETS_IN_MORPH_FILES;
+    let hasVertexSlaveAttributes =
+        loader::CURRENT_FILE_VERSION >= loader::FIRST_FILE_VERSION_WITH_VERTEX_SLAVES_ATTRIBUTES;
+
+    let vectorVertex = pCoreSubmesh.getVectorVertex();
+    let vectorFace = pCoreSubmesh.getVectorFace();
+    let vectorPhysicalProperty = pCoreSubmesh.getVectorPhysicalProperty();
+    let vectorSpring = pCoreSubmesh.getVectorSpring();
+    let vectorvectorTextureCoordinate = pCoreSubmesh.getVectorVectorTextureCoordinate();
+    let vectorCoreSubMorphTarget = pCoreSubmesh.getVectorCoreSubMorphTarget();
+    let vectorSlaveAttribute = pCoreSubmesh.getVectorSlaveAttribute();
+
+    // write the core material thread id
+    dataSink.writeInteger(pCoreSubmesh.getCoreMaterialThreadId())?;
+
+    // write the number of vertices, faces, level-of-details and springs
+    dataSink.writeInteger(vectorVertex.len() as i32)?;
+    dataSink.writeInteger(vectorFace.len() as i32)?;
+    dataSink.writeInteger(pCoreSubmesh.getLodCount())?;
+    dataSink.writeInteger(vectorSpring.len() as i32)?;
+
+    // write the number of texture coordinates per vertex
+    let textureCoordinateCount = vectorvectorTextureCoordinate.len();
+    dataSink.writeInteger(textureCoordinateCount as i32)?;
+
+    // write the number of morph targets
+    if hasMorphTargetsInMorphFiles {
+        dataSink.writeInteger(vectorCoreSubMorphTarget.len() as i32)?;
+    }
+
+    // write the names and sizes of the vertex slave attributes
+    if hasVertexSlaveAttributes {
+        dataSink.writeInteger(vectorSlaveAttribute.len() as i32)?;
+        for slaveAttribute in vectorSlaveAttribute.iter() {
+            saveString(dataSink, "slave attribute name", &slaveAttribute.name)?;
+            dataSink.writeInteger(slaveAttribute.componentCount as i32)?;
+        }
+    }
+
+    // write all vertices
+    for (vertexId, vertex) in vectorVertex.iter().enumerate() {
+        // write the vertex data
+        CalVectorToDataSink(dataSink, &vertex.position)?;
+        CalVectorToDataSink(dataSink, &vertex.normal)?;
+        if hasVertexColors {
+            CalVectorToDataSink(dataSink, &vertex.vertexColor)?;
+        }
+        if hasVertexSlaveAttributes {
+            for slaveAttribute in vectorSlaveAttribute.iter() {
+                let value = slaveAttribute.getValue(vertexId).ok_or_else(|| {
+                    SaverError::FormatError(format!(
+                        "Vertex {vertexId} has no value for slave attribute {}",
+                        slaveAttribute.name
+                    ))
+                })?;
+                for component in value {
+                    dataSink.writeFloat(*component)?;
+                }
+            }
+        }
+        dataSink.writeInteger(vertex.collapseId)?;
+        dataSink.writeInteger(vertex.faceCollapseCount)?;
+
+        // write all texture coordinates of this vertex
+        for vectorTextureCoordinate in vectorvectorTextureCoordinate.iter() {
+            let textureCoordinate = vectorTextureCoordinate.get(vertexId).ok_or_else(|| {
+                SaverError::FormatError(format!("Vertex {vertexId} has no texture coordinate"))
+            })?;
+            dataSink.writeFloat(textureCoordinate.u)?;
+            dataSink.writeFloat(textureCoordinate.v)?;
+        }
+
+        // write the number of influences
+        dataSink.writeInteger(vertex.vectorInfluence.len() as i32)?;
+
+        // write all influences of this vertex
+        for influence in vertex.vectorInfluence.iter() {
+            dataSink.writeInteger(influence.boneId)?;
+            dataSink.writeFloat(influence.weight)?;
+        }
+
+        // save the physical property of the vertex if there are springs in the core submesh
+        if !vectorSpring.is_empty() {
+            let physicalProperty = vectorPhysicalProperty.get(vertexId).ok_or_else(|| {
+                SaverError::FormatError(format!("Vertex {vertexId} has no physical property"))
+            })?;
+            dataSink.writeFloat(physicalProperty.weight)?;
+        }
+    }
+
+    // write all springs
+    for spring in vectorSpring.iter() {
+        dataSink.writeInteger(spring.vertexId[0])?;
+        dataSink.writeInteger(spring.vertexId[1])?;
+        dataSink.writeFloat(spring.springCoefficient)?;
+        dataSink.writeFloat(spring.idleLength)?;
+    }
+
+    // write all morph targets
+    for morphTarget in vectorCoreSubMorphTarget.iter() {
+        saveString(dataSink, "morph target name", morphTarget.getName())?;
+
+        let vectorBlendVertex = morphTarget.getVectorBlendVertex();
+        let blendVertexIds = storedBlendVertexIds(morphTarget);
+
+        dataSink.writeInteger(blendVertexIds.len() as i32)?;
+
+        for blendVertexId in blendVertexIds {
+            let blendVertex = &vectorBlendVertex[blendVertexId];
+
+            dataSink.writeInteger(blendVertexId as i32)?;
+            CalVectorToDataSink(dataSink, &blendVertex.position)?;
+            CalVectorToDataSink(dataSink, &blendVertex.normal)?;
+
+            for textureCoordinateId in 0..textureCoordinateCount {
+                let textureCoordinate = blendVertex
+                    .textureCoords
+                    .get(textureCoordinateId)
+                    .cloned()
+                    .unwrap_or_default();
+                dataSink.writeFloat(textureCoordinate.u)?;
+                dataSink.writeFloat(textureCoordinate.v)?;
+            }
+        }
+    }
+
+    // write all faces
+    for face in vectorFace.iter() {
+        dataSink.writeInteger(face.vertexId[0])?;
+        dataSink.writeInteger(face.vertexId[1])?;
+        dataSink.writeInteger(face.vertexId[2])?;
+    }
+
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core track instance.
+ *
+ * This function saves a core track instance to a data sink.
+ *
+ * @param dataSink The data sink to save the core track instance to.
+ * @param pCoreTrack The core track instance that should be saved.
+ *****************************************************************************/
+fn saveCoreTrack(dataSink: &mut dyn DataSink, pCoreTrack: &CalCoreTrack) -> Result<(), SaverError> {
+    // write the bone id
+    dataSink.writeInteger(pCoreTrack.getCoreBoneId() as i32)?;
+
+    // write the number of keyframes
+    let keyframeCount = pCoreTrack.getCoreKeyframeCount();
+    if keyframeCount == 0 {
+        return Err(SaverError::FormatError(format!(
+            "Track for bone {} has no keyframes",
+            pCoreTrack.getCoreBoneId()
+        )));
+    }
+    dataSink.writeInteger(keyframeCount as i32)?;
+
+    // save all core keyframes
+    for keyframeId in 0..keyframeCount {
+        if let Some(pCoreKeyframe) = pCoreTrack.getCoreKeyframe(keyframeId) {
+            saveCoreKeyframe(dataSink, pCoreKeyframe)?;
+        }
+    }
+
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core track instance with compressed keyframes.
+ *
+ * This function saves a core track instance to a data sink. The bone id is
+ * stored in the low 13 bits of a 16 bit header, with the top 3 bits flagging
+ * whether translations are required, need the high range, and change between
+ * keyframes. A translation outside the low range forces the high range.
+ *
+ * @param dataSink The data sink to save the core track instance to.
+ * @param pCoreTrack The core track instance that should be saved.
+ *****************************************************************************/
+fn saveCompressedCoreTrack(
+    dataSink: &mut dyn DataSink,
+    pCoreTrack: &CalCoreTrack,
+) -> Result<(), SaverError> {
+    let coreBoneId = pCoreTrack.getCoreBoneId();
+    if coreBoneId > 0x1fff {
+        return Err(SaverError::FormatError(format!(
+            "Core bone ID {coreBoneId} does not fit in a compressed track"
+        )));
+    }
+
+    let keyframeCount = pCoreTrack.getCoreKeyframeCount();
+    if keyframeCount == 0 || keyframeCount > loader::keyframeTimeMax as usize {
+        return Err(SaverError::FormatError(format!(
+            "Track for bone {coreBoneId} has {keyframeCount} keyframes"
+        )));
+    }
+
+    let keyframes = (0..keyframeCount)
+        .filter_map(|keyframeId| pCoreTrack.getCoreKeyframe(keyframeId))
+        .collect::<Vec<_>>();
+
+    let translationRequired = pCoreTrack.getTranslationRequired();
+    let translationIsDynamic = pCoreTrack.getTranslationIsDynamic();
+    let highRangeRequired = pCoreTrack.getHighRangeRequired()
+        || keyframes
+            .iter()
+            .any(|keyframe| !fitsLowRange(keyframe.getTranslation()));
+
+    // write the bone id and flags, low byte first, then the keyframe count
+    let mut flags = 0;
+    if translationRequired {
+        flags |= 0x80;
+    }
+    if highRangeRequired {
+        flags |= 0x40;
+    }
+    if translationIsDynamic {
+        flags |= 0x20;
+    }
+    dataSink.writeBytes(&[
+        (coreBoneId & 0xff) as u8,
+        (coreBoneId >> 8) as u8 | flags,
+        (keyframeCount & 0xff) as u8,
+        (keyframeCount >> 8) as u8,
+    ])?;
+
+    // save all core keyframes
+    let mut lastFrame = None;
+    for (keyframeId, pCoreKeyframe) in keyframes.iter().enumerate() {
+        let time = pCoreKeyframe.getTime();
+        let frame = (time * loader::keyframeTimeResolution).round();
+        if !(0.0..=loader::keyframeTimeMax as f32).contains(&frame) {
+            return Err(SaverError::FormatError(format!(
+                "Keyframe time {time} does not fit in a compressed track"
+            )));
+        }
+        let frame = frame as u16;
+        if lastFrame == Some(frame) {
+            return Err(SaverError::FormatError(format!(
+                "Keyframe at time {time} of track for bone {coreBoneId} falls on the same frame as the previous one"
+            )));
+        }
+        lastFrame = Some(frame);
+
+        // translations that are not dynamic are only written once
+        if translationRequired && (keyframeId == 0 || translationIsDynamic) {
+            let translation = pCoreKeyframe.getTranslation();
+            if highRangeRequired {
+                for value in [translation.x, translation.y, translation.z] {
+                    dataSink.writeBytes(&value.to_le_bytes())?;
+                }
+            } else {
+                let mask = (1u64 << loader::translationLowRangeBits) - 1;
+                let bits = [translation.x, translation.y, translation.z]
+                    .iter()
+                    .enumerate()
+                    .fold(0u64, |bits, (i, value)| {
+                        let raw = (value * loader::translationLowRangeScale).round() as i64 as u64;
+                        bits | (raw & mask) << (i as u32 * loader::translationLowRangeBits)
+                    });
+                dataSink.writeBytes(&bits.to_le_bytes())?;
+            }
+        }
+
+        dataSink
+            .writeBytes(&compressedRotationBits(pCoreKeyframe.getRotation()).to_le_bytes()[..6])?;
+        dataSink.writeBytes(&frame.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Checks whether a translation can be stored as fixed point in the low range.
+fn fitsLowRange(translation: &CalVector<f32>) -> bool {
+    let limit = (1i64 << (loader::translationLowRangeBits - 1)) as f32;
+    [translation.x, translation.y, translation.z]
+        .iter()
+        .all(|value| {
+            let raw = (value * loader::translationLowRangeScale).round();
+            raw >= -limit && raw < limit
+        })
+}
+
+/// Packs a rotation into 48 bits: the index of its largest component in the
+/// low 2 bits, followed by the other three components in 15 bits each. The
+/// quaternion is negated if needed so the dropped component is positive.
+fn compressedRotationBits(rotation: &CalQuaternion<f32>) -> u64 {
+    let rotation = rotation.normalize();
+    let mut components = [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s];
+
+    let mut largest = 0;
+    for i in 1..components.len() {
+        if components[i].abs() > components[largest].abs() {
+            largest = i;
+        }
+    }
+    if components[largest] < 0.0 {
+        components
+            .iter_mut()
+            .for_each(|component| *component = -*component);
+    }
+
+    let maxValue = ((1u64 << loader::rotationComponentBits) - 1) as f32;
+    let mut bits = largest as u64;
+    let mut shift = 2;
+    for (i, component) in components.iter().enumerate() {
+        if i == largest {
+            continue;
+        }
+        let scaled = (component / loader::rotationComponentRange + 1.0) / 2.0 * maxValue;
+        bits |= (scaled.round().clamp(0.0, maxValue) as u64) << shift;
+        shift += loader::rotationComponentBits;
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use cgmath::Rotation3;
+
+    use super::*;
+    use crate::core::datasource::Endianness;
+    use crate::core::loader::LoaderOptions;
+    use crate::core::{MemorySource, testdata};
+
+    /// Builds a track of keyframes 1/30 s apart, turning about a changing
+    /// axis while moving in all three directions.
+    fn wanderingTrack(boneId: usize, highRangeRequired: bool) -> CalCoreTrack {
+        let keyframes = (0..40)
+            .map(|i| {
+                let i = i as f32;
+                let axis = CalVector::new(i.sin(), (i * 0.7).cos(), 0.3).normalize();
+                let mut rotation = CalQuaternion::from_axis_angle(axis, cgmath::Rad(i * 0.37));
+                // both signs of a quaternion describe the same rotation
+                if i as i32 % 3 == 0 {
+                    rotation = -rotation;
+                }
+                let translation = CalVector::new((i * 0.31).sin(), -1.5 * i, 0.123);
+                Rc::new(CalCoreKeyframe::new(i / 30.0, translation, rotation))
+            })
+            .collect();
+        CalCoreTrack::new(boneId, true, highRangeRequired, true, keyframes)
+    }
+
+    #[test]
+    fn compressed_tracks_are_within_quantization_error() {
+        let skeleton = testdata::skeleton();
+        let animation = CalCoreAnimation::new(
+            40.0 / 30.0,
+            vec![
+                Rc::new(RefCell::new(wanderingTrack(0, false))),
+                Rc::new(RefCell::new(wanderingTrack(1, true))),
+            ],
+        );
+
+        let data = testdata::saveToBuffer(Endianness::LittleEndian, |s| {
+            saveCompressedCoreAnimationToSink(s, &animation)
+        });
+        let loaded = loader::loadCoreAnimationFromSource(
+            &mut MemorySource::new(data),
+            &skeleton,
+            &LoaderOptions::default(),
+        )
+        .unwrap();
+        let loaded = loaded.borrow();
+
+        // low range translations are fixed point with 21 bits per component,
+        // 9 of them fractional, so each component is off by half a step
+        let translationStep = 1.0 / loader::translationLowRangeScale;
+        let maxTranslationError = 3f32.sqrt() * translationStep / 2.0;
+        // the three smallest rotation components are stored in 15 bits each
+        // and are off by half a step, and the largest one derived from them by
+        // at most three half steps
+        let rotationStep = 2.0 * loader::rotationComponentRange
+            / ((1 << loader::rotationComponentBits) - 1) as f32;
+        let maxRotationError = 3f32.sqrt() * rotationStep;
+
+        for (track, loadedTrack) in animation
+            .getListCoreTrack()
+            .iter()
+            .zip(loaded.getListCoreTrack())
+        {
+            let (track, loadedTrack) = (track.borrow(), loadedTrack.borrow());
+            let highRange = track.getHighRangeRequired();
+            assert_eq!(loadedTrack.getCoreBoneId(), track.getCoreBoneId());
+            assert_eq!(loadedTrack.getHighRangeRequired(), highRange);
+            assert_eq!(
+                loadedTrack.getCoreKeyframeCount(),
+                track.getCoreKeyframeCount()
+            );
+
+            for keyframeId in 0..track.getCoreKeyframeCount() {
+                let keyframe = track.getCoreKeyframe(keyframeId).unwrap();
+                let loadedKeyframe = loadedTrack.getCoreKeyframe(keyframeId).unwrap();
+                assert!((keyframe.getTime() - loadedKeyframe.getTime()).abs() < 1e-5);
+
+                let translationError =
+                    (keyframe.getTranslation() - loadedKeyframe.getTranslation()).magnitude();
+                if highRange {
+                    assert_eq!(translationError, 0.0);
+                } else {
+                    assert!(
+                        translationError <= maxTranslationError,
+                        "translation of keyframe {keyframeId} is off by {translationError}"
+                    );
+                }
+
+                // the distance between the rotations, whatever their signs
+                let (rotation, loadedRotation) =
+                    (*keyframe.getRotation(), *loadedKeyframe.getRotation());
+                let rotationError = (rotation - loadedRotation)
+                    .magnitude()
+                    .min((rotation + loadedRotation).magnitude());
+                assert!(
+                    rotationError <= maxRotationError,
+                    "rotation of keyframe {keyframeId} is off by {rotationError}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn strings_the_loader_would_refuse_are_not_saved() {
+        let mut material = testdata::material();
+        let longest = "m".repeat(maxStringLength as usize - 1);
+        material.getMapsMut()[0].strFilename = longest.clone();
+        let data = testdata::saveToBuffer(Endianness::LittleEndian, |s| {
+            saveCoreMaterialToSink(s, &material)
+        });
+        let loaded = loader::loadCoreMaterialFromSource(
+            &mut MemorySource::new(data),
+            &LoaderOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(loaded.getMaps()[0].strFilename, longest);
+
+        for filename in [format!("{longest}m"), String::from("skin\0.png")] {
+            material.getMapsMut()[0].strFilename = filename;
+            let mut sink = WriterSink::new(Vec::new());
+            let error = saveCoreMaterialToSink(&mut sink, &material).unwrap_err();
+            let SaverError::FormatError(message) = error else {
+                panic!("{error}");
+            };
+            assert!(message.starts_with("map filename"), "{message}");
+        }
+    }
+}
@@ -24,6 +24,23 @@ impl CalCoreSkeleton {
         &self.m_vectorRootCoreBoneId
     }
 
+    //130
+    /*****************************************************************************/
+    /** Returns the ID of a specified core bone.
+     *
+     * This function returns the ID of a specified core bone.
+     *
+     * @param strName The name of the core bone that should be returned.
+     *
+     * @return One of the following values:
+     *         \li the \b ID of the core bone
+     *         \li \b None if an error happened
+     *****************************************************************************/
+    pub fn getCoreBoneId(&self, strName: &str) -> Option<usize> {
+        //Check to make sure the mapping exists
+        self.m_mapCoreBoneNames.get(strName).copied()
+    }
+
     //43
     /*****************************************************************************/
     /** Adds a core bone.
@@ -62,12 +79,30 @@ impl CalCoreSkeleton {
      * calculating all the core bone states.
      *****************************************************************************/
     pub fn calculateState(&mut self) {
-        // calculate all bone states of the skeleton
-
-        for iteratorRootCoreBoneId in self.m_vectorRootCoreBoneId.iter() {
-            self.m_vectorCoreBone[*iteratorRootCoreBoneId as usize]
-                .borrow_mut()
-                .calculateState();
+        // calculate all bone states of the skeleton, parents before their
+        // children; the bones are walked here rather than recursively so that
+        // no bone is borrowed while its children are updated
+        let mut visited = vec![false; self.m_vectorCoreBone.len()];
+        let mut pending = self.m_vectorRootCoreBoneId.clone();
+        while let Some(boneId) = pending.pop() {
+            if boneId >= visited.len() || visited[boneId] {
+                eprintln!("Invalid child bone Id {boneId} in calculateState");
+                continue;
+            }
+            visited[boneId] = true;
+
+            let mut bone = self.m_vectorCoreBone[boneId].borrow_mut();
+            let parent = usize::try_from(bone.getParentId())
+                .ok()
+                .and_then(|parentId| self.m_vectorCoreBone.get(parentId))
+                .filter(|_| bone.getParentId() as usize != boneId)
+                .map(|parent| parent.borrow());
+            bone.calculateStateFromParent(parent.as_deref());
+            pending.extend(
+                bone.getListChildId()
+                    .iter()
+                    .filter_map(|&childId| usize::try_from(childId).ok()),
+            );
         }
     }
 
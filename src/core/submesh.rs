@@ -53,7 +53,7 @@ pub struct Influence {
 
 #[derive(Clone, Default)]
 pub struct PhysicalProperty {
-    weight: f32,
+    pub weight: f32,
 }
 
 impl PhysicalProperty {
@@ -99,9 +99,9 @@ impl Face {
 /// The core submesh Spring.
 #[derive(Clone, Default)]
 pub struct Spring {
-    vertexId: [i32; 2],
-    springCoefficient: f32,
-    idleLength: f32,
+    pub vertexId: [i32; 2],
+    pub springCoefficient: f32,
+    pub idleLength: f32,
 }
 
 impl Spring {
@@ -114,6 +114,72 @@ impl Spring {
     }
 }
 
+/// A per-vertex attribute with no meaning to Cal3D, such as a colour or a
+/// wetness mask, which is interpolated linearly between vertices.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SlaveAttribute {
+    pub name: String,
+    pub componentCount: usize,
+    /// The components of every vertex, one vertex after the other.
+    pub values: Vec<f32>,
+}
+
+impl SlaveAttribute {
+    pub fn new(name: String, componentCount: usize, vertexCount: usize) -> Self {
+        SlaveAttribute {
+            name,
+            componentCount,
+            values: vec![0.0; componentCount * vertexCount],
+        }
+    }
+
+    /// Returns the number of vertices the attribute has values for.
+    pub fn getVertexCount(&self) -> usize {
+        match self.componentCount {
+            0 => 0,
+            componentCount => self.values.len() / componentCount,
+        }
+    }
+
+    /// Returns the components of a vertex, if it exists.
+    pub fn getValue(&self, vertexId: usize) -> Option<&[f32]> {
+        let start = vertexId.checked_mul(self.componentCount)?;
+        self.values.get(start..start + self.componentCount)
+    }
+
+    /// Sets the components of a vertex, returning false if the vertex does
+    /// not exist or the number of components is wrong.
+    pub fn setValue(&mut self, vertexId: usize, value: &[f32]) -> bool {
+        if value.len() != self.componentCount {
+            return false;
+        }
+        let Some(start) = vertexId.checked_mul(self.componentCount) else {
+            return false;
+        };
+        match self.values.get_mut(start..start + self.componentCount) {
+            Some(components) => {
+                components.copy_from_slice(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the components linearly interpolated from one vertex, at a
+    /// factor of 0, to another, at a factor of 1.
+    pub fn interpolate(&self, vertexId0: usize, vertexId1: usize, factor: f32) -> Option<Vec<f32>> {
+        let value0 = self.getValue(vertexId0)?;
+        let value1 = self.getValue(vertexId1)?;
+        Some(
+            value0
+                .iter()
+                .zip(value1.iter())
+                .map(|(a, b)| a + (b - a) * factor)
+                .collect(),
+        )
+    }
+}
+
 #[derive(Default)]
 pub struct CalCoreSubmesh {
     m_vectorVertex: Vec<Vertex>,
@@ -128,6 +194,7 @@ pub struct CalCoreSubmesh {
     m_lodCount: i32,
     m_vectorSubMorphTargetGroupIndex: Vec<u32>,
     m_hasNonWhiteVertexColors: bool,
+    m_vectorSlaveAttribute: Vec<SlaveAttribute>,
 }
 
 impl CalCoreSubmesh {
@@ -178,6 +245,17 @@ impl CalCoreSubmesh {
         self.m_coreMaterialThreadId
     }
 
+    /*****************************************************************************/
+    /** Returns the number of LOD steps.
+     *
+     * This function returns the number of LOD steps in the core submesh instance.
+     *
+     * @return The number of LOD steps.
+     *****************************************************************************/
+    pub fn getLodCount(&self) -> i32 {
+        self.m_lodCount
+    }
+
     // 136 cpp
     /*****************************************************************************/
     /** Returns the number of springs.
@@ -404,6 +482,11 @@ impl CalCoreSubmesh {
         self.m_vectorvectorTextureCoordinate = texture_coordinates;
     }
 
+    /// Replaces all the faces, for loaders that only know them once read.
+    pub fn setAllFaces(&mut self, faces: Vec<Face>) {
+        self.m_vectorFace = faces;
+    }
+
     //683
     /*****************************************************************************/
     /** Adds a core sub morph target.
@@ -448,4 +531,114 @@ impl CalCoreSubmesh {
     pub fn getVectorVertexMut(&mut self) -> &mut Vec<Vertex> {
         &mut self.m_vectorVertex
     }
+
+    /*****************************************************************************/
+    /** Returns the face vector.
+     *
+     * This function returns the vector that contains all faces of the core
+     * submesh instance.
+     *
+     * @return A reference to the face vector.
+     *****************************************************************************/
+    pub fn getVectorFace(&self) -> &Vec<Face> {
+        &self.m_vectorFace
+    }
+
+    /*****************************************************************************/
+    /** Returns the physical property vector.
+     *
+     * This function returns the vector that contains all physical properties of
+     * the core submesh instance.
+     *
+     * @return A reference to the physical property vector.
+     *****************************************************************************/
+    pub fn getVectorPhysicalProperty(&self) -> &Vec<PhysicalProperty> {
+        &self.m_vectorPhysicalProperty
+    }
+
+    /*****************************************************************************/
+    /** Returns the spring vector.
+     *
+     * This function returns the vector that contains all springs of the core
+     * submesh instance.
+     *
+     * @return A reference to the spring vector.
+     *****************************************************************************/
+    pub fn getVectorSpring(&self) -> &Vec<Spring> {
+        &self.m_vectorSpring
+    }
+
+    /*****************************************************************************/
+    /** Returns the texture coordinate vector-vector.
+     *
+     * This function returns the vector that contains all texture coordinate
+     * vectors of the core submesh instance. This vector contains another vector
+     * because there can be more than one texture map at each vertex.
+     *
+     * @return A reference to the texture coordinate vector-vector.
+     *****************************************************************************/
+    pub fn getVectorVectorTextureCoordinate(&self) -> &Vec<Vec<TextureCoordinate>> {
+        &self.m_vectorvectorTextureCoordinate
+    }
+
+    /*****************************************************************************/
+    /** Returns the core sub morph target vector.
+     *
+     * This function returns the vector that contains all core sub morph targets
+     * of the core submesh instance.
+     *
+     * @return A reference to the core sub morph target vector.
+     *****************************************************************************/
+    pub fn getVectorCoreSubMorphTarget(&self) -> &Vec<CalCoreSubMorphTarget> {
+        &self.m_vectorCoreSubMorphTarget
+    }
+
+    /*****************************************************************************/
+    /** Adds a slave attribute.
+     *
+     * This function adds a per-vertex slave attribute to the core submesh
+     * instance. The attribute must have values for every vertex.
+     *
+     * @param slaveAttribute The slave attribute that should be added.
+     *
+     * @return One of the following values:
+     *         \li the assigned \b ID of the added slave attribute
+     *         \li \b None if the attribute does not match the vertices
+     *****************************************************************************/
+    pub fn addSlaveAttribute(&mut self, slaveAttribute: SlaveAttribute) -> Option<usize> {
+        if slaveAttribute.componentCount == 0
+            || slaveAttribute.values.len()
+                != slaveAttribute.componentCount * self.m_vectorVertex.len()
+        {
+            return None;
+        }
+
+        let slaveAttributeId = self.m_vectorSlaveAttribute.len();
+        self.m_vectorSlaveAttribute.push(slaveAttribute);
+
+        Some(slaveAttributeId)
+    }
+
+    /*****************************************************************************/
+    /** Returns the slave attribute vector.
+     *
+     * This function returns the vector that contains all per-vertex slave
+     * attributes of the core submesh instance.
+     *
+     * @return A reference to the slave attribute vector.
+     *****************************************************************************/
+    pub fn getVectorSlaveAttribute(&self) -> &Vec<SlaveAttribute> {
+        &self.m_vectorSlaveAttribute
+    }
+
+    pub fn getVectorSlaveAttributeMut(&mut self) -> &mut Vec<SlaveAttribute> {
+        &mut self.m_vectorSlaveAttribute
+    }
+
+    /// Returns the slave attribute with the given name, if there is one.
+    pub fn getSlaveAttribute(&self, name: &str) -> Option<&SlaveAttribute> {
+        self.m_vectorSlaveAttribute
+            .iter()
+            .find(|slaveAttribute| slaveAttribute.name == name)
+    }
 }
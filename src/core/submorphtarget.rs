@@ -61,6 +61,22 @@ impl CalCoreSubMorphTarget {
         self.m_morphTargetID = i;
     }
 
+    pub fn getName(&self) -> &str {
+        &self.m_name
+    }
+
+    /*****************************************************************************/
+    /** Returns the blend vertex vector.
+     *
+     * This function returns the vector that contains all blend vertices of the
+     * core sub morph target instance.
+     *
+     * @return A reference to the blend vertex vector.
+     *****************************************************************************/
+    pub fn getVectorBlendVertex(&self) -> &Vec<BlendVertex> {
+        &self.m_vectorBlendVertex
+    }
+
     //120
     /*****************************************************************************/
     /** Sets a specified blend vertex.
@@ -0,0 +1,273 @@
+//! Small assets shared by the tests of the loaders and savers, written in the
+//! XML formats and converted to the binary ones by the savers.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::datasink::DataSink;
+use super::datasource::Endianness;
+use super::loader::{self, LoaderOptions};
+use super::saver;
+use super::writersink::WriterSink;
+use super::{CalCoreAnimatedMorph, CalCoreAnimation, CalCoreMaterial, CalCoreMesh};
+use super::{CalCoreSkeleton, SaverError};
+
+pub(crate) const SKELETON_XML: &str = r#"<HEADER MAGIC="XSF" VERSION="1400" />
+<SKELETON NUMBONES="2">
+    <BONE ID="0" NAME="root" NUMCHILDS="1">
+        <TRANSLATION>1 2 3</TRANSLATION>
+        <ROTATION>0 0 0 1</ROTATION>
+        <LOCALTRANSLATION>-1 -2 -3</LOCALTRANSLATION>
+        <LOCALROTATION>0 0 0 1</LOCALROTATION>
+        <PARENTID>-1</PARENTID>
+        <CHILDID>1</CHILDID>
+    </BONE>
+    <BONE ID="1" NAME="child" NUMCHILDS="0">
+        <TRANSLATION>0 1 0</TRANSLATION>
+        <ROTATION>0 0 0.7071068 0.7071068</ROTATION>
+        <LOCALTRANSLATION>0 0 0</LOCALTRANSLATION>
+        <LOCALROTATION>0 0 0 1</LOCALROTATION>
+        <PARENTID>0</PARENTID>
+    </BONE>
+</SKELETON>
+"#;
+
+pub(crate) const ANIMATION_XML: &str = r#"<HEADER MAGIC="XAF" VERSION="1400" />
+<ANIMATION DURATION="1" NUMTRACKS="2">
+    <TRACK BONEID="0" NUMKEYFRAMES="2" TRANSLATIONREQUIRED="1" HIGHRANGEREQUIRED="1" TRANSLATIONISDYNAMIC="1">
+        <KEYFRAME TIME="0">
+            <TRANSLATION>1 2 3</TRANSLATION>
+            <ROTATION>0 0 0 1</ROTATION>
+        </KEYFRAME>
+        <KEYFRAME TIME="1">
+            <TRANSLATION>1 2.5 3</TRANSLATION>
+            <ROTATION>0.258819 0 0 0.9659258</ROTATION>
+        </KEYFRAME>
+    </TRACK>
+    <TRACK BONEID="1" NUMKEYFRAMES="3" TRANSLATIONREQUIRED="1" HIGHRANGEREQUIRED="1" TRANSLATIONISDYNAMIC="1">
+        <KEYFRAME TIME="0">
+            <TRANSLATION>1 1 1</TRANSLATION>
+            <ROTATION>0 0 0 1</ROTATION>
+        </KEYFRAME>
+        <KEYFRAME TIME="0.5">
+            <TRANSLATION>0 1 0</TRANSLATION>
+            <ROTATION>0 0 0.7071068 0.7071068</ROTATION>
+        </KEYFRAME>
+        <KEYFRAME TIME="1">
+            <TRANSLATION>0 1 0</TRANSLATION>
+            <ROTATION>0 0 0 1</ROTATION>
+        </KEYFRAME>
+    </TRACK>
+</ANIMATION>
+"#;
+
+pub(crate) const MESH_XML: &str = r#"<HEADER MAGIC="XMF" VERSION="1400" />
+<MESH NUMSUBMESH="1">
+    <SUBMESH NUMVERTICES="3" NUMFACES="1" NUMLODSTEPS="0" NUMSPRINGS="1" NUMMORPHS="1" NUMTEXCOORDS="1" MATERIAL="0">
+        <SLAVEATTRIBUTE NAME="wetness" COMPONENTS="2" />
+        <VERTEX ID="0" NUMINFLUENCES="1">
+            <POS>0 0 0</POS>
+            <NORM>0 0 1</NORM>
+            <COLOR>1 1 1</COLOR>
+            <SLAVE>0 0.5</SLAVE>
+            <COLLAPSEID>-1</COLLAPSEID>
+            <COLLAPSECOUNT>0</COLLAPSECOUNT>
+            <TEXCOORD>0 0</TEXCOORD>
+            <INFLUENCE ID="0">1</INFLUENCE>
+            <PHYSIQUE>0.5</PHYSIQUE>
+        </VERTEX>
+        <VERTEX ID="1" NUMINFLUENCES="2">
+            <POS>1 0 0</POS>
+            <NORM>0 0 1</NORM>
+            <COLOR>1 1 1</COLOR>
+            <SLAVE>1 0.5</SLAVE>
+            <COLLAPSEID>0</COLLAPSEID>
+            <COLLAPSECOUNT>1</COLLAPSECOUNT>
+            <TEXCOORD>1 0</TEXCOORD>
+            <INFLUENCE ID="0">0.5</INFLUENCE>
+            <INFLUENCE ID="1">0.5</INFLUENCE>
+            <PHYSIQUE>0.5</PHYSIQUE>
+        </VERTEX>
+        <VERTEX ID="2" NUMINFLUENCES="1">
+            <POS>0 1 0</POS>
+            <NORM>0 0 1</NORM>
+            <COLOR>1 1 1</COLOR>
+            <SLAVE>2 0.5</SLAVE>
+            <COLLAPSEID>-1</COLLAPSEID>
+            <COLLAPSECOUNT>0</COLLAPSECOUNT>
+            <TEXCOORD>0 1</TEXCOORD>
+            <INFLUENCE ID="1">1</INFLUENCE>
+            <PHYSIQUE>0.5</PHYSIQUE>
+        </VERTEX>
+        <SPRING VERTEXID="0 1" COEF="2" LENGTH="1" />
+        <MORPH NAME="smile" NUMBLENDVERTS="1" MORPHID="0">
+            <BLENDVERTEX VERTEXID="2">
+                <POSITION>0 2 0</POSITION>
+                <NORMAL>0 0 1</NORMAL>
+                <TEXCOORD>0 1</TEXCOORD>
+            </BLENDVERTEX>
+        </MORPH>
+        <FACE VERTEXID="0 1 2" />
+    </SUBMESH>
+</MESH>
+"#;
+
+pub(crate) const MATERIAL_XML: &str = r#"<HEADER MAGIC="XRF" VERSION="1400" />
+<MATERIAL NUMMAPS="2">
+    <AMBIENT>1 2 3 4</AMBIENT>
+    <DIFFUSE>5 6 7 8</DIFFUSE>
+    <SPECULAR>9 10 11 12</SPECULAR>
+    <SHININESS>0.5</SHININESS>
+    <MAP TYPE="Diffuse Color">skin.png</MAP>
+    <MAP TYPE="Normal Map">skin_normal.png</MAP>
+</MATERIAL>
+"#;
+
+pub(crate) const ANIMATED_MORPH_XML: &str = r#"<HEADER MAGIC="XPF" VERSION="1000" />
+<ANIMATION NUMTRACKS="2" DURATION="2">
+  <TRACK NUMKEYFRAMES="3" MORPHNAME="smile">
+    <KEYFRAME TIME="0"><WEIGHT>0</WEIGHT></KEYFRAME>
+    <KEYFRAME TIME="1"><WEIGHT>1</WEIGHT></KEYFRAME>
+    <KEYFRAME TIME="2"><WEIGHT>0.5</WEIGHT></KEYFRAME>
+  </TRACK>
+  <TRACK NUMKEYFRAMES="1" MORPHNAME="blink">
+    <KEYFRAME TIME="0.5"><WEIGHT>1</WEIGHT></KEYFRAME>
+  </TRACK>
+</ANIMATION>
+"#;
+
+pub(crate) fn skeleton() -> Rc<RefCell<CalCoreSkeleton>> {
+    let skeleton = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+    loader::loadCoreSkeletonFromReader(
+        SKELETON_XML.as_bytes(),
+        &skeleton,
+        &LoaderOptions::default(),
+    )
+    .unwrap();
+    skeleton
+}
+
+pub(crate) fn animation(skeleton: &Rc<RefCell<CalCoreSkeleton>>) -> Rc<RefCell<CalCoreAnimation>> {
+    loader::loadCoreAnimationFromReader(
+        ANIMATION_XML.as_bytes(),
+        skeleton,
+        &LoaderOptions::default(),
+    )
+    .unwrap()
+}
+
+pub(crate) fn mesh() -> Rc<RefCell<CalCoreMesh>> {
+    loader::loadCoreMeshFromReader(MESH_XML.as_bytes(), &LoaderOptions::default()).unwrap()
+}
+
+pub(crate) fn material() -> CalCoreMaterial {
+    loader::loadCoreMaterialFromReader(MATERIAL_XML.as_bytes(), &LoaderOptions::default()).unwrap()
+}
+
+pub(crate) fn animatedMorph() -> Rc<RefCell<CalCoreAnimatedMorph>> {
+    loader::loadCoreAnimatedMorphFromReader(
+        ANIMATED_MORPH_XML.as_bytes(),
+        &LoaderOptions::default(),
+    )
+    .unwrap()
+}
+
+/// Saves an asset to a buffer in the given byte order.
+pub(crate) fn saveToBuffer(
+    endianness: Endianness,
+    save: impl FnOnce(&mut dyn DataSink) -> Result<(), SaverError>,
+) -> Vec<u8> {
+    let mut sink = WriterSink::with_endianness(Vec::new(), endianness);
+    save(&mut sink).unwrap();
+    sink.into_inner()
+}
+
+/// Saves an animated morph in the binary format, which the savers do not
+/// write.
+pub(crate) fn saveAnimatedMorphToBuffer(
+    endianness: Endianness,
+    animatedMorph: &CalCoreAnimatedMorph,
+) -> Vec<u8> {
+    saveToBuffer(endianness, |dataSink| {
+        dataSink.writeBytes(loader::ANIMATEDMORPH_FILE_MAGIC)?;
+        dataSink.writeInteger(loader::CURRENT_FILE_VERSION)?;
+        dataSink.writeFloat(animatedMorph.getDuration())?;
+        dataSink.writeInteger(animatedMorph.getListCoreTrack().len() as i32)?;
+        for track in animatedMorph.getListCoreTrack() {
+            dataSink.writeString(track.getMorphName())?;
+            dataSink.writeInteger(track.getCoreMorphKeyframeCount() as i32)?;
+            for keyframe in track.getVectorCoreMorphKeyframes() {
+                dataSink.writeFloat(keyframe.getTime())?;
+                dataSink.writeFloat(keyframe.getWeight())?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// The sample assets in the binary formats, by file extension.
+pub(crate) fn binaryFiles(endianness: Endianness) -> Vec<(&'static str, Vec<u8>)> {
+    let skeleton = skeleton();
+    let animation = animation(&skeleton);
+    vec![
+        (
+            "csf",
+            saveToBuffer(endianness, |s| {
+                saver::saveCoreSkeletonToSink(s, &skeleton.borrow())
+            }),
+        ),
+        (
+            "caf",
+            saveToBuffer(endianness, |s| {
+                saver::saveCoreAnimationToSink(s, &animation.borrow())
+            }),
+        ),
+        (
+            "caf",
+            saveToBuffer(endianness, |s| {
+                saver::saveCompressedCoreAnimationToSink(s, &animation.borrow())
+            }),
+        ),
+        (
+            "cmf",
+            saveToBuffer(endianness, |s| {
+                saver::saveCoreMeshToSink(s, &mesh().borrow())
+            }),
+        ),
+        (
+            "crf",
+            saveToBuffer(endianness, |s| {
+                saver::saveCoreMaterialToSink(s, &material())
+            }),
+        ),
+        (
+            "cpf",
+            saveAnimatedMorphToBuffer(endianness, &animatedMorph().borrow()),
+        ),
+    ]
+}
+
+/// Packs files into an uncompressed ustar archive.
+pub(crate) fn tarArchive(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    for (name, contents) in files {
+        let mut header = [0; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{:011o}", contents.len()).as_bytes());
+        header[156] = b'0';
+        header[257..265].copy_from_slice(b"ustar\x0000");
+
+        // the checksum is summed with its own field as spaces
+        header[148..156].fill(b' ');
+        let checksum: usize = header.iter().map(|&b| b as usize).sum();
+        header[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(contents);
+        archive.resize(archive.len().next_multiple_of(512), 0);
+    }
+    // the archive ends with two blocks of zeros
+    archive.resize(archive.len() + 1024, 0);
+    archive
+}
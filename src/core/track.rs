@@ -2,6 +2,7 @@ use super::keyframe::CalCoreKeyframe;
 use super::skeleton::CalCoreSkeleton;
 use crate::vector::blend::Blend;
 use crate::{CalQuaternion, CalVector};
+use cgmath::InnerSpace;
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -19,6 +20,9 @@ pub struct CalCoreTrack {
 
     // /// List of keyframes, always sorted by time.
     m_keyframes: Vec<Rc<CalCoreKeyframe>>,
+
+    /// The number of keyframes removed by collapseSequences().
+    m_collapsedKeyframeCount: usize,
 }
 
 impl CalCoreTrack {
@@ -35,6 +39,7 @@ impl CalCoreTrack {
             m_highRangeRequired,
             m_translationIsDynamic,
             m_keyframes,
+            m_collapsedKeyframeCount: 0,
         }
     }
 
@@ -46,6 +51,26 @@ impl CalCoreTrack {
         self.m_translationRequired
     }
 
+    pub fn getHighRangeRequired(&self) -> bool {
+        self.m_highRangeRequired
+    }
+
+    pub fn getTranslationIsDynamic(&self) -> bool {
+        self.m_translationIsDynamic
+    }
+
+    pub fn setTranslationRequired(&mut self, translationRequired: bool) {
+        self.m_translationRequired = translationRequired;
+    }
+
+    pub fn setHighRangeRequired(&mut self, highRangeRequired: bool) {
+        self.m_highRangeRequired = highRangeRequired;
+    }
+
+    pub fn setTranslationIsDynamic(&mut self, translationIsDynamic: bool) {
+        self.m_translationIsDynamic = translationIsDynamic;
+    }
+
     // 73
     pub fn addCoreKeyframe(&mut self, pCoreKeyframe: CalCoreKeyframe) -> bool {
         self.m_keyframes.push(Rc::new(pCoreKeyframe));
@@ -58,18 +83,182 @@ impl CalCoreTrack {
     }
 
     //226
+    /*****************************************************************************/
+    /** Compresses the core track.
+     *
+     * This function removes every keyframe that blending between the keyframes
+     * kept around it reproduces within the given tolerances. The first and last
+     * keyframes are always kept. Afterwards the track is marked as not needing
+     * translations if they all match the translation of the core bone in the
+     * skeleton, and as having a static translation if they all match each other.
+     *
+     * @param translationTolerance The distance a reproduced translation may be
+     *                             off by.
+     * @param rotationToleranceDegrees The angle in degrees a reproduced rotation
+     *                                 may be off by.
+     * @param skel The skeleton holding the core bone of the track.
+     *
+     * @return The number of keyframes removed.
+     *****************************************************************************/
     pub fn compress(
-        &self,
+        &mut self,
         translationTolerance: f64,
         rotationToleranceDegrees: f64,
         skel: &Rc<RefCell<CalCoreSkeleton>>,
-    ) {
-        todo!();
+    ) -> usize {
+        let translationTolerance = translationTolerance as f32;
+        let rotationTolerance = (rotationToleranceDegrees as f32).to_radians();
+        let keyframeCount = self.m_keyframes.len();
+
+        if self.m_keyframes.len() > 2 {
+            let last = self.m_keyframes.len() - 1;
+            let mut output = vec![self.m_keyframes[0].clone()];
+
+            let mut start = 0;
+            while start < last {
+                // find the furthest keyframe the ones in between can be blended from
+                let mut end = start + 1;
+                while end < last
+                    && self.keyframesReproduced(
+                        start,
+                        end + 1,
+                        translationTolerance,
+                        rotationTolerance,
+                    )
+                {
+                    end += 1;
+                }
+
+                output.push(self.m_keyframes[end].clone());
+                start = end;
+            }
+
+            self.m_keyframes = output;
+        }
+
+        let withinTolerance =
+            |a: &CalVector<f32>, b: &CalVector<f32>| (a - b).magnitude() <= translationTolerance;
+
+        // The translation status can go from required to not required, but
+        // not the other way around.
+        if let Some(coreBone) = skel.borrow().getCoreBone(self.m_coreBoneId) {
+            let boneTranslation = *coreBone.borrow().getTranslation();
+            if self
+                .m_keyframes
+                .iter()
+                .all(|keyframe| withinTolerance(keyframe.getTranslation(), &boneTranslation))
+            {
+                self.m_translationRequired = false;
+            }
+        }
+
+        if let Some(first) = self.m_keyframes.first()
+            && self
+                .m_keyframes
+                .iter()
+                .all(|keyframe| withinTolerance(keyframe.getTranslation(), first.getTranslation()))
+        {
+            self.m_translationIsDynamic = false;
+        }
+
+        keyframeCount - self.m_keyframes.len()
+    }
+
+    /// Checks whether blending between the keyframes at `start` and `end`
+    /// reproduces every keyframe in between within the given tolerances.
+    fn keyframesReproduced(
+        &self,
+        start: usize,
+        end: usize,
+        translationTolerance: f32,
+        rotationTolerance: f32,
+    ) -> bool {
+        let pCoreKeyframeBefore = &self.m_keyframes[start];
+        let pCoreKeyframeAfter = &self.m_keyframes[end];
+        let duration = pCoreKeyframeAfter.getTime() - pCoreKeyframeBefore.getTime();
+
+        self.m_keyframes[start + 1..end].iter().all(|keyframe| {
+            let blendFactor = match duration > 0.0 {
+                true => (keyframe.getTime() - pCoreKeyframeBefore.getTime()) / duration,
+                false => 0.0,
+            };
+
+            let mut translation = *pCoreKeyframeBefore.getTranslation();
+            translation.blend(blendFactor, pCoreKeyframeAfter.getTranslation());
+
+            let mut rotation = *pCoreKeyframeBefore.getRotation();
+            rotation.blend(blendFactor, pCoreKeyframeAfter.getRotation());
+
+            (translation - keyframe.getTranslation()).magnitude() <= translationTolerance
+                && angleBetween(&rotation, keyframe.getRotation()) <= rotationTolerance
+        })
     }
 
     //344
-    pub fn collapseSequences(&self, translationTolerance: f64, rotationToleranceDegrees: f64) {
-        todo!();
+    /*****************************************************************************/
+    /** Collapses sequences of keyframes holding the same pose.
+     *
+     * This function replaces every run of keyframes that stay within the given
+     * tolerances of the first keyframe of the run by the first and last
+     * keyframes of the run.
+     *
+     * @param translationTolerance The distance a translation may move by
+     *                             within a run.
+     * @param rotationToleranceDegrees The angle in degrees a rotation may turn
+     *                                 by within a run.
+     *
+     * @return The number of keyframes removed.
+     *****************************************************************************/
+    pub fn collapseSequences(
+        &mut self,
+        translationTolerance: f64,
+        rotationToleranceDegrees: f64,
+    ) -> usize {
+        let translationTolerance = translationTolerance as f32;
+        let rotationTolerance = (rotationToleranceDegrees as f32).to_radians();
+
+        let keyframeCount = self.m_keyframes.len();
+        let mut output = Vec::with_capacity(keyframeCount);
+
+        let mut start = 0;
+        while start < keyframeCount {
+            // find the last keyframe still holding the pose of the first one
+            let first = &self.m_keyframes[start];
+            let mut end = start;
+            while end + 1 < keyframeCount {
+                let next = &self.m_keyframes[end + 1];
+                if (next.getTranslation() - first.getTranslation()).magnitude()
+                    > translationTolerance
+                    || angleBetween(next.getRotation(), first.getRotation()) > rotationTolerance
+                {
+                    break;
+                }
+                end += 1;
+            }
+
+            output.push(first.clone());
+            if end > start {
+                output.push(self.m_keyframes[end].clone());
+            }
+            start = end + 1;
+        }
+
+        self.m_keyframes = output;
+
+        let removed = keyframeCount - self.m_keyframes.len();
+        self.m_collapsedKeyframeCount += removed;
+        removed
+    }
+
+    /*****************************************************************************/
+    /** Returns the number of collapsed keyframes.
+     *
+     * This function returns the number of keyframes collapseSequences() has
+     * removed from the core track, such as when loading with the
+     * collapse_sequences loader option.
+     *****************************************************************************/
+    pub fn getCollapsedKeyframeCount(&self) -> usize {
+        self.m_collapsedKeyframeCount
     }
 
     // 485 cpp
@@ -98,7 +287,7 @@ impl CalCoreTrack {
         let mut iteratorCoreKeyframeAfter = self.getUpperBound(time);
 
         // check if the time is after the last keyframe
-        if iteratorCoreKeyframeAfter == self.m_keyframes.len() - 1 {
+        if iteratorCoreKeyframeAfter == self.m_keyframes.len() {
             // return the last keyframe state
             iteratorCoreKeyframeAfter -= 1;
             let rotation = self.m_keyframes[iteratorCoreKeyframeAfter].getRotation();
@@ -143,6 +332,15 @@ impl CalCoreTrack {
     fn getUpperBound(&self, time: f32) -> usize {
         let mut lowerBound = 0;
         let mut upperBound = self.m_keyframes.len() - 1;
+
+        // there is no keyframe after the last one
+        if time >= self.m_keyframes[upperBound].getTime() {
+            return self.m_keyframes.len();
+        }
+
+        if time < self.m_keyframes[lowerBound].getTime() {
+            return lowerBound;
+        }
         //static int aa = 0;
 
         //upperBound += aa;
@@ -174,3 +372,117 @@ impl CalCoreTrack {
         self.m_keyframes.get(idx)
     }
 }
+
+/// Returns the angle between two rotations, whichever way round they are
+/// stored.
+fn angleBetween(a: &CalQuaternion<f32>, b: &CalQuaternion<f32>) -> f32 {
+    let cosHalfAngle = a.normalize().dot(b.normalize());
+    2.0 * cosHalfAngle.abs().min(1.0).acos()
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Rotation3;
+
+    use super::*;
+    use crate::core::testdata;
+
+    /// Builds a track of the given poses, 1/30 s apart.
+    fn track(poses: &[(CalVector<f32>, CalQuaternion<f32>)]) -> CalCoreTrack {
+        let keyframes = poses
+            .iter()
+            .enumerate()
+            .map(|(i, (translation, rotation))| {
+                Rc::new(CalCoreKeyframe::new(
+                    i as f32 / 30.0,
+                    *translation,
+                    *rotation,
+                ))
+            })
+            .collect();
+        CalCoreTrack::new(0, true, true, true, keyframes)
+    }
+
+    fn turn(degrees: f32) -> CalQuaternion<f32> {
+        let axis = CalVector::new(0.0, 0.6, 0.8);
+        CalQuaternion::from_axis_angle(axis, cgmath::Deg(degrees))
+    }
+
+    /// Checks that a track still holds every pose given, at the time of its
+    /// keyframe, within the tolerances.
+    fn assertPoses(
+        track: &CalCoreTrack,
+        poses: &[(CalVector<f32>, CalQuaternion<f32>)],
+        translationTolerance: f32,
+        rotationToleranceDegrees: f32,
+    ) {
+        for (i, (translation, rotation)) in poses.iter().enumerate() {
+            let (stateTranslation, stateRotation) = track.getState(i as f32 / 30.0);
+            let translationError = (stateTranslation - translation).magnitude();
+            let rotationError = angleBetween(&stateRotation, rotation).to_degrees();
+            assert!(
+                translationError <= translationTolerance * 1.001,
+                "translation of pose {i} is off by {translationError}"
+            );
+            assert!(
+                rotationError <= rotationToleranceDegrees * 1.001,
+                "rotation of pose {i} is off by {rotationError} degrees"
+            );
+        }
+    }
+
+    #[test]
+    fn compressed_tracks_keep_the_pose() {
+        let poses = (0..60)
+            .map(|i| {
+                let t = i as f32 / 10.0;
+                (CalVector::new(t.sin(), 0.5 * t, 0.0), turn(90.0 * t.cos()))
+            })
+            .collect::<Vec<_>>();
+        let mut compressed = track(&poses);
+        let removed = compressed.compress(0.01, 1.0, &testdata::skeleton());
+
+        assert!(removed > 0);
+        assert_eq!(compressed.getCoreKeyframeCount(), poses.len() - removed);
+        assertPoses(&compressed, &poses, 0.01, 1.0);
+        assert!(compressed.getTranslationRequired());
+        assert!(compressed.getTranslationIsDynamic());
+    }
+
+    #[test]
+    fn compressed_tracks_drop_translations_of_the_skeleton() {
+        // bone 0 of the sample skeleton is at 1 2 3
+        let poses = (0..10)
+            .map(|i| (CalVector::new(1.0, 2.0, 3.0), turn(10.0 * i as f32)))
+            .collect::<Vec<_>>();
+        let mut compressed = track(&poses);
+        let removed = compressed.compress(0.01, 1.0, &testdata::skeleton());
+
+        // the rotation turns at a steady rate, so only the ends are needed
+        assert_eq!(removed, poses.len() - 2);
+        assertPoses(&compressed, &poses, 0.01, 1.0);
+        assert!(!compressed.getTranslationRequired());
+        assert!(!compressed.getTranslationIsDynamic());
+    }
+
+    #[test]
+    fn collapsed_sequences_keep_the_pose() {
+        let moving = |i: usize| (CalVector::new(i as f32, 0.0, 0.0), turn(20.0 * i as f32));
+        let held = (CalVector::new(3.0, 0.0, 0.0), turn(60.0));
+        let mut poses = (0..3).map(moving).collect::<Vec<_>>();
+        poses.extend([held; 5]);
+        poses.extend((8..11).map(moving));
+
+        let mut collapsed = track(&poses);
+        let removed = collapsed.collapseSequences(0.01, 1.0);
+
+        // the held pose is kept by the first and last keyframes of the run
+        assert_eq!(removed, 3);
+        assert_eq!(collapsed.getCollapsedKeyframeCount(), 3);
+        assert_eq!(collapsed.getCoreKeyframeCount(), poses.len() - 3);
+        assertPoses(&collapsed, &poses, 0.01, 1.0);
+
+        assert_eq!(collapsed.collapseSequences(0.01, 1.0), 0);
+        assert_eq!(collapsed.getCollapsedKeyframeCount(), 3);
+    }
+}
@@ -0,0 +1,294 @@
+//! Virtual file systems to load assets from.
+//!
+//! The core model loads its assets, model descriptions and the files of
+//! material maps through a `FileSystem`, so that they can be read from a
+//! directory on disk or from an archive the content is packed into:
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use std::rc::Rc;
+//!
+//! use cal3d::core::CalCoreModel;
+//! use cal3d::core::vfs::TarFileSystem;
+//!
+//! let archive = TarFileSystem::from_file(Path::new("paladin.tar")).unwrap();
+//! let mut core_model = CalCoreModel::default();
+//! core_model.setFileSystem(Rc::new(archive));
+//! core_model.load_from_cfg(Path::new("paladin.cfg")).unwrap();
+//! ```
+//!
+//! Paths are always relative to the root of the file system. The `.` and `..`
+//! components of paths are resolved before looking files up in an archive.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use super::loader::{AssetKind, LoaderError};
+
+/// A source of files to load assets from.
+pub trait FileSystem {
+    /// Opens a file for reading.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    /// Checks whether a file exists.
+    fn exists(&self, path: &Path) -> bool;
+}
+
+/// Reads the whole of a file of a file system.
+pub(crate) fn readFile(fileSystem: &dyn FileSystem, path: &Path) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    fileSystem.open(path)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// A file system reading from a directory on disk.
+#[derive(Clone, Debug, Default)]
+pub struct DirectoryFileSystem {
+    root: PathBuf,
+}
+
+/// The file system of the process, relative to the current directory. The
+/// core model loads through it unless given another file system.
+pub static NATIVE_FILE_SYSTEM: DirectoryFileSystem = DirectoryFileSystem {
+    root: PathBuf::new(),
+};
+
+impl DirectoryFileSystem {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        DirectoryFileSystem { root: root.into() }
+    }
+
+    pub fn getRoot(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl FileSystem for DirectoryFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(fs::File::open(self.root.join(path))?))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.root.join(path).is_file()
+    }
+}
+
+/// A file system reading from an uncompressed tar archive, which is read
+/// into memory as a whole.
+///
+/// Both the ustar and the GNU long name extensions are understood, as well
+/// as the paths of pax extended headers. Links and special files are
+/// ignored.
+#[derive(Clone, Debug, Default)]
+pub struct TarFileSystem {
+    data: Vec<u8>,
+    // the offset and size of the data of each file, by normalized path
+    m_mapFile: BTreeMap<PathBuf, (usize, usize)>,
+}
+
+const tarBlockSize: usize = 512;
+
+impl TarFileSystem {
+    /*****************************************************************************/
+    /** Reads a tar archive.
+     *
+     * This function reads a whole tar archive from a file.
+     *
+     * @param path The archive to read.
+     *
+     * @return The file system of the archive, or the reason it could not be
+     *         read.
+     *****************************************************************************/
+    pub fn from_file(path: &Path) -> Result<Self, LoaderError> {
+        let data =
+            fs::read(path).map_err(|e| LoaderError::from(e).in_file(path, AssetKind::Archive))?;
+        Self::from_bytes(data).map_err(|e| e.in_file(path, AssetKind::Archive))
+    }
+
+    /*****************************************************************************/
+    /** Reads a tar archive.
+     *
+     * This function reads a tar archive that is already in memory, checking
+     * the header of every entry.
+     *
+     * @param data The contents of the archive.
+     *
+     * @return The file system of the archive, or the reason it could not be
+     *         read.
+     *****************************************************************************/
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, LoaderError> {
+        let mut mapFile = BTreeMap::new();
+
+        // the name given by a GNU long name or pax header for the next entry
+        let mut nextName: Option<String> = None;
+
+        let mut offset = 0;
+        while offset < data.len() {
+            let Some(header) = data.get(offset..offset + tarBlockSize) else {
+                return Err(LoaderError::FormatError("Header is truncated".to_string())
+                    .at_offset(offset as u64));
+            };
+
+            // the archive ends with blocks of zeros
+            if header.iter().all(|&b| b == 0) {
+                break;
+            }
+
+            let inHeader = |e: LoaderError| e.at_offset(offset as u64);
+            checkTarHeaderChecksum(header).map_err(inHeader)?;
+
+            let size = parseTarNumber(&header[124..136], "size").map_err(inHeader)?;
+            let start = offset + tarBlockSize;
+            let Some(end) = start.checked_add(size).filter(|&end| end <= data.len()) else {
+                return Err(LoaderError::FormatError(format!(
+                    "Entry of {size} bytes is truncated"
+                ))
+                .at_offset(offset as u64));
+            };
+            let contents = &data[start..end];
+
+            let name = match nextName.take() {
+                Some(name) => name,
+                None => tarHeaderName(header),
+            };
+
+            match header[156] {
+                // regular files
+                b'0' | b'\0' | b'7' => {
+                    mapFile.insert(normalizePath(Path::new(&name)), (start, size));
+                }
+                // GNU long name of the next entry
+                b'L' => nextName = Some(cString(contents)),
+                // pax extended header of the next entry
+                b'x' => nextName = paxPath(contents),
+                // directories, links, special files and global headers
+                _ => {}
+            }
+
+            offset = start + size.div_ceil(tarBlockSize) * tarBlockSize;
+        }
+
+        Ok(TarFileSystem {
+            data,
+            m_mapFile: mapFile,
+        })
+    }
+
+    /// Returns the number of files in the archive.
+    pub fn getFileCount(&self) -> usize {
+        self.m_mapFile.len()
+    }
+
+    /// Returns the paths of the files in the archive, in order.
+    pub fn getFilePaths(&self) -> impl Iterator<Item = &Path> {
+        self.m_mapFile.keys().map(PathBuf::as_path)
+    }
+
+    fn getFileData(&self, path: &Path) -> Option<&[u8]> {
+        let &(start, size) = self.m_mapFile.get(&normalizePath(path))?;
+        Some(&self.data[start..start + size])
+    }
+}
+
+impl FileSystem for TarFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        match self.getFileData(path) {
+            Some(contents) => Ok(Box::new(contents)),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not in the archive", path.display()),
+            )),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.getFileData(path).is_some()
+    }
+}
+
+/// Resolves the `.` and `..` components of a path and makes it relative.
+fn normalizePath(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    normalized
+}
+
+/// Checks the checksum of a tar header, which is the sum of its bytes with
+/// the checksum field itself counted as spaces.
+fn checkTarHeaderChecksum(header: &[u8]) -> Result<(), LoaderError> {
+    let checksum = parseTarNumber(&header[148..156], "checksum")?;
+    let sum: usize = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as usize)
+        .sum();
+    if sum != checksum {
+        return Err(LoaderError::FormatError(format!(
+            "Header checksum {checksum} does not match {sum}"
+        )));
+    }
+    Ok(())
+}
+
+/// Parses an octal number field of a tar header.
+fn parseTarNumber(field: &[u8], what: &str) -> Result<usize, LoaderError> {
+    let text = String::from_utf8_lossy(field);
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    usize::from_str_radix(text, 8)
+        .map_err(|_| LoaderError::FormatError(format!("Invalid {what} {text:?} in header")))
+}
+
+/// Returns the name of the entry of a tar header, including the prefix of
+/// ustar headers.
+fn tarHeaderName(header: &[u8]) -> String {
+    let name = cString(&header[0..100]);
+    if &header[257..262] != b"ustar" {
+        return name;
+    }
+    let prefix = cString(&header[345..500]);
+    if prefix.is_empty() {
+        name
+    } else {
+        format!("{prefix}/{name}")
+    }
+}
+
+/// Returns the text of a field up to the first null.
+fn cString(field: &[u8]) -> String {
+    let length = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..length]).into_owned()
+}
+
+/// Returns the path of a pax extended header, made of "length key=value\n"
+/// records, if it has one.
+fn paxPath(contents: &[u8]) -> Option<String> {
+    let mut records = contents;
+    let mut path = None;
+    while !records.is_empty() {
+        let space = records.iter().position(|&b| b == b' ')?;
+        let length: usize = std::str::from_utf8(&records[..space]).ok()?.parse().ok()?;
+        if length <= space || length > records.len() {
+            return None;
+        }
+        let record = &records[space + 1..length];
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+        if let Some(value) = record.strip_prefix(b"path=") {
+            path = Some(String::from_utf8_lossy(value).into_owned());
+        }
+        records = &records[length..];
+    }
+    path
+}
@@ -0,0 +1,64 @@
+use std::io::Write;
+
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+
+use super::datasink::{DataSink, SinkError};
+use super::datasource::Endianness;
+
+/// A data sink writing to any writer, little-endian unless asked otherwise.
+pub struct WriterSink<W: Write> {
+    writer: W,
+    endianness: Endianness,
+}
+
+impl<W: Write> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_endianness(writer, Endianness::LittleEndian)
+    }
+
+    pub fn with_endianness(writer: W, endianness: Endianness) -> Self {
+        WriterSink { writer, endianness }
+    }
+
+    pub fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> DataSink for WriterSink<W> {
+    fn writeBytes(&mut self, pBuffer: &[u8]) -> Result<(), SinkError> {
+        Ok(self.writer.write_all(pBuffer)?)
+    }
+
+    fn writeByte(&mut self, value: u8) -> Result<(), SinkError> {
+        Ok(self.writer.write_u8(value)?)
+    }
+
+    fn writeFloat(&mut self, value: f32) -> Result<(), SinkError> {
+        match self.endianness {
+            Endianness::LittleEndian => self.writer.write_f32::<LittleEndian>(value)?,
+            Endianness::BigEndian => self.writer.write_f32::<BigEndian>(value)?,
+        }
+        Ok(())
+    }
+
+    fn writeShort(&mut self, value: i16) -> Result<(), SinkError> {
+        match self.endianness {
+            Endianness::LittleEndian => self.writer.write_i16::<LittleEndian>(value)?,
+            Endianness::BigEndian => self.writer.write_i16::<BigEndian>(value)?,
+        }
+        Ok(())
+    }
+
+    fn writeInteger(&mut self, value: i32) -> Result<(), SinkError> {
+        match self.endianness {
+            Endianness::LittleEndian => self.writer.write_i32::<LittleEndian>(value)?,
+            Endianness::BigEndian => self.writer.write_i32::<BigEndian>(value)?,
+        }
+        Ok(())
+    }
+}
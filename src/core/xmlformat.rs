@@ -1,9 +1,28 @@
-use std::io::{self, prelude::*, BufReader};
+use std::cell::RefCell;
+use std::fmt::{self, Write as _};
 use std::num::{ParseFloatError, ParseIntError};
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
 
-use super::loader::LoaderError;
+use crate::{CalQuaternion, CalVector};
+
+use super::animatedmorph::CalCoreAnimatedMorph;
+use super::animation::CalCoreAnimation;
+use super::bone::CalCoreBone;
+use super::keyframe::CalCoreKeyframe;
+use super::loader::{self, ErrorLocation, LoadStage, LoaderError, LoaderOptions};
 use super::material::{CalCoreMaterial, Color, Map};
+use super::mesh::CalCoreMesh;
+use super::morphkeyframe::CalCoreMorphKeyframe;
+use super::morphtrack::CalCoreMorphTrack;
+use super::saver::{self, SaverError};
+use super::skeleton::CalCoreSkeleton;
+use super::submesh::{
+    CalCoreSubmesh, Face, Influence, PhysicalProperty, SlaveAttribute, Spring, TextureCoordinate,
+};
+use super::submorphtarget::{BlendVertex, CalCoreSubMorphTarget};
+use super::track::CalCoreTrack;
 
 impl From<ParseIntError> for LoaderError {
     fn from(error: ParseIntError) -> Self {
@@ -20,18 +39,23 @@ impl From<ParseFloatError> for LoaderError {
     }
 }
 
-pub fn loadXmlCoreMaterial(filename: &PathBuf) -> Result<CalCoreMaterial, LoaderError> {
-    // use
-
+pub fn loadXmlCoreMaterial(
+    filename: &PathBuf,
+    options: &LoaderOptions,
+) -> Result<CalCoreMaterial, LoaderError> {
     let text = std::fs::read_to_string(filename)?;
 
-    let file = std::fs::File::open(filename)?;
-    let mut reader = BufReader::new(file);
+    loadXmlCoreMaterialFromText(&text, options)
+}
 
-    let mut buf = String::new();
-    reader.read_line(&mut buf)?;
+pub fn loadXmlCoreMaterialFromText(
+    text: &str,
+    options: &LoaderOptions,
+) -> Result<CalCoreMaterial, LoaderError> {
+    // the first line holds the header
+    let (header, buf) = text.split_once('\n').unwrap_or((text, ""));
 
-    for token in xmlparser::Tokenizer::from(buf.as_str()) {
+    for token in xmlparser::Tokenizer::from(header) {
         match token {
             Ok(val) => match val {
                 ElementStart => {}
@@ -40,11 +64,7 @@ pub fn loadXmlCoreMaterial(filename: &PathBuf) -> Result<CalCoreMaterial, Loader
         }
     }
 
-    buf.clear();
-
-    reader.read_to_string(&mut buf)?; //.map(|l| l.unwrap());
-
-    let mut tokenizer = xmlparser::Tokenizer::from(buf.as_str());
+    let mut tokenizer = xmlparser::Tokenizer::from(buf);
     let material = loop {
         let next = tokenizer.next();
         match next {
@@ -78,7 +98,12 @@ pub fn loadXmlCoreMaterial(filename: &PathBuf) -> Result<CalCoreMaterial, Loader
             }
         }
     };
-    println!("Material: {material:?}");
+
+    options.checkFiniteAt(
+        "Material shininess",
+        &[material.getShininess()],
+        ErrorLocation::default(),
+    )?;
 
     Ok(material)
 }
@@ -196,12 +221,8 @@ fn parse_material_elements(
                                 if vector_map.is_none() {
                                     vector_map = Some(Vec::new());
                                 }
-                                let map = parse_string(tokenizer, local.as_str())?;
-                                vector_map.as_mut().unwrap().push(Map::new(
-                                     map,
-                                     String::from("Diffuse Color"),
-                                     0
-                                ))
+                                let (map, map_type) = parse_map(tokenizer)?;
+                                vector_map.as_mut().unwrap().push(Map::new(map, map_type, 0))
                             }
                             _ => {
                                 return Err(LoaderError::FormatError(format!(
@@ -360,30 +381,42 @@ fn parse_float(tokenizer: &mut xmlparser::Tokenizer, element: &str) -> Result<f3
     Ok(float.ok_or(LoaderError::FormatError(format!("")))?)
 }
 
-fn parse_string(tokenizer: &mut xmlparser::Tokenizer, element: &str) -> Result<String, LoaderError> {
-    let mut string: Option<String> = None;
+fn parse_map(tokenizer: &mut xmlparser::Tokenizer) -> Result<(String, String), LoaderError> {
+    let mut string = String::new();
+    let mut map_type = String::from("Diffuse Color");
     loop {
         let next = tokenizer.next();
         match next {
             Some(token) => match token {
                 Ok(val) => {
                     match val {
-                        xmlparser::Token::Text { text } => {
-                            string = Some(text.to_string());
-                        }
-                        xmlparser::Token::ElementEnd { end, span } => match end {
-                            xmlparser::ElementEnd::Close(a, b) => break,
-                            xmlparser::ElementEnd::Open => {}
-                            xmlparser::ElementEnd::Empty => {
+                        xmlparser::Token::Attribute {
+                            prefix: _,
+                            local,
+                            value,
+                            span,
+                        } => match local.as_str() {
+                            "TYPE" => {
+                                map_type = unescape_xml(value.as_str());
+                            }
+                            _ => {
                                 return Err(LoaderError::FormatError(format!(
-                                    "XML {} element empty",
-                                    element
+                                    "Unexpect attribute '{}' in MAP",
+                                    span
                                 )))
                             }
                         },
+                        xmlparser::Token::Text { text } => {
+                            string = unescape_xml(text.as_str());
+                        }
+                        xmlparser::Token::ElementEnd { end, span: _ } => match end {
+                            xmlparser::ElementEnd::Close(_, _) => break,
+                            xmlparser::ElementEnd::Open => {}
+                            xmlparser::ElementEnd::Empty => break,
+                        },
                         _ => {
                             return Err(LoaderError::FormatError(format!(
-                                "XML error in {element} string: {:?}",
+                                "XML error in MAP: {:?}",
                                 val
                             )))
                         }
@@ -397,12 +430,1886 @@ fn parse_string(tokenizer: &mut xmlparser::Tokenizer, element: &str) -> Result<S
                 }
             },
             None => {
-                return Err(LoaderError::FormatError(format!(
+                return Err(LoaderError::FormatError(String::from(
                     "Unexpected end of XML file"
                 )))
             }
         }
     }
 
-    Ok(string.ok_or(LoaderError::FormatError(format!("")))?)
+    Ok((string, map_type))
+}
+
+/// An XML element read into memory, with its attributes, child elements and
+/// text content. The skeleton, mesh and animation formats are nested deeply
+/// enough that it is simpler to walk a tree than the raw tokens.
+struct XmlElement<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, String)>,
+    children: Vec<XmlElement<'a>>,
+    text: String,
+    /// The byte offset of the start of the element in the file.
+    offset: usize,
+}
+
+impl<'a> XmlElement<'a> {
+    fn new(name: &'a str, offset: usize) -> Self {
+        XmlElement {
+            name,
+            offset,
+            attributes: Vec::new(),
+            children: Vec::new(),
+            text: String::new(),
+        }
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn parse_attribute<T>(&self, name: &str) -> Result<T, LoaderError>
+    where
+        T: FromStr,
+        LoaderError: From<T::Err>,
+    {
+        let value = self.attribute(name).ok_or_else(|| {
+            LoaderError::FormatError(format!("XML {} has no {name} attribute", self.name))
+        })?;
+        Ok(value.trim().parse::<T>()?)
+    }
+
+    fn children<'b>(&'b self, name: &'b str) -> impl Iterator<Item = &'b XmlElement<'a>> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn child(&self, name: &str) -> Result<&XmlElement<'a>, LoaderError> {
+        self.children
+            .iter()
+            .find(|child| child.name == name)
+            .ok_or_else(|| {
+                LoaderError::FormatError(format!("XML {} has no {name} element", self.name))
+            })
+    }
+
+    fn parse_values<T>(&self, count: usize) -> Result<Vec<T>, LoaderError>
+    where
+        T: FromStr,
+        LoaderError: From<T::Err>,
+    {
+        let values = self
+            .text
+            .split_whitespace()
+            .map(|value| value.parse::<T>())
+            .collect::<Result<Vec<T>, _>>()?;
+        if values.len() != count {
+            return Err(LoaderError::FormatError(format!(
+                "XML {} has wrong number of values: {}",
+                self.name, self.text
+            )));
+        }
+        Ok(values)
+    }
+
+    fn parse_text<T>(&self) -> Result<T, LoaderError>
+    where
+        T: FromStr,
+        LoaderError: From<T::Err>,
+    {
+        Ok(self.text.trim().parse::<T>()?)
+    }
+
+    fn parse_vector(&self) -> Result<CalVector<f32>, LoaderError> {
+        let v = self.parse_values::<f32>(3)?;
+        Ok(CalVector::new(v[0], v[1], v[2]))
+    }
+
+    fn parse_quaternion(&self) -> Result<CalQuaternion<f32>, LoaderError> {
+        let q = self.parse_values::<f32>(4)?;
+        Ok(CalQuaternion::new(q[3], q[0], q[1], q[2]))
+    }
+
+    /// The location of the element, to report problems found in it at.
+    fn location(&self) -> ErrorLocation {
+        ErrorLocation {
+            offset: Some(self.offset as u64),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a function recording this element, described as given, in the
+    /// location of an error found while reading it.
+    fn locate(&self, description: String) -> impl Fn(LoaderError) -> LoaderError {
+        let offset = self.offset as u64;
+        move |error| error.at_offset(offset).in_element(description.clone())
+    }
+}
+
+/// The deepest nesting of elements accepted, so that a hostile document
+/// cannot exhaust the stack when the element tree is dropped.
+const MAX_DEPTH: usize = 128;
+
+fn parse_xml_elements(text: &str) -> Result<Vec<XmlElement<'_>>, LoaderError> {
+    let mut stack: Vec<XmlElement> = Vec::new();
+    let mut elements = Vec::new();
+
+    // Cal3D files put a HEADER element before the root element, which is not
+    // a well formed document, so the content is read as a fragment instead.
+    let mut start = 0;
+    if text.trim_start().starts_with("<?xml") {
+        start = text.find("?>").map(|end| end + 2).unwrap_or(text.len());
+    }
+
+    for token in xmlparser::Tokenizer::from_fragment(text, start..text.len()) {
+        let token =
+            token.map_err(|e| LoaderError::FormatError(format!("XML Parse error: {:?}", e)))?;
+        match token {
+            xmlparser::Token::ElementStart { local, span, .. } => {
+                if stack.len() >= MAX_DEPTH {
+                    return Err(LoaderError::FormatError(format!(
+                        "XML element {local} nested too deeply"
+                    ))
+                    .at_offset(span.start() as u64));
+                }
+                stack.push(XmlElement::new(local.as_str(), span.start()));
+            }
+            xmlparser::Token::Attribute { local, value, .. } => {
+                let Some(element) = stack.last_mut() else {
+                    return Err(LoaderError::FormatError(format!(
+                        "XML attribute {local} outside element"
+                    )));
+                };
+                element
+                    .attributes
+                    .push((local.as_str(), unescape_xml(value.as_str())));
+            }
+            xmlparser::Token::ElementEnd { end, .. } => {
+                let name = match end {
+                    xmlparser::ElementEnd::Open => continue,
+                    xmlparser::ElementEnd::Close(_, name) => Some(name.as_str()),
+                    xmlparser::ElementEnd::Empty => None,
+                };
+                let Some(element) = stack.pop() else {
+                    return Err(LoaderError::FormatError(String::from(
+                        "XML unexpected element end",
+                    )));
+                };
+                if name.is_some_and(|name| name != element.name) {
+                    return Err(LoaderError::FormatError(format!(
+                        "XML element {} closed by {}",
+                        element.name,
+                        name.unwrap()
+                    )));
+                }
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => elements.push(element),
+                }
+            }
+            xmlparser::Token::Text { text } => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&unescape_xml(text.as_str()));
+                }
+            }
+            xmlparser::Token::Cdata { text, .. } => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(text.as_str());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !stack.is_empty() {
+        return Err(LoaderError::FormatError(String::from(
+            "Unexpected end of XML file",
+        )));
+    }
+
+    Ok(elements)
+}
+
+/// Finds the root element of a Cal3D XML document, checking the magic and
+/// version given either in a leading HEADER element or on the root itself.
+fn find_root_element<'a, 'b>(
+    elements: &'b [XmlElement<'a>],
+    magic: &[u8; 4],
+    root: &str,
+) -> Result<&'b XmlElement<'a>, LoaderError> {
+    let element = elements
+        .iter()
+        .find(|element| element.name == root)
+        .ok_or_else(|| LoaderError::FormatError(format!("XML file has no {root} element")))?;
+
+    let header = match elements.iter().find(|element| element.name == "HEADER") {
+        Some(header) => header,
+        None => element,
+    };
+
+    let magic = String::from_utf8_lossy(magic);
+    if header.attribute("MAGIC") != Some(magic.trim_matches(char::from(0))) {
+        return Err(LoaderError::MagicError);
+    }
+
+    let version = header.parse_attribute::<i32>("VERSION")?;
+    if !(loader::EARLIEST_COMPATIBLE_FILE_VERSION..=loader::CURRENT_FILE_VERSION).contains(&version)
+    {
+        return Err(LoaderError::VersionError);
+    }
+
+    Ok(element)
+}
+
+/*****************************************************************************/
+/** Loads a core skeleton instance from a XML file.
+ *
+ * This function loads a core skeleton instance from a XML file, adding the
+ * bones to the given skeleton.
+ *
+ * @param filename The file to load the core skeleton instance from.
+ * @param options The conversions to apply while loading.
+ *****************************************************************************/
+pub fn loadXmlCoreSkeleton(
+    filename: &PathBuf,
+    skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
+) -> Result<(), LoaderError> {
+    let text = std::fs::read_to_string(filename)?;
+    loadXmlCoreSkeletonFromText(&text, skeleton, options)
+}
+
+pub fn loadXmlCoreSkeletonFromText(
+    text: &str,
+    skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
+) -> Result<(), LoaderError> {
+    let elements = parse_xml_elements(text)?;
+    let root = find_root_element(&elements, loader::SKELETON_XMLFILE_MAGIC, "SKELETON")?;
+
+    let bones = root.children("BONE").collect::<Vec<_>>();
+    if let Some(boneCount) = root.attribute("NUMBONES")
+        && boneCount.trim().parse::<usize>()? != bones.len()
+    {
+        return Err(LoaderError::FormatError(format!(
+            "Skeleton has {} bones, expected {boneCount}",
+            bones.len()
+        )));
+    }
+
+    let boneCount = bones.len();
+    let mut coreSkeleton = skeleton.borrow_mut();
+    for (boneId, bone) in bones.into_iter().enumerate() {
+        let offset = Some(bone.offset as u64);
+        options.reportProgressAt(LoadStage::Bones, boneId, boneCount, offset)?;
+        (|| -> Result<(), LoaderError> {
+            // bones are identified by their position, so the ids must be in order
+            if bone.parse_attribute::<usize>("ID")? != boneId {
+                return Err(LoaderError::FormatError(format!(
+                    "Bone {boneId} has out of order ID {}",
+                    bone.attribute("ID").unwrap_or_default()
+                )));
+            }
+
+            let strName = bone.attribute("NAME").unwrap_or_default().to_string();
+
+            let translationElement = bone.child("TRANSLATION")?;
+            let mut translation = translationElement.parse_vector()?;
+            options.checkFiniteAt(
+                "Bone translation",
+                &[translation.x, translation.y, translation.z],
+                translationElement.location(),
+            )?;
+            let rotationElement = bone.child("ROTATION")?;
+            let mut rotation = rotationElement.parse_quaternion()?;
+            options.checkRotationAt("Bone rotation", &rotation, rotationElement.location())?;
+            let translationElement = bone.child("LOCALTRANSLATION")?;
+            let translationBoneSpace = translationElement.parse_vector()?;
+            options.checkFiniteAt(
+                "Bone space translation",
+                &[
+                    translationBoneSpace.x,
+                    translationBoneSpace.y,
+                    translationBoneSpace.z,
+                ],
+                translationElement.location(),
+            )?;
+            let rotationElement = bone.child("LOCALROTATION")?;
+            let rotationBoneSpace = rotationElement.parse_quaternion()?;
+            options.checkRotationAt(
+                "Bone space rotation",
+                &rotationBoneSpace,
+                rotationElement.location(),
+            )?;
+            let rotationBoneSpace = options.rotateBoneSpace(rotationBoneSpace);
+            let parentId = bone.child("PARENTID")?.parse_text::<i32>()?;
+
+            if parentId == -1 {
+                (rotation, translation) = options.rotateRootTransform(rotation, translation);
+            }
+
+            let mut childs = Vec::new();
+            for child in bone.children("CHILDID") {
+                let childId = child.parse_text::<i32>()?;
+                if childId < 0 {
+                    return Err(LoaderError::FormatError(format!(
+                        "Child ID {childId} is not positive",
+                    )));
+                }
+                childs.push(childId);
+            }
+
+            if let Some(childCount) = bone.attribute("NUMCHILDS")
+                && childCount.trim().parse::<usize>()? != childs.len()
+            {
+                return Err(LoaderError::FormatError(format!(
+                    "Bone {strName} has {} children, expected {childCount}",
+                    childs.len()
+                )));
+            }
+
+            let pCoreBone = CalCoreBone::new(
+                strName,
+                skeleton.clone(),
+                parentId,
+                childs,
+                translation,
+                rotation,
+                translationBoneSpace,
+                rotationBoneSpace,
+            );
+
+            coreSkeleton.addCoreBone(Rc::new(RefCell::new(pCoreBone)));
+
+            Ok(())
+        })()
+        .map_err(bone.locate(format!("bone {boneId}")))?;
+    }
+    let textLength = Some(text.len() as u64);
+    options.reportProgressAt(LoadStage::Bones, boneCount, boneCount, textLength)?;
+
+    coreSkeleton.calculateState();
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Loads a core mesh instance from a XML file.
+ *
+ * This function loads a core mesh instance from a XML file.
+ *
+ * @param filename The file to load the core mesh instance from.
+ * @param options The conversions to apply while loading.
+ *****************************************************************************/
+pub fn loadXmlCoreMesh(
+    filename: &PathBuf,
+    options: &LoaderOptions,
+) -> Result<CalCoreMesh, LoaderError> {
+    let text = std::fs::read_to_string(filename)?;
+    loadXmlCoreMeshFromText(&text, options)
+}
+
+pub fn loadXmlCoreMeshFromText(
+    text: &str,
+    options: &LoaderOptions,
+) -> Result<CalCoreMesh, LoaderError> {
+    let elements = parse_xml_elements(text)?;
+    let root = find_root_element(&elements, loader::MESH_XMLFILE_MAGIC, "MESH")?;
+
+    let submeshCount = root.children("SUBMESH").count();
+    loader::checkLimit("Submesh count", submeshCount, options.limits.max_submeshes)?;
+    let mut subMeshes = Vec::new();
+    for (submeshId, submesh) in root.children("SUBMESH").enumerate() {
+        let offset = Some(submesh.offset as u64);
+        options.reportProgressAt(LoadStage::Submeshes, submeshId, submeshCount, offset)?;
+        subMeshes.push(
+            loadXmlCoreSubmesh(submesh, options)
+                .map_err(submesh.locate(format!("submesh {submeshId}")))?,
+        );
+    }
+    let textLength = Some(text.len() as u64);
+    options.reportProgressAt(LoadStage::Submeshes, submeshCount, submeshCount, textLength)?;
+
+    if let Some(subMeshCount) = root.attribute("NUMSUBMESH")
+        && subMeshCount.trim().parse::<usize>()? != subMeshes.len()
+    {
+        return Err(LoaderError::FormatError(format!(
+            "Mesh has {} submeshes, expected {subMeshCount}",
+            subMeshes.len()
+        )));
+    }
+
+    Ok(CalCoreMesh::new(subMeshes))
+}
+
+fn loadXmlTextureCoordinate(
+    element: &XmlElement,
+    options: &LoaderOptions,
+) -> Result<TextureCoordinate, LoaderError> {
+    let uv = element.parse_values::<f32>(2)?;
+    options.checkFiniteAt("Texture coordinate", &uv, element.location())?;
+    Ok(options.textureCoordinate(uv[0], uv[1]))
+}
+
+fn loadXmlCoreSubmesh(
+    element: &XmlElement,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreSubmesh>>, LoaderError> {
+    let coreMaterialThreadId = element.parse_attribute::<i32>("MATERIAL")?;
+    let vertexCount = element.parse_attribute::<usize>("NUMVERTICES")?;
+    let faceCount = element.parse_attribute::<usize>("NUMFACES")?;
+    let lodCount = element.parse_attribute::<i32>("NUMLODSTEPS")?;
+    let springCount = element.parse_attribute::<usize>("NUMSPRINGS")?;
+    let textureCoordinateCount = element.parse_attribute::<usize>("NUMTEXCOORDS")?;
+
+    let limits = &options.limits;
+    loader::checkLimit("Vertex count", vertexCount, limits.max_vertices)?;
+    loader::checkLimit("Face count", faceCount, limits.max_faces)?;
+    loader::checkLimit("Spring count", springCount, limits.max_springs)?;
+    loader::checkLimit(
+        "Texture coordinate count",
+        textureCoordinateCount,
+        limits.max_texture_coordinate_sets,
+    )?;
+
+    // get the names and sizes of the vertex slave attributes, whose values
+    // are added as the vertices are read
+    loader::checkLimit(
+        "Slave attribute count",
+        element.children("SLAVEATTRIBUTE").count(),
+        limits.max_slave_attributes,
+    )?;
+    let mut slaveAttributes = Vec::new();
+    for (slaveAttributeId, slaveAttribute) in element.children("SLAVEATTRIBUTE").enumerate() {
+        let inSlaveAttribute = slaveAttribute.locate(format!("slave attribute {slaveAttributeId}"));
+        let name = slaveAttribute
+            .attribute("NAME")
+            .unwrap_or_default()
+            .to_string();
+        let componentCount = slaveAttribute
+            .parse_attribute::<usize>("COMPONENTS")
+            .map_err(&inSlaveAttribute)?;
+        if componentCount == 0 {
+            return Err(inSlaveAttribute(LoaderError::FormatError(format!(
+                "Slave attribute {name} has no components"
+            ))));
+        }
+        loader::checkLimit(
+            "Slave attribute component count",
+            componentCount,
+            limits.max_slave_attribute_components,
+        )
+        .map_err(&inSlaveAttribute)?;
+        slaveAttributes.push(SlaveAttribute::new(name, componentCount, 0));
+    }
+
+    let morphCount = element.children("MORPH").count();
+    loader::checkLimit("Morph target count", morphCount, limits.max_morph_targets)?;
+    let slaveComponentCount: usize = slaveAttributes.iter().map(|a| a.componentCount).sum();
+    loader::checkSubmeshValues(
+        vertexCount,
+        textureCoordinateCount + slaveComponentCount + morphCount,
+        limits,
+    )?;
+
+    // check the counts against the elements before allocating for them
+    let vertices = element.children("VERTEX").collect::<Vec<_>>();
+    if vertices.len() != vertexCount {
+        return Err(LoaderError::FormatError(format!(
+            "Submesh has {} vertices, expected {vertexCount}",
+            vertices.len()
+        )));
+    }
+    let springs = element.children("SPRING").collect::<Vec<_>>();
+    if springs.len() != springCount {
+        return Err(LoaderError::FormatError(format!(
+            "Submesh has {} springs, expected {springCount}",
+            springs.len()
+        )));
+    }
+    let faces = element.children("FACE").collect::<Vec<_>>();
+    if faces.len() != faceCount {
+        return Err(LoaderError::FormatError(format!(
+            "Submesh has {} faces, expected {faceCount}",
+            faces.len()
+        )));
+    }
+
+    let coreSubmesh = Rc::new(RefCell::new(CalCoreSubmesh::new(
+        coreMaterialThreadId,
+        lodCount,
+        vertexCount,
+        textureCoordinateCount,
+        faceCount,
+        springCount,
+    )));
+
+    let mut pCoreSubmesh = coreSubmesh.borrow_mut();
+
+    for textureCoordinateId in 0..textureCoordinateCount {
+        pCoreSubmesh.enableTangents(textureCoordinateId, false);
+    }
+
+    // load all vertices and their influences
+
+    let mut has_non_white_vertex_colors = false;
+    let mut textureCoordinates: Vec<Vec<TextureCoordinate>> =
+        vec![vec![TextureCoordinate::new(); vertexCount]; textureCoordinateCount];
+
+    for (vertexId, vertexElement) in vertices.into_iter().enumerate() {
+        options.checkCancelled(vertexId)?;
+        (|| -> Result<(), LoaderError> {
+            if vertexElement.parse_attribute::<usize>("ID")? != vertexId {
+                return Err(LoaderError::FormatError(format!(
+                    "Vertex {vertexId} has out of order ID {}",
+                    vertexElement.attribute("ID").unwrap_or_default()
+                )));
+            }
+
+            let vertex = &mut pCoreSubmesh.getVectorVertexMut()[vertexId];
+
+            let position = vertexElement.child("POS")?.parse_vector()?;
+            let normal = vertexElement.child("NORM")?.parse_vector()?;
+            options.checkFiniteAt(
+                "Vertex position and normal",
+                &[position.x, position.y, position.z, normal.x, normal.y, normal.z],
+                vertexElement.location(),
+            )?;
+            vertex.position = options.rotateVector(position);
+            vertex.normal = options.rotateVector(normal);
+            vertex.vertexColor = CalVector::new(1.0, 1.0, 1.0);
+            if let Ok(color) = vertexElement.child("COLOR") {
+                vertex.vertexColor = color.parse_vector()?;
+                if vertex.vertexColor != CalVector::new(1.0, 1.0, 1.0) {
+                    has_non_white_vertex_colors = true;
+                }
+            }
+
+            // load all slave attributes of the vertex
+            let slaves = vertexElement.children("SLAVE").collect::<Vec<_>>();
+            if slaves.len() != slaveAttributes.len() {
+                return Err(LoaderError::FormatError(format!(
+                    "Vertex {vertexId} has {} slave attributes, expected {}",
+                    slaves.len(),
+                    slaveAttributes.len()
+                )));
+            }
+            for (slaveAttribute, slave) in slaveAttributes.iter_mut().zip(slaves) {
+                let value = slave.parse_values::<f32>(slaveAttribute.componentCount)?;
+                slaveAttribute.values.extend(value);
+            }
+
+            vertex.collapseId = match vertexElement.child("COLLAPSEID") {
+                Ok(collapseId) => collapseId.parse_text::<i32>()?,
+                Err(_) => -1,
+            };
+            vertex.faceCollapseCount = match vertexElement.child("COLLAPSECOUNT") {
+                Ok(collapseCount) => collapseCount.parse_text::<i32>()?,
+                Err(_) => 0,
+            };
+
+            // load all texture coordinates of the vertex
+            let texcoords = vertexElement.children("TEXCOORD").collect::<Vec<_>>();
+            if texcoords.len() != textureCoordinateCount {
+                return Err(LoaderError::FormatError(format!(
+                    "Vertex {vertexId} has {} texture coordinates, expected {textureCoordinateCount}",
+                    texcoords.len()
+                )));
+            }
+            for (textureCoordinateId, texcoord) in texcoords.into_iter().enumerate() {
+                textureCoordinates[textureCoordinateId][vertexId] =
+                    loadXmlTextureCoordinate(texcoord, options)?;
+            }
+
+            // load all influences of the vertex
+            vertex.vectorInfluence.clear();
+            for (influenceId, influence) in vertexElement.children("INFLUENCE").enumerate() {
+                let inInfluence = influence.locate(format!("influence {influenceId}"));
+                let boneId = influence.parse_attribute::<i32>("ID").map_err(&inInfluence)?;
+                let weight = influence.parse_text::<f32>().map_err(&inInfluence)?;
+                if boneId < 0 {
+                    options.reportProblemAt(
+                        format!("Influence of vertex {vertexId} has negative bone ID {boneId}"),
+                        influence.location(),
+                    )?;
+                }
+                options.checkFiniteAt("Influence weight", &[weight], influence.location())?;
+                vertex.vectorInfluence.push(Influence { boneId, weight });
+            }
+
+            if let Some(influenceCount) = vertexElement.attribute("NUMINFLUENCES")
+                && influenceCount.trim().parse::<usize>()? != vertex.vectorInfluence.len()
+            {
+                return Err(LoaderError::FormatError(format!(
+                    "Vertex {vertexId} has {} influences, expected {influenceCount}",
+                    vertex.vectorInfluence.len()
+                )));
+            }
+
+            // load the physical property of the vertex if there are springs in the core submesh
+            if springCount > 0 {
+                let physique = vertexElement.child("PHYSIQUE")?;
+                let weight = physique.parse_text::<f32>()?;
+                options.checkFiniteAt("Physical property weight", &[weight], physique.location())?;
+                pCoreSubmesh.setPhysicalProperty(vertexId, PhysicalProperty::new(weight));
+            }
+
+            Ok(())
+        })()
+        .map_err(vertexElement.locate(format!("vertex {vertexId}")))?;
+    }
+    pCoreSubmesh.setAllTextureCoordinates(textureCoordinates);
+
+    pCoreSubmesh.setHasNonWhiteVertexColors(has_non_white_vertex_colors);
+
+    for slaveAttribute in slaveAttributes {
+        pCoreSubmesh.addSlaveAttribute(slaveAttribute);
+    }
+
+    // load all springs
+    for (springId, spring) in springs.into_iter().enumerate() {
+        (|| -> Result<(), LoaderError> {
+            let vertexId = spring
+                .attribute("VERTEXID")
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(|id| id.parse::<i32>())
+                .collect::<Result<Vec<i32>, _>>()?;
+            if vertexId.len() != 2 {
+                return Err(LoaderError::FormatError(format!(
+                    "Spring {springId} needs 2 vertex ids"
+                )));
+            }
+            let springCoefficient = spring.parse_attribute::<f32>("COEF")?;
+            let idleLength = spring.parse_attribute::<f32>("LENGTH")?;
+
+            pCoreSubmesh.setSpring(
+                springId,
+                Spring::from_values([vertexId[0], vertexId[1]], springCoefficient, idleLength),
+            );
+
+            Ok(())
+        })()
+        .map_err(spring.locate(format!("spring {springId}")))?;
+    }
+
+    // load all morph targets
+    for (morphId, morph) in element.children("MORPH").enumerate() {
+        (|| -> Result<(), LoaderError> {
+            let morphName = morph.attribute("NAME").unwrap_or_default().to_string();
+
+            let mut morphTarget =
+                CalCoreSubMorphTarget::new(coreSubmesh.clone(), vertexCount, morphName);
+
+            for (blendVertexId, blendVertexElement) in morph.children("BLENDVERTEX").enumerate() {
+                (|| -> Result<(), LoaderError> {
+                    let blendVertId = blendVertexElement.parse_attribute::<usize>("VERTEXID")?;
+
+                    let mut blendVertex = BlendVertex::new(textureCoordinateCount);
+                    blendVertex.position =
+                        options.rotateVector(blendVertexElement.child("POSITION")?.parse_vector()?);
+                    blendVertex.normal =
+                        options.rotateVector(blendVertexElement.child("NORMAL")?.parse_vector()?);
+                    for texcoord in blendVertexElement.children("TEXCOORD") {
+                        blendVertex
+                            .textureCoords
+                            .push(loadXmlTextureCoordinate(texcoord, options)?);
+                    }
+
+                    if !morphTarget.setBlendVertex(blendVertId, &blendVertex) {
+                        return Err(LoaderError::FormatError(format!(
+                            "Invalid blend vertex id {blendVertId}"
+                        )));
+                    }
+
+                    Ok(())
+                })()
+                .map_err(blendVertexElement.locate(format!("blend vertex {blendVertexId}")))?;
+            }
+
+            pCoreSubmesh.addCoreSubMorphTarget(morphTarget);
+
+            Ok(())
+        })()
+        .map_err(morph.locate(format!("morph target {morphId}")))?;
+    }
+
+    // load all faces
+    let mut flipModel = false;
+    for (faceId, faceElement) in faces.into_iter().enumerate() {
+        (|| -> Result<(), LoaderError> {
+            let vertexId = faceElement
+                .attribute("VERTEXID")
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(|id| id.parse::<crate::CalIndex>())
+                .collect::<Result<Vec<_>, _>>()?;
+            if vertexId.len() != 3 {
+                return Err(LoaderError::FormatError(format!(
+                    "Face {faceId} needs 3 vertex ids"
+                )));
+            }
+
+            let mut face = Face::new([vertexId[0], vertexId[1], vertexId[2]]);
+
+            // as for the binary format, the first face decides the winding of all faces
+            if faceId == 0 {
+                flipModel = loader::faceNeedsFlip(
+                    pCoreSubmesh.getVectorVertex(),
+                    &face.vertexId,
+                    options.flip_winding,
+                )?;
+            }
+
+            if flipModel {
+                face.vertexId.swap(1, 2);
+            }
+
+            pCoreSubmesh.setFace(faceId, face);
+
+            Ok(())
+        })()
+        .map_err(faceElement.locate(format!("face {faceId}")))?;
+    }
+
+    // Must be dropped before we return the value it refers to.
+    drop(pCoreSubmesh);
+
+    Ok(coreSubmesh)
+}
+
+/*****************************************************************************/
+/** Loads a core animation instance from a XML file.
+ *
+ * This function loads a core animation instance from a XML file. Keyframes
+ * without a translation, or with an invalid one, take the translation of the
+ * core bone in the given skeleton.
+ *
+ * @param filename The file to load the core animation instance from.
+ * @param skel The skeleton the animation is for.
+ * @param options The conversions to apply while loading.
+ *****************************************************************************/
+pub fn loadXmlCoreAnimation(
+    filename: &PathBuf,
+    skel: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimation>>, LoaderError> {
+    let text = std::fs::read_to_string(filename)?;
+    loadXmlCoreAnimationFromText(&text, skel, options)
+}
+
+pub fn loadXmlCoreAnimationFromText(
+    text: &str,
+    skel: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimation>>, LoaderError> {
+    let elements = parse_xml_elements(text)?;
+    let root = find_root_element(&elements, loader::ANIMATION_XMLFILE_MAGIC, "ANIMATION")?;
+
+    let duration = root.parse_attribute::<f32>("DURATION")?;
+    options.checkFiniteAt("Animation duration", &[duration], root.location())?;
+    if duration <= 0.0 {
+        return Err(LoaderError::FormatError(format!(
+            "Animation duration {duration} is negative"
+        )));
+    }
+
+    let trackCount = root.children("TRACK").count();
+    let mut tracks = Vec::new();
+    for (trackId, track) in root.children("TRACK").enumerate() {
+        let offset = Some(track.offset as u64);
+        options.reportProgressAt(LoadStage::Tracks, trackId, trackCount, offset)?;
+        tracks.push(
+            loadXmlCoreTrack(track, skel, options)
+                .map_err(track.locate(format!("track {trackId}")))?,
+        );
+    }
+    let textLength = Some(text.len() as u64);
+    options.reportProgressAt(LoadStage::Tracks, trackCount, trackCount, textLength)?;
+
+    if tracks.is_empty() {
+        return Err(LoaderError::FormatError(String::from(
+            "Animation has no tracks",
+        )));
+    }
+
+    if let Some(trackCount) = root.attribute("NUMTRACKS")
+        && trackCount.trim().parse::<usize>()? != tracks.len()
+    {
+        return Err(LoaderError::FormatError(format!(
+            "Animation has {} tracks, expected {trackCount}",
+            tracks.len()
+        )));
+    }
+
+    Ok(Rc::new(RefCell::new(CalCoreAnimation::new(
+        duration, tracks,
+    ))))
+}
+
+/*****************************************************************************/
+/** Loads a core animated morph instance from an XML file.
+ *
+ * This function loads a core animated morph instance from an XML file. Each
+ * track holds the weights of the morph target named by its MORPHNAME
+ * attribute.
+ *
+ * @param filename The file to load the core animated morph instance from.
+ *****************************************************************************/
+pub fn loadXmlCoreAnimatedMorph(
+    filename: &PathBuf,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimatedMorph>>, LoaderError> {
+    let text = std::fs::read_to_string(filename)?;
+    loadXmlCoreAnimatedMorphFromText(&text, options)
+}
+
+pub fn loadXmlCoreAnimatedMorphFromText(
+    text: &str,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreAnimatedMorph>>, LoaderError> {
+    let elements = parse_xml_elements(text)?;
+    let root = find_root_element(&elements, loader::ANIMATEDMORPH_XMLFILE_MAGIC, "ANIMATION")?;
+
+    let duration = root.parse_attribute::<f32>("DURATION")?;
+    options.checkFiniteAt("Animated morph duration", &[duration], root.location())?;
+    if duration <= 0.0 {
+        return Err(LoaderError::FormatError(format!(
+            "Animated morph duration {duration} is negative"
+        )));
+    }
+
+    let trackCount = root.children("TRACK").count();
+    let mut tracks = Vec::new();
+    for (trackId, track) in root.children("TRACK").enumerate() {
+        let offset = Some(track.offset as u64);
+        options.reportProgressAt(LoadStage::Tracks, trackId, trackCount, offset)?;
+        tracks.push(
+            loadXmlCoreMorphTrack(track, options)
+                .map_err(track.locate(format!("track {trackId}")))?,
+        );
+    }
+    let textLength = Some(text.len() as u64);
+    options.reportProgressAt(LoadStage::Tracks, trackCount, trackCount, textLength)?;
+
+    if tracks.is_empty() {
+        return Err(LoaderError::FormatError(String::from(
+            "Animated morph has no tracks",
+        )));
+    }
+
+    if let Some(trackCount) = root.attribute("NUMTRACKS")
+        && trackCount.trim().parse::<usize>()? != tracks.len()
+    {
+        return Err(LoaderError::FormatError(format!(
+            "Animated morph has {} tracks, expected {trackCount}",
+            tracks.len()
+        )));
+    }
+
+    Ok(Rc::new(RefCell::new(CalCoreAnimatedMorph::new(
+        duration, tracks,
+    ))))
+}
+
+fn loadXmlCoreMorphTrack(
+    element: &XmlElement,
+    options: &LoaderOptions,
+) -> Result<CalCoreMorphTrack, LoaderError> {
+    let morphName = element.attribute("MORPHNAME").ok_or_else(|| {
+        LoaderError::FormatError(String::from("XML TRACK has no MORPHNAME attribute"))
+    })?;
+
+    let mut keyframes = Vec::new();
+    for (keyframeId, keyframe) in element.children("KEYFRAME").enumerate() {
+        options.checkCancelled(keyframeId)?;
+        (|| -> Result<(), LoaderError> {
+            let time = keyframe.parse_attribute::<f32>("TIME")?;
+            let weight = keyframe.child("WEIGHT")?.parse_text::<f32>()?;
+            options.checkFiniteAt(
+                "Morph keyframe time and weight",
+                &[time, weight],
+                keyframe.location(),
+            )?;
+            keyframes.push(CalCoreMorphKeyframe::new(time, weight));
+            Ok(())
+        })()
+        .map_err(keyframe.locate(format!("keyframe {keyframeId}")))?;
+    }
+
+    if keyframes.is_empty() {
+        return Err(LoaderError::FormatError(format!(
+            "Morph track {morphName} has no keyframes"
+        )));
+    }
+
+    if let Some(keyframeCount) = element.attribute("NUMKEYFRAMES")
+        && keyframeCount.trim().parse::<usize>()? != keyframes.len()
+    {
+        return Err(LoaderError::FormatError(format!(
+            "Morph track has {} keyframes, expected {keyframeCount}",
+            keyframes.len()
+        )));
+    }
+
+    Ok(CalCoreMorphTrack::new(morphName.to_string(), keyframes))
+}
+
+fn parse_flag_attribute(element: &XmlElement, name: &str) -> Result<bool, LoaderError> {
+    match element.attribute(name) {
+        Some(value) => Ok(value.trim().parse::<i32>()? != 0),
+        None => Ok(true),
+    }
+}
+
+fn loadXmlCoreTrack(
+    element: &XmlElement,
+    skeleton: &Rc<RefCell<CalCoreSkeleton>>,
+    options: &LoaderOptions,
+) -> Result<Rc<RefCell<CalCoreTrack>>, LoaderError> {
+    let coreBoneId = element.parse_attribute::<i32>("BONEID")?;
+    if coreBoneId < 0 {
+        return Err(LoaderError::FormatError(format!(
+            "Core bone ID {coreBoneId} is negative"
+        )));
+    }
+    let coreBoneId = coreBoneId as usize;
+
+    let translationRequired = parse_flag_attribute(element, "TRANSLATIONREQUIRED")?;
+    let highRangeRequired = parse_flag_attribute(element, "HIGHRANGEREQUIRED")?;
+    let translationIsDynamic = parse_flag_attribute(element, "TRANSLATIONISDYNAMIC")?;
+
+    let cb = skeleton.borrow().getCoreBone(coreBoneId);
+    if cb.is_none() {
+        options.reportProblemAt(
+            format!("Track of bone {coreBoneId}, which is not in the skeleton"),
+            element.location(),
+        )?;
+    }
+    let boneTranslation = match &cb {
+        Some(bone) => *bone.borrow().getTranslation(),
+        None => CalVector::new(0.0, 0.0, 0.0),
+    };
+    let rotatesKeyframes = options.rotatesKeyframes(&cb, coreBoneId)?;
+
+    // the keyframes, with whether their translation came from the skeleton
+    let mut keyframes: Vec<(Rc<CalCoreKeyframe>, bool)> = Vec::new();
+    for (keyframeId, keyframe) in element.children("KEYFRAME").enumerate() {
+        options.checkCancelled(keyframeId)?;
+        (|| -> Result<(), LoaderError> {
+            let time = keyframe.parse_attribute::<f32>("TIME")?;
+            options.checkFiniteAt("Keyframe time", &[time], keyframe.location())?;
+
+            // keyframes of tracks with a static translation only store it once
+            let (translation, translationFromSkeleton) = match keyframe.child("TRANSLATION") {
+                Ok(element) => {
+                    let translation = element.parse_vector()?;
+                    if cb.is_some()
+                        && loader::TranslationInvalid(translation.x, translation.y, translation.z)
+                    {
+                        options.reportProblemAt(
+                            String::from(
+                                "Keyframe translation is invalid, using the bone translation",
+                            ),
+                            element.location(),
+                        )?;
+                        (boneTranslation, true)
+                    } else {
+                        options.checkFiniteAt(
+                            "Keyframe translation",
+                            &[translation.x, translation.y, translation.z],
+                            element.location(),
+                        )?;
+                        (translation, false)
+                    }
+                }
+                Err(_) => match keyframes.last() {
+                    Some((last, lastFromSkeleton))
+                        if translationRequired && !translationIsDynamic =>
+                    {
+                        (*last.getTranslation(), *lastFromSkeleton)
+                    }
+                    _ => (boneTranslation, cb.is_some()),
+                },
+            };
+
+            let rotationElement = keyframe.child("ROTATION")?;
+            let rotation = rotationElement.parse_quaternion()?;
+            options.checkRotationAt("Keyframe rotation", &rotation, rotationElement.location())?;
+
+            keyframes.push((
+                Rc::new(CalCoreKeyframe::new(time, translation, rotation)),
+                translationFromSkeleton,
+            ));
+
+            Ok(())
+        })()
+        .map_err(keyframe.locate(format!("keyframe {keyframeId}")))?;
+    }
+
+    if keyframes.is_empty() {
+        return Err(LoaderError::FormatError(format!(
+            "Track for bone {coreBoneId} has no keyframes"
+        )));
+    }
+
+    if let Some(keyframeCount) = element.attribute("NUMKEYFRAMES")
+        && keyframeCount.trim().parse::<usize>()? != keyframes.len()
+    {
+        return Err(LoaderError::FormatError(format!(
+            "Track has {} keyframes, expected {keyframeCount}",
+            keyframes.len()
+        )));
+    }
+
+    // rotate once all are read, as keyframes may copy an earlier translation
+    let mut keyframes: Vec<Rc<CalCoreKeyframe>> = keyframes
+        .into_iter()
+        .map(|(keyframe, translationFromSkeleton)| match rotatesKeyframes {
+            true => options.rotateKeyframe(keyframe, translationFromSkeleton),
+            false => keyframe,
+        })
+        .collect();
+
+    // keyframes are always kept sorted by time
+    keyframes.sort_by(|a, b| a.getTime().total_cmp(&b.getTime()));
+
+    let mut coreTrack = CalCoreTrack::new(
+        coreBoneId,
+        translationRequired,
+        highRangeRequired,
+        translationIsDynamic,
+        keyframes,
+    );
+
+    if options.collapse_sequences {
+        coreTrack.collapseSequences(
+            options.translation_tolerance,
+            options.rotation_tolerance_degrees,
+        );
+    }
+    if options.compress_tracks {
+        coreTrack.compress(
+            options.translation_tolerance,
+            options.rotation_tolerance_degrees,
+            skeleton,
+        );
+    }
+
+    Ok(Rc::new(RefCell::new(coreTrack)))
+}
+
+fn unescape_xml(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn write_xml_header(xml: &mut String, magic: &[u8; 4]) -> fmt::Result {
+    writeln!(
+        xml,
+        "<HEADER MAGIC=\"{}\" VERSION=\"{}\" />",
+        String::from_utf8_lossy(magic).trim_matches(char::from(0)),
+        loader::CURRENT_FILE_VERSION
+    )
+}
+
+fn write_xml_vector(xml: &mut String, indent: &str, name: &str, v: &CalVector<f32>) -> fmt::Result {
+    writeln!(xml, "{indent}<{name}>{} {} {}</{name}>", v.x, v.y, v.z)
+}
+
+fn write_xml_quaternion(
+    xml: &mut String,
+    indent: &str,
+    name: &str,
+    q: &CalQuaternion<f32>,
+) -> fmt::Result {
+    writeln!(
+        xml,
+        "{indent}<{name}>{} {} {} {}</{name}>",
+        q.v.x, q.v.y, q.v.z, q.s
+    )
+}
+
+fn write_xml_texture_coordinate(
+    xml: &mut String,
+    indent: &str,
+    textureCoordinate: &TextureCoordinate,
+) -> fmt::Result {
+    writeln!(
+        xml,
+        "{indent}<TEXCOORD>{} {}</TEXCOORD>",
+        textureCoordinate.u, textureCoordinate.v
+    )
+}
+
+/*****************************************************************************/
+/** Saves a core animation instance to a XML file.
+ *
+ * This function saves a core animation instance to a XML file, in the layout
+ * read by loadXmlCoreAnimation.
+ *
+ * @param filename The name of the file to save the core animation instance
+ *                 to.
+ * @param pCoreAnimation The core animation instance that should be saved.
+ *****************************************************************************/
+pub fn saveXmlCoreAnimation(
+    filename: &PathBuf,
+    pCoreAnimation: &CalCoreAnimation,
+) -> Result<(), SaverError> {
+    let listCoreTrack = pCoreAnimation.getListCoreTrack();
+    if listCoreTrack.is_empty() {
+        return Err(SaverError::FormatError(String::from(
+            "Animation has no tracks",
+        )));
+    }
+
+    let mut xml = String::new();
+    write_xml_header(&mut xml, loader::ANIMATION_XMLFILE_MAGIC)?;
+    writeln!(
+        xml,
+        "<ANIMATION DURATION=\"{}\" NUMTRACKS=\"{}\">",
+        pCoreAnimation.getDuration(),
+        listCoreTrack.len()
+    )?;
+
+    for pCoreTrack in listCoreTrack.iter() {
+        let pCoreTrack = pCoreTrack.borrow();
+        let keyframeCount = pCoreTrack.getCoreKeyframeCount();
+        if keyframeCount == 0 {
+            return Err(SaverError::FormatError(format!(
+                "Track for bone {} has no keyframes",
+                pCoreTrack.getCoreBoneId()
+            )));
+        }
+
+        writeln!(
+            xml,
+            "    <TRACK BONEID=\"{}\" NUMKEYFRAMES=\"{keyframeCount}\" TRANSLATIONREQUIRED=\"{}\" HIGHRANGEREQUIRED=\"{}\" TRANSLATIONISDYNAMIC=\"{}\">",
+            pCoreTrack.getCoreBoneId(),
+            pCoreTrack.getTranslationRequired() as i32,
+            pCoreTrack.getHighRangeRequired() as i32,
+            pCoreTrack.getTranslationIsDynamic() as i32,
+        )?;
+
+        for keyframeId in 0..keyframeCount {
+            let Some(pCoreKeyframe) = pCoreTrack.getCoreKeyframe(keyframeId) else {
+                continue;
+            };
+            writeln!(
+                xml,
+                "        <KEYFRAME TIME=\"{}\">",
+                pCoreKeyframe.getTime()
+            )?;
+            write_xml_vector(
+                &mut xml,
+                "            ",
+                "TRANSLATION",
+                pCoreKeyframe.getTranslation(),
+            )?;
+            write_xml_quaternion(
+                &mut xml,
+                "            ",
+                "ROTATION",
+                pCoreKeyframe.getRotation(),
+            )?;
+            writeln!(xml, "        </KEYFRAME>")?;
+        }
+
+        writeln!(xml, "    </TRACK>")?;
+    }
+
+    writeln!(xml, "</ANIMATION>")?;
+
+    std::fs::write(filename, xml)?;
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core material instance to a XML file.
+ *
+ * This function saves a core material instance to a XML file, in the layout
+ * read by loadXmlCoreMaterial.
+ *
+ * @param filename The name of the file to save the core material instance
+ *                 to.
+ * @param pCoreMaterial The core material instance that should be saved.
+ *****************************************************************************/
+pub fn saveXmlCoreMaterial(
+    filename: &PathBuf,
+    pCoreMaterial: &CalCoreMaterial,
+) -> Result<(), SaverError> {
+    fn write_xml_color(xml: &mut String, name: &str, color: &Color) -> fmt::Result {
+        writeln!(
+            xml,
+            "    <{name}>{} {} {} {}</{name}>",
+            color.red(),
+            color.green(),
+            color.blue(),
+            color.alpha()
+        )
+    }
+
+    let maps = pCoreMaterial.getMaps();
+
+    // the material loader takes the whole first line as the header
+    let mut xml = String::new();
+    write_xml_header(&mut xml, loader::MATERIAL_XMLFILE_MAGIC)?;
+    writeln!(xml, "<MATERIAL NUMMAPS=\"{}\">", maps.len())?;
+    write_xml_color(&mut xml, "AMBIENT", pCoreMaterial.getAmbientColor())?;
+    write_xml_color(&mut xml, "DIFFUSE", pCoreMaterial.getDiffuseColor())?;
+    write_xml_color(&mut xml, "SPECULAR", pCoreMaterial.getSpecularColor())?;
+    writeln!(
+        xml,
+        "    <SHININESS>{}</SHININESS>",
+        pCoreMaterial.getShininess()
+    )?;
+    for map in maps.iter() {
+        writeln!(
+            xml,
+            "    <MAP TYPE=\"{}\">{}</MAP>",
+            escape_xml(&map.mapType),
+            escape_xml(&map.strFilename)
+        )?;
+    }
+    writeln!(xml, "</MATERIAL>")?;
+
+    std::fs::write(filename, xml)?;
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core mesh instance to a XML file.
+ *
+ * This function saves a core mesh instance to a XML file, in the layout read
+ * by loadXmlCoreMesh.
+ *
+ * @param filename The name of the file to save the core mesh instance to.
+ * @param pCoreMesh The core mesh instance that should be saved.
+ *****************************************************************************/
+pub fn saveXmlCoreMesh(filename: &PathBuf, pCoreMesh: &CalCoreMesh) -> Result<(), SaverError> {
+    let vectorCoreSubmesh = pCoreMesh.getCoreSubmeshes();
+
+    let mut xml = String::new();
+    write_xml_header(&mut xml, loader::MESH_XMLFILE_MAGIC)?;
+    writeln!(xml, "<MESH NUMSUBMESH=\"{}\">", vectorCoreSubmesh.len())?;
+
+    for pCoreSubmesh in vectorCoreSubmesh.iter() {
+        saveXmlCoreSubmesh(&mut xml, &pCoreSubmesh.borrow())?;
+    }
+
+    writeln!(xml, "</MESH>")?;
+
+    std::fs::write(filename, xml)?;
+    Ok(())
+}
+
+fn saveXmlCoreSubmesh(xml: &mut String, pCoreSubmesh: &CalCoreSubmesh) -> Result<(), SaverError> {
+    let vectorVertex = pCoreSubmesh.getVectorVertex();
+    let vectorFace = pCoreSubmesh.getVectorFace();
+    let vectorPhysicalProperty = pCoreSubmesh.getVectorPhysicalProperty();
+    let vectorSpring = pCoreSubmesh.getVectorSpring();
+    let vectorvectorTextureCoordinate = pCoreSubmesh.getVectorVectorTextureCoordinate();
+    let vectorCoreSubMorphTarget = pCoreSubmesh.getVectorCoreSubMorphTarget();
+    let vectorSlaveAttribute = pCoreSubmesh.getVectorSlaveAttribute();
+    let textureCoordinateCount = vectorvectorTextureCoordinate.len();
+
+    writeln!(
+        xml,
+        "    <SUBMESH NUMVERTICES=\"{}\" NUMFACES=\"{}\" NUMLODSTEPS=\"{}\" NUMSPRINGS=\"{}\" NUMMORPHS=\"{}\" NUMTEXCOORDS=\"{textureCoordinateCount}\" MATERIAL=\"{}\">",
+        vectorVertex.len(),
+        vectorFace.len(),
+        pCoreSubmesh.getLodCount(),
+        vectorSpring.len(),
+        vectorCoreSubMorphTarget.len(),
+        pCoreSubmesh.getCoreMaterialThreadId()
+    )?;
+
+    for slaveAttribute in vectorSlaveAttribute.iter() {
+        writeln!(
+            xml,
+            "        <SLAVEATTRIBUTE NAME=\"{}\" COMPONENTS=\"{}\" />",
+            escape_xml(&slaveAttribute.name),
+            slaveAttribute.componentCount
+        )?;
+    }
+
+    for (vertexId, vertex) in vectorVertex.iter().enumerate() {
+        writeln!(
+            xml,
+            "        <VERTEX ID=\"{vertexId}\" NUMINFLUENCES=\"{}\">",
+            vertex.vectorInfluence.len()
+        )?;
+        write_xml_vector(xml, "            ", "POS", &vertex.position)?;
+        write_xml_vector(xml, "            ", "NORM", &vertex.normal)?;
+        write_xml_vector(xml, "            ", "COLOR", &vertex.vertexColor)?;
+        for slaveAttribute in vectorSlaveAttribute.iter() {
+            let value = slaveAttribute.getValue(vertexId).ok_or_else(|| {
+                SaverError::FormatError(format!(
+                    "Vertex {vertexId} has no value for slave attribute {}",
+                    slaveAttribute.name
+                ))
+            })?;
+            let value = value.iter().map(f32::to_string).collect::<Vec<_>>();
+            writeln!(xml, "            <SLAVE>{}</SLAVE>", value.join(" "))?;
+        }
+        writeln!(
+            xml,
+            "            <COLLAPSEID>{}</COLLAPSEID>",
+            vertex.collapseId
+        )?;
+        writeln!(
+            xml,
+            "            <COLLAPSECOUNT>{}</COLLAPSECOUNT>",
+            vertex.faceCollapseCount
+        )?;
+        for vectorTextureCoordinate in vectorvectorTextureCoordinate.iter() {
+            let textureCoordinate = vectorTextureCoordinate.get(vertexId).ok_or_else(|| {
+                SaverError::FormatError(format!("Vertex {vertexId} has no texture coordinate"))
+            })?;
+            write_xml_texture_coordinate(xml, "            ", textureCoordinate)?;
+        }
+        for influence in vertex.vectorInfluence.iter() {
+            writeln!(
+                xml,
+                "            <INFLUENCE ID=\"{}\">{}</INFLUENCE>",
+                influence.boneId, influence.weight
+            )?;
+        }
+        if !vectorSpring.is_empty() {
+            let physicalProperty = vectorPhysicalProperty.get(vertexId).ok_or_else(|| {
+                SaverError::FormatError(format!("Vertex {vertexId} has no physical property"))
+            })?;
+            writeln!(
+                xml,
+                "            <PHYSIQUE>{}</PHYSIQUE>",
+                physicalProperty.weight
+            )?;
+        }
+        writeln!(xml, "        </VERTEX>")?;
+    }
+
+    for spring in vectorSpring.iter() {
+        writeln!(
+            xml,
+            "        <SPRING VERTEXID=\"{} {}\" COEF=\"{}\" LENGTH=\"{}\" />",
+            spring.vertexId[0], spring.vertexId[1], spring.springCoefficient, spring.idleLength
+        )?;
+    }
+
+    for (morphId, morphTarget) in vectorCoreSubMorphTarget.iter().enumerate() {
+        let vectorBlendVertex = morphTarget.getVectorBlendVertex();
+        let blendVertexIds = saver::storedBlendVertexIds(morphTarget);
+
+        writeln!(
+            xml,
+            "        <MORPH NAME=\"{}\" NUMBLENDVERTS=\"{}\" MORPHID=\"{morphId}\">",
+            escape_xml(morphTarget.getName()),
+            blendVertexIds.len()
+        )?;
+        for blendVertexId in blendVertexIds {
+            let blendVertex = &vectorBlendVertex[blendVertexId];
+            writeln!(
+                xml,
+                "            <BLENDVERTEX VERTEXID=\"{blendVertexId}\">"
+            )?;
+            write_xml_vector(xml, "                ", "POSITION", &blendVertex.position)?;
+            write_xml_vector(xml, "                ", "NORMAL", &blendVertex.normal)?;
+            // the binary format always stores one set of coordinates per map
+            for textureCoordinateId in 0..textureCoordinateCount {
+                let textureCoordinate = blendVertex
+                    .textureCoords
+                    .get(textureCoordinateId)
+                    .cloned()
+                    .unwrap_or_default();
+                write_xml_texture_coordinate(xml, "                ", &textureCoordinate)?;
+            }
+            writeln!(xml, "            </BLENDVERTEX>")?;
+        }
+        writeln!(xml, "        </MORPH>")?;
+    }
+
+    for face in vectorFace.iter() {
+        writeln!(
+            xml,
+            "        <FACE VERTEXID=\"{} {} {}\" />",
+            face.vertexId[0], face.vertexId[1], face.vertexId[2]
+        )?;
+    }
+
+    writeln!(xml, "    </SUBMESH>")?;
+    Ok(())
+}
+
+/*****************************************************************************/
+/** Saves a core skeleton instance to a XML file.
+ *
+ * This function saves a core skeleton instance to a XML file, in the layout
+ * read by loadXmlCoreSkeleton.
+ *
+ * @param filename The name of the file to save the core skeleton instance
+ *                 to.
+ * @param pCoreSkeleton The core skeleton instance that should be saved.
+ *****************************************************************************/
+pub fn saveXmlCoreSkeleton(
+    filename: &PathBuf,
+    pCoreSkeleton: &CalCoreSkeleton,
+) -> Result<(), SaverError> {
+    let vectorCoreBone = pCoreSkeleton.getVectorCoreBone();
+    if vectorCoreBone.is_empty() {
+        return Err(SaverError::FormatError(String::from(
+            "Skeleton has no bones",
+        )));
+    }
+    let listChildIds = saver::childIdsFromParentIds(pCoreSkeleton)?;
+
+    let mut xml = String::new();
+    write_xml_header(&mut xml, loader::SKELETON_XMLFILE_MAGIC)?;
+    writeln!(xml, "<SKELETON NUMBONES=\"{}\">", vectorCoreBone.len())?;
+
+    for (boneId, (pCoreBone, listChildId)) in vectorCoreBone.iter().zip(listChildIds).enumerate() {
+        let pCoreBone = pCoreBone.borrow();
+        writeln!(
+            xml,
+            "    <BONE ID=\"{boneId}\" NAME=\"{}\" NUMCHILDS=\"{}\">",
+            escape_xml(pCoreBone.getName()),
+            listChildId.len()
+        )?;
+        write_xml_vector(
+            &mut xml,
+            "        ",
+            "TRANSLATION",
+            pCoreBone.getTranslation(),
+        )?;
+        write_xml_quaternion(&mut xml, "        ", "ROTATION", pCoreBone.getRotation())?;
+        write_xml_vector(
+            &mut xml,
+            "        ",
+            "LOCALTRANSLATION",
+            pCoreBone.getTranslationBoneSpace(),
+        )?;
+        write_xml_quaternion(
+            &mut xml,
+            "        ",
+            "LOCALROTATION",
+            pCoreBone.getRotationBoneSpace(),
+        )?;
+        writeln!(
+            xml,
+            "        <PARENTID>{}</PARENTID>",
+            pCoreBone.getParentId()
+        )?;
+        for childId in listChildId {
+            writeln!(xml, "        <CHILDID>{childId}</CHILDID>")?;
+        }
+        writeln!(xml, "    </BONE>")?;
+    }
+
+    writeln!(xml, "</SKELETON>")?;
+
+    std::fs::write(filename, xml)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::submesh::Vertex;
+    use crate::core::testdata;
+    use crate::core::{Endianness, MemorySource};
+
+    fn assertSameSkeleton(xml: &CalCoreSkeleton, binary: &CalCoreSkeleton) {
+        assert_eq!(
+            xml.getVectorCoreBone().len(),
+            binary.getVectorCoreBone().len()
+        );
+        for (xmlBone, binaryBone) in xml
+            .getVectorCoreBone()
+            .iter()
+            .zip(binary.getVectorCoreBone())
+        {
+            let (xmlBone, binaryBone) = (xmlBone.borrow(), binaryBone.borrow());
+            assert_eq!(xmlBone.getName(), binaryBone.getName());
+            assert_eq!(xmlBone.getParentId(), binaryBone.getParentId());
+            assert_eq!(xmlBone.getListChildId(), binaryBone.getListChildId());
+            assert_eq!(xmlBone.getTranslation(), binaryBone.getTranslation());
+            assert_eq!(xmlBone.getRotation(), binaryBone.getRotation());
+            assert_eq!(
+                xmlBone.getTranslationBoneSpace(),
+                binaryBone.getTranslationBoneSpace()
+            );
+            assert_eq!(
+                xmlBone.getRotationBoneSpace(),
+                binaryBone.getRotationBoneSpace()
+            );
+        }
+        assert_eq!(
+            xml.getVectorRootCoreBoneId(),
+            binary.getVectorRootCoreBoneId()
+        );
+    }
+
+    #[test]
+    fn xml_skeletons_load_like_binary_ones() {
+        let skeleton = testdata::skeleton();
+        {
+            let skeleton = skeleton.borrow();
+            let child = skeleton.getCoreBone(1).unwrap();
+            let child = child.borrow();
+            assert_eq!(child.getName(), "child");
+            assert_eq!(child.getParentId(), 0);
+            assert_eq!(*child.getTranslation(), CalVector::new(0.0, 1.0, 0.0));
+            assert_eq!(
+                *child.getRotation(),
+                CalQuaternion::new(0.7071068, 0.0, 0.0, 0.7071068)
+            );
+            assert_eq!(
+                skeleton.getCoreBone(0).unwrap().borrow().getListChildId(),
+                &vec![1]
+            );
+            // the children are linked, so the state reaches the child bone
+            assert_eq!(
+                *child.getTranslationAbsolute(),
+                CalVector::new(1.0, 3.0, 3.0)
+            );
+        }
+
+        let data = testdata::saveToBuffer(Endianness::LittleEndian, |s| {
+            saver::saveCoreSkeletonToSink(s, &skeleton.borrow())
+        });
+        let binary = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+        loader::loadCoreSkeletonFromSource(
+            &mut MemorySource::new(&data),
+            &binary,
+            &LoaderOptions::default(),
+        )
+        .unwrap();
+        assertSameSkeleton(&skeleton.borrow(), &binary.borrow());
+    }
+
+    fn assertSameMesh(xml: &CalCoreMesh, binary: &CalCoreMesh) {
+        assert_eq!(
+            xml.getCoreSubmeshes().len(),
+            binary.getCoreSubmeshes().len()
+        );
+        for (xml, binary) in xml.getCoreSubmeshes().iter().zip(binary.getCoreSubmeshes()) {
+            let (xml, binary) = (xml.borrow(), binary.borrow());
+            assert_eq!(
+                xml.getCoreMaterialThreadId(),
+                binary.getCoreMaterialThreadId()
+            );
+            assert_eq!(xml.getLodCount(), binary.getLodCount());
+
+            assert_eq!(xml.getVectorVertex().len(), binary.getVectorVertex().len());
+            for (xml, binary) in xml.getVectorVertex().iter().zip(binary.getVectorVertex()) {
+                assert_eq!(xml.position, binary.position);
+                assert_eq!(xml.normal, binary.normal);
+                assert_eq!(xml.vertexColor, binary.vertexColor);
+                assert_eq!(xml.collapseId, binary.collapseId);
+                assert_eq!(xml.faceCollapseCount, binary.faceCollapseCount);
+                let influences = |vertex: &Vertex| {
+                    vertex
+                        .vectorInfluence
+                        .iter()
+                        .map(|influence| (influence.boneId, influence.weight))
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(influences(xml), influences(binary));
+            }
+
+            let faces = |submesh: &CalCoreSubmesh| {
+                submesh
+                    .getVectorFace()
+                    .iter()
+                    .map(|face| face.vertexId)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(faces(&xml), faces(&binary));
+            let springs = |submesh: &CalCoreSubmesh| {
+                submesh
+                    .getVectorSpring()
+                    .iter()
+                    .map(|spring| (spring.vertexId, spring.springCoefficient, spring.idleLength))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(springs(&xml), springs(&binary));
+            let weights = |submesh: &CalCoreSubmesh| {
+                submesh
+                    .getVectorPhysicalProperty()
+                    .iter()
+                    .map(|property| property.weight)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(weights(&xml), weights(&binary));
+            let textureCoordinates = |submesh: &CalCoreSubmesh| {
+                submesh
+                    .getVectorVectorTextureCoordinate()
+                    .iter()
+                    .map(|set| set.iter().map(|uv| (uv.u, uv.v)).collect::<Vec<_>>())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(textureCoordinates(&xml), textureCoordinates(&binary));
+            assert_eq!(
+                xml.getVectorSlaveAttribute(),
+                binary.getVectorSlaveAttribute()
+            );
+
+            let morphs = |submesh: &CalCoreSubmesh| {
+                submesh
+                    .getVectorCoreSubMorphTarget()
+                    .iter()
+                    .map(|morph| {
+                        let blendVertices = morph
+                            .getVectorBlendVertex()
+                            .iter()
+                            .map(|blend| {
+                                let uvs = blend.textureCoords.iter().map(|uv| (uv.u, uv.v));
+                                (blend.position, blend.normal, uvs.collect::<Vec<_>>())
+                            })
+                            .collect::<Vec<_>>();
+                        (morph.getName().to_string(), blendVertices)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(morphs(&xml), morphs(&binary));
+        }
+    }
+
+    #[test]
+    fn xml_meshes_load_like_binary_ones() {
+        let mesh = testdata::mesh();
+        {
+            let mesh = mesh.borrow();
+            let submesh = mesh.getCoreSubmeshes()[0].borrow();
+            let vertex = &submesh.getVectorVertex()[1];
+            assert_eq!(vertex.position, CalVector::new(1.0, 0.0, 0.0));
+            assert_eq!(vertex.collapseId, 0);
+            assert_eq!(vertex.faceCollapseCount, 1);
+            assert_eq!(vertex.vectorInfluence.len(), 2);
+            assert_eq!(vertex.vectorInfluence[1].boneId, 1);
+            assert_eq!(vertex.vectorInfluence[1].weight, 0.5);
+            assert_eq!(submesh.getVectorFace()[0].vertexId, [0, 1, 2]);
+            assert_eq!(submesh.getVectorSpring()[0].vertexId, [0, 1]);
+            assert_eq!(submesh.getVectorSpring()[0].springCoefficient, 2.0);
+            assert_eq!(
+                submesh.getSlaveAttribute("wetness").unwrap().getValue(2),
+                Some(&[2.0, 0.5][..])
+            );
+            let morph = &submesh.getVectorCoreSubMorphTarget()[0];
+            assert_eq!(morph.getName(), "smile");
+            assert_eq!(
+                morph.getVectorBlendVertex()[2].position,
+                CalVector::new(0.0, 2.0, 0.0)
+            );
+        }
+
+        let data = testdata::saveToBuffer(Endianness::LittleEndian, |s| {
+            saver::saveCoreMeshToSink(s, &mesh.borrow())
+        });
+        let binary = loader::loadCoreMeshFromSource(
+            &mut MemorySource::new(&data),
+            &LoaderOptions::default(),
+        )
+        .unwrap();
+        assertSameMesh(&mesh.borrow(), &binary);
+    }
+
+    fn assertSameAnimation(xml: &CalCoreAnimation, binary: &CalCoreAnimation) {
+        assert_eq!(xml.getDuration(), binary.getDuration());
+        assert_eq!(
+            xml.getListCoreTrack().len(),
+            binary.getListCoreTrack().len()
+        );
+        for (xml, binary) in xml.getListCoreTrack().iter().zip(binary.getListCoreTrack()) {
+            let (xml, binary) = (xml.borrow(), binary.borrow());
+            assert_eq!(xml.getCoreBoneId(), binary.getCoreBoneId());
+            assert_eq!(
+                xml.getTranslationRequired(),
+                binary.getTranslationRequired()
+            );
+            assert_eq!(xml.getHighRangeRequired(), binary.getHighRangeRequired());
+            assert_eq!(
+                xml.getTranslationIsDynamic(),
+                binary.getTranslationIsDynamic()
+            );
+            assert_eq!(xml.getCoreKeyframeCount(), binary.getCoreKeyframeCount());
+            for keyframeId in 0..xml.getCoreKeyframeCount() {
+                let xml = xml.getCoreKeyframe(keyframeId).unwrap();
+                let binary = binary.getCoreKeyframe(keyframeId).unwrap();
+                assert_eq!(xml.getTime(), binary.getTime());
+                assert_eq!(xml.getTranslation(), binary.getTranslation());
+                assert_eq!(xml.getRotation(), binary.getRotation());
+            }
+        }
+    }
+
+    #[test]
+    fn xml_animations_load_like_binary_ones() {
+        let skeleton = testdata::skeleton();
+        let animation = testdata::animation(&skeleton);
+        {
+            let animation = animation.borrow();
+            assert_eq!(animation.getDuration(), 1.0);
+            let track = animation.getListCoreTrack()[1].borrow();
+            assert_eq!(track.getCoreBoneId(), 1);
+            assert_eq!(track.getCoreKeyframeCount(), 3);
+            let keyframe = track.getCoreKeyframe(1).unwrap();
+            assert_eq!(keyframe.getTime(), 0.5);
+            assert_eq!(*keyframe.getTranslation(), CalVector::new(0.0, 1.0, 0.0));
+            assert_eq!(
+                *keyframe.getRotation(),
+                CalQuaternion::new(0.7071068, 0.0, 0.0, 0.7071068)
+            );
+        }
+
+        let data = testdata::saveToBuffer(Endianness::LittleEndian, |s| {
+            saver::saveCoreAnimationToSink(s, &animation.borrow())
+        });
+        let binary = loader::loadCoreAnimationFromSource(
+            &mut MemorySource::new(&data),
+            &skeleton,
+            &LoaderOptions::default(),
+        )
+        .unwrap();
+        assertSameAnimation(&animation.borrow(), &binary.borrow());
+    }
+
+    #[test]
+    fn xml_writers_round_trip() {
+        let directory = std::env::temp_dir().join(format!("cal3d-xml-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let options = LoaderOptions::default();
+
+        let skeleton = testdata::skeleton();
+        let filename = directory.join("skeleton.xsf");
+        saveXmlCoreSkeleton(&filename, &skeleton.borrow()).unwrap();
+        let loaded = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+        loadXmlCoreSkeleton(&filename, &loaded, &options).unwrap();
+        assertSameSkeleton(&skeleton.borrow(), &loaded.borrow());
+
+        let animation = testdata::animation(&skeleton);
+        let filename = directory.join("animation.xaf");
+        saveXmlCoreAnimation(&filename, &animation.borrow()).unwrap();
+        let loaded = loadXmlCoreAnimation(&filename, &skeleton, &options).unwrap();
+        assertSameAnimation(&animation.borrow(), &loaded.borrow());
+
+        let mesh = testdata::mesh();
+        let filename = directory.join("mesh.xmf");
+        saveXmlCoreMesh(&filename, &mesh.borrow()).unwrap();
+        let loaded = loadXmlCoreMesh(&filename, &options).unwrap();
+        assertSameMesh(&mesh.borrow(), &loaded);
+
+        let material = testdata::material();
+        let filename = directory.join("material.xrf");
+        saveXmlCoreMaterial(&filename, &material).unwrap();
+        let loaded = loadXmlCoreMaterial(&filename, &options).unwrap();
+        assert_eq!(loaded.getAmbientColor(), material.getAmbientColor());
+        assert_eq!(loaded.getDiffuseColor(), material.getDiffuseColor());
+        assert_eq!(loaded.getSpecularColor(), material.getSpecularColor());
+        assert_eq!(loaded.getShininess(), material.getShininess());
+        assert_eq!(loaded.getMaps(), material.getMaps());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn deeply_nested_xml_is_rejected() {
+        let depth = 1 << 20;
+        let text = format!(
+            "<HEADER MAGIC=\"XSF\" VERSION=\"1400\" /><SKELETON NUMBONES=\"0\">{}{}</SKELETON>",
+            "<a>".repeat(depth),
+            "</a>".repeat(depth)
+        );
+        let skeleton = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+        let error =
+            loadXmlCoreSkeletonFromText(&text, &skeleton, &LoaderOptions::default()).unwrap_err();
+        assert!(error.to_string().contains("nested too deeply"), "{error}");
+    }
+
+    /// Checks that loading strictly fails with a problem, and that loading
+    /// leniently records it as the only warning.
+    fn assertReported(problem: &str, load: impl Fn(&LoaderOptions) -> Result<(), LoaderError>) {
+        let strict = LoaderOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let error = load(&strict).unwrap_err();
+        assert!(error.to_string().contains(problem), "{error}");
+
+        let options = LoaderOptions::default();
+        load(&options).unwrap();
+        let warnings = options.warnings.borrow();
+        assert_eq!(warnings.len(), 1, "{problem}");
+        assert!(warnings[0].message.contains(problem), "{}", warnings[0]);
+    }
+
+    #[test]
+    fn xml_problems_are_reported_like_binary_ones() {
+        let loadSkeleton = |text: String| {
+            move |options: &LoaderOptions| {
+                let skeleton = Rc::new(RefCell::new(CalCoreSkeleton::default()));
+                loadXmlCoreSkeletonFromText(&text, &skeleton, options)
+            }
+        };
+        let skeletonXml = testdata::SKELETON_XML;
+        assertReported(
+            "not normalised",
+            loadSkeleton(skeletonXml.replacen("<ROTATION>0 0 0 1", "<ROTATION>0 0 0 2", 1)),
+        );
+        assertReported(
+            "not finite",
+            loadSkeleton(skeletonXml.replacen("<TRANSLATION>1 2", "<TRANSLATION>NaN 2", 1)),
+        );
+
+        let loadMesh = |text: String| {
+            move |options: &LoaderOptions| loadXmlCoreMeshFromText(&text, options).map(drop)
+        };
+        let meshXml = testdata::MESH_XML;
+        assertReported(
+            "not finite",
+            loadMesh(meshXml.replacen("<POS>1 0 0", "<POS>1 inf 0", 1)),
+        );
+        assertReported(
+            "negative bone ID",
+            loadMesh(meshXml.replacen("<INFLUENCE ID=\"1\"", "<INFLUENCE ID=\"-1\"", 1)),
+        );
+
+        let skeleton = testdata::skeleton();
+        let loadAnimation = |text: String| {
+            let skeleton = skeleton.clone();
+            move |options: &LoaderOptions| {
+                loadXmlCoreAnimationFromText(&text, &skeleton, options).map(drop)
+            }
+        };
+        let animationXml = testdata::ANIMATION_XML;
+        assertReported(
+            "using the bone translation",
+            loadAnimation(animationXml.replacen(
+                "<TRANSLATION>1 2 3",
+                "<TRANSLATION>1e10 1e10 1e10",
+                1,
+            )),
+        );
+        assertReported(
+            "not normalised",
+            loadAnimation(animationXml.replacen("<ROTATION>0 0 0 1", "<ROTATION>0 0 0.5 1", 1)),
+        );
+        assertReported(
+            "not in the skeleton",
+            loadAnimation(animationXml.replacen("BONEID=\"1\"", "BONEID=\"7\"", 1)),
+        );
+    }
 }
@@ -199,8 +199,7 @@ impl CalMixer {
                     Rc::new(RefCell::new(CalAnimationCycle::new(pCoreAnimation.clone())));
 
                 // insert new animation into the tables
-                self.m_vectorAnimation
-                    .insert(id, CalAnimation::Cycle(pAnimationCycle.clone()));
+                self.m_vectorAnimation[id] = CalAnimation::Cycle(pAnimationCycle.clone());
                 self.m_listAnimationCycle.push(pAnimationCycle.clone());
 
                 // blend the animation
@@ -213,7 +212,7 @@ impl CalMixer {
 
                 // clear the animation cycle from the active vector if the target weight is zero
                 if weight == 0.0 {
-                    self.m_vectorAnimation.insert(id, CalAnimation::None);
+                    self.m_vectorAnimation[id] = CalAnimation::None;
                 }
 
                 true
@@ -259,7 +258,7 @@ impl CalMixer {
                 drop(pAnimationCycle);
 
                 // clear the animation cycle from the active vector
-                self.m_vectorAnimation.insert(id, CalAnimation::None);
+                self.m_vectorAnimation[id] = CalAnimation::None;
 
                 true
             }
@@ -270,6 +269,56 @@ impl CalMixer {
         }
     }
 
+    /*****************************************************************************/
+    /** Interpolates the weight of an animation cycle given by name.
+     *
+     * This function interpolates the weight of the animation cycle the core
+     * model has mapped to the given name, see `CalCoreModel::addAnimationName`.
+     *
+     * @param core_model The core model of the model the mixer animates.
+     * @param name The name of the animation cycle.
+     * @param weight The weight to interpolate the animation cycle to.
+     * @param delay The time in seconds until the new weight should be reached.
+     *
+     * @return One of the following values:
+     *         \li \b true if successful
+     *         \li \b false if no animation has the name or an error happened
+     *****************************************************************************/
+    pub fn blendCycleByName(
+        &mut self,
+        core_model: &CalCoreModel,
+        name: &str,
+        weight: f32,
+        delay: f32,
+    ) -> bool {
+        match core_model.getCoreAnimationId(name) {
+            Some(id) => self.blendCycle(core_model, id, weight, delay),
+            None => false,
+        }
+    }
+
+    /*****************************************************************************/
+    /** Fades an animation cycle given by name out.
+     *
+     * This function fades out the animation cycle the core model has mapped to
+     * the given name, see `CalCoreModel::addAnimationName`.
+     *
+     * @param core_model The core model of the model the mixer animates.
+     * @param name The name of the animation cycle.
+     * @param delay The time in seconds until the the animation cycle is
+     *              completely removed.
+     *
+     * @return One of the following values:
+     *         \li \b true if successful
+     *         \li \b false if no animation has the name or an error happened
+     *****************************************************************************/
+    pub fn clearCycleByName(&mut self, core_model: &CalCoreModel, name: &str, delay: f32) -> bool {
+        match core_model.getCoreAnimationId(name) {
+            Some(id) => self.clearCycle(id, delay),
+            None => false,
+        }
+    }
+
     // 946 cpp
     fn applyBoneAdjustments(&self, skeleton: &CalSkeleton) {
         let vectorBone = skeleton.getVectorBone();
@@ -557,3 +606,45 @@ impl CalMixerTrait for CalMixer {
         skeleton.calculateState();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testdata;
+    use crate::core::{LoaderOptions, loadCoreSkeletonFromReader};
+
+    #[test]
+    fn cycles_are_blended_by_name() {
+        let mut coreModel = CalCoreModel::default();
+        let skeleton = coreModel.getCoreSkeleton().clone();
+        let options = LoaderOptions::default();
+        loadCoreSkeletonFromReader(testdata::SKELETON_XML.as_bytes(), &skeleton, &options).unwrap();
+        let animation = testdata::animation(&skeleton);
+        let walkId = coreModel.addCoreAnimation(animation.clone());
+        let runId = coreModel.addCoreAnimation(animation);
+        coreModel.addAnimationName("walk", walkId).unwrap();
+        coreModel.addAnimationName("run", runId).unwrap();
+        let coreModel = Rc::new(RefCell::new(coreModel));
+        let model = Rc::new(RefCell::new(CalModel::new(coreModel.clone())));
+        let mut mixer = CalMixer::new(model);
+
+        let coreModel = coreModel.borrow();
+        assert!(mixer.blendCycleByName(&coreModel, "run", 1.0, 0.0));
+        assert!(!mixer.blendCycleByName(&coreModel, "jump", 1.0, 0.0));
+        assert_eq!(mixer.m_vectorAnimation.len(), 2);
+        assert!(matches!(
+            mixer.m_vectorAnimation[walkId],
+            CalAnimation::None
+        ));
+        assert!(matches!(
+            mixer.m_vectorAnimation[runId],
+            CalAnimation::Cycle(_)
+        ));
+        assert_eq!(mixer.m_listAnimationCycle.len(), 1);
+
+        assert!(mixer.clearCycleByName(&coreModel, "run", 0.5));
+        assert!(!mixer.clearCycleByName(&coreModel, "jump", 0.5));
+        assert_eq!(mixer.m_vectorAnimation.len(), 2);
+        assert!(matches!(mixer.m_vectorAnimation[runId], CalAnimation::None));
+    }
+}
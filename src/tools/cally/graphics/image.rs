@@ -131,6 +131,17 @@ pub fn get_texture(filename: &PathBuf, wrap: bool, filter: u32) -> Result<u32, s
     return load_texture(&rgba, wrap, filter);
 }
 
+pub fn get_texture_from_memory(
+    data: &[u8],
+    wrap: bool,
+    filter: u32,
+) -> Result<u32, std::io::Error> {
+    let rgba = image::load_from_memory(data)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    return load_texture(&rgba, wrap, filter);
+}
+
 pub fn get_sprite(filename: &PathBuf) -> Result<(u32, u32, u32), std::io::Error> {
     let rgba = if filename.extension().unwrap() == "raw" {
         load_raw(filename)
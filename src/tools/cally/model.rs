@@ -1,8 +1,9 @@
-use super::graphics::get_texture;
+use super::graphics::get_texture_from_memory;
 use cal3d::{CalMixer, CalModel, CalMorphTargetMixer, CalPhysique, CalRenderer, CalSpringSystem};
 use cgmath::Matrix4;
+use std::io::Read;
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{cell::RefCell, rc::Rc};
 
 pub const STATE_IDLE: usize = 0;
@@ -29,11 +30,8 @@ pub struct Model {
 #[derive(Debug)]
 pub(crate) enum ModelError {
     IoError(std::io::Error),
-    ScanfError(sscanf::Error),
-    ParseError(std::num::ParseFloatError),
     CoreError(cal3d::core::CoreError),
     CalModelError(cal3d::model::ModelError),
-    SyntaxError,
     FormatError(String),
 }
 
@@ -43,18 +41,6 @@ impl From<std::io::Error> for ModelError {
     }
 }
 
-impl From<sscanf::Error> for ModelError {
-    fn from(error: sscanf::Error) -> Self {
-        ModelError::ScanfError(error)
-    }
-}
-
-impl From<std::num::ParseFloatError> for ModelError {
-    fn from(error: std::num::ParseFloatError) -> Self {
-        ModelError::ParseError(error)
-    }
-}
-
 impl From<cal3d::core::CoreError> for ModelError {
     fn from(error: cal3d::core::CoreError) -> Self {
         ModelError::CoreError(error)
@@ -78,6 +64,12 @@ impl From<cal3d::core::LoaderError> for ModelError {
                 ModelError::FormatError(String::from("Invalid version number"))
             }
             cal3d::core::LoaderError::FormatError(e) => ModelError::FormatError(e),
+            cal3d::core::LoaderError::Cancelled => {
+                ModelError::FormatError(String::from("Loading was cancelled"))
+            }
+            located @ cal3d::core::LoaderError::Located(..) => {
+                ModelError::FormatError(located.to_string())
+            }
         }
     }
 }
@@ -94,123 +86,67 @@ impl Model {
         }
     }
 
-    fn readFile(
-        &mut self,
-        name: &str,
-        buff_reader: &mut std::io::BufReader<std::fs::File>,
-    ) -> Result<(), ModelError> {
-        use std::io::BufRead;
-
-        let mut line = 0;
-
-        let mut strPath = self.path.clone();
-
-        let mut animationCount = 0;
-
-        loop {
-            line += 1;
-            let mut buff = String::new();
-            buff_reader.read_line(&mut buff)?;
-            if buff.is_empty() {
-                break;
-            }
-            if buff.ends_with('\n') {
-                buff.pop();
-            }
-            println!("{}: '{}' ", buff.len(), buff);
-            if buff.len() <= 1 || buff.starts_with('#') {
-                println!("Skipping blank or comment line");
-                continue;
-            }
+    fn readFile(&mut self, name: &str) -> Result<(), ModelError> {
+        let description = if self.path.eq(&PathBuf::default()) {
+            self.calCoreModel
+                .borrow_mut()
+                .load_from_cfg(Path::new(name))?
+        } else {
+            // the assets are in the directory of the model rather than next to
+            // the description
+            let text = std::fs::read_to_string(name)?;
+            cal3d::core::cfg::loadCfgFromText(
+                &text,
+                &self.path,
+                &mut self.calCoreModel.borrow_mut(),
+            )?
+        };
 
-            let (key, value) = sscanf::scanf!(buff, r"{str}={str:/.+/}")?;
-
-            match key {
-                "scale" => {
-                    self.renderScale = value.parse::<f32>()?;
-                }
-                "path" => {
-                    if self.path.eq(&PathBuf::default()) {
-                        strPath = PathBuf::from(value);
-                    }
-                }
-                "skeleton" => {
-                    let mut filename = strPath.clone();
-                    filename.push(value);
-                    self.calCoreModel.borrow_mut().loadCoreSkeleton(&filename)?;
-                }
-                "animation" => {
-                    let mut filename = strPath.clone();
-                    filename.push(value);
-                    self.animationId[animationCount] = self
-                        .calCoreModel
-                        .borrow_mut()
-                        .loadCoreAnimation(&filename)?;
-                    animationCount += 1;
-                }
-                "mesh" => {
-                    let mut filename = strPath.clone();
-                    filename.push(value);
-                    self.calCoreModel.borrow_mut().loadCoreMesh(&filename)?;
-                }
-                "material" => {
-                    let mut filename = strPath.clone();
-                    filename.push(value);
-                    self.calCoreModel.borrow_mut().loadCoreMaterial(&filename)?;
-                }
-                _ => {
-                    println!("{name}({line}): Invalid syntax.");
-                    return Err(ModelError::SyntaxError);
-                }
-            }
+        self.renderScale = description.scale;
+        if self.path.eq(&PathBuf::default()) {
+            self.path = description.path;
+        }
+        for (animationCount, animationId) in description.animationIds.iter().enumerate() {
+            let Some(slot) = self.animationId.get_mut(animationCount) else {
+                return Err(ModelError::FormatError(format!(
+                    "{name}: too many animations"
+                )));
+            };
+            *slot = *animationId;
         }
         Ok(())
     }
 
     pub fn onInit(&mut self, filename: &str) -> Result<(), ModelError> {
-        use std::fs;
-        use std::io::BufReader;
         println!("Opening path: {filename}");
-        let mut buff_reader = BufReader::new(fs::File::open(filename)?);
-        self.readFile(filename, &mut buff_reader)?;
+        self.readFile(filename)?;
 
         let strPath = self.path.clone();
 
         let mut core_model = self.calCoreModel.borrow_mut();
-        let core_materials = core_model.getCoreMaterialsMut();
         // load all textures and store the opengl texture id in the corresponding map in the material
-        //   int materialId;
-
-        // get the core material
-
-        for pCoreMaterial in core_materials.iter_mut() {
-            let material_maps = pCoreMaterial.getMapsMut();
+        for materialId in 0..core_model.getCoreMaterials().len() {
             // loop through all maps of the core material
-
-            for map in material_maps.iter_mut() {
-                // get the filename of the texture
-
-                let mut filename = strPath.clone();
-                filename.push(&map.strFilename);
+            for mapId in 0..core_model.getCoreMaterials()[materialId].getMapCount() {
+                // read the texture through the file system of the model, so
+                // that it is found the same way as the model files
+                let mut data = Vec::new();
+                core_model
+                    .openMapFile(&strPath, materialId as i32, mapId)?
+                    .read_to_end(&mut data)?;
 
                 // load the texture from the file
-                let textureId = get_texture(&filename, false, 1)?;
+                let textureId = get_texture_from_memory(&data, false, 1)?;
 
                 // store the opengl texture id in the user data of the map
-                map.userData = textureId as i32;
+                core_model.getCoreMaterialsMut()[materialId]
+                    .setMapUserData(mapId, textureId as i32);
             }
         }
 
-        // make one material thread for each material
-        // NOTE: this is not the right way to do it, but this viewer can't do the right
-        // mapping without further information on the model etc.
-        for materialId in 0..core_materials.len() as i32 {
-            // create the a material thread
-            core_model.createCoreMaterialThread(materialId);
-
-            // initialize the material thread
-            core_model.setCoreMaterialId(materialId, 0, materialId);
-        }
+        // the model files have no material set information, so each material
+        // thread uses the material with the same id
+        core_model.createDefaultMaterialSet(0);
 
         // Drop mut borrow, as not-mut borrow must be possible below.
         drop(core_model);